@@ -0,0 +1,156 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use anyhow::Result;
+
+/// Skip first n elements from a vector
+pub fn skip_first_n(v: &[i16], len: usize) -> Vec<i16> {
+    if len >= v.len() {
+        Vec::new()
+    } else {
+        v[len..].to_vec()
+    }
+}
+
+/// Wczytaj plik PCM jako wektor i16
+pub fn read_pcm_i16(path: &str) -> Result<Vec<i16>> {
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    let samples: Vec<i16> = buffer
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    Ok(samples)
+}
+
+/// Zapisz wynikowy wektor i16 do pliku PCM
+pub fn write_pcm_i16(path: &str, samples: &[i16]) -> Result<()> {
+    let mut file = File::create(path)?;
+    for &sample in samples {
+        file.write_all(&sample.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Znajdź najlepsze przesunięcie A względem C (maksymalna korelacja)
+/// Computational complexity: O(samplerate*number_of_samples)
+pub fn find_best_lag(a: &[i16], c: &[i16], max_lag: usize) -> isize {
+    let mut best_lag = 0;
+    let mut best_corr = f64::MIN;
+
+    for lag in -(max_lag as isize)..=(max_lag as isize) {
+        let corr: f64 = a.iter().enumerate()
+            .filter_map(|(i, &a_val)| {
+                let j = i as isize + lag;
+                if j >= 0 && (j as usize) < c.len() {
+                    Some(a_val as f64 * c[j as usize] as f64)
+                } else {
+                    None
+                }
+            })
+            .sum();
+
+        if corr > best_corr {
+            best_corr = corr;
+            best_lag = lag;
+        }
+    }
+
+    best_lag
+}
+
+/// Seconds of the dubbed track `find_lag` compares
+const EXCERPT_SECONDS: usize = 120;
+
+/// Lag in `-max_lag..=max_lag` at which sample `i` of the dubbed track lines up with sample
+/// `i + lag` of the original, fast enough for a whole film. Only two minutes from the middle
+/// of the dubbed track are compared: first their energy envelope in blocks of `max_lag / 1000`
+/// samples, then sample by sample within one block either way of the best envelope match.
+///
+/// The sample correlation is the one `find_best_lag(dubbed, original, max_lag)` maximizes, but
+/// over the excerpt only and near the envelope match, so the two can disagree: e.g. when the
+/// excerpt is silent or repeats itself, or when the envelopes line up best at another lag
+/// than the samples do.
+pub fn find_lag(original: &[i16], dubbed: &[i16], max_lag: usize, sample_rate: usize) -> isize {
+    // about a thousand envelope blocks either way
+    let step = (max_lag / 1000).max(1);
+    let len = (EXCERPT_SECONDS * sample_rate).min(dubbed.len());
+    let start = (dubbed.len() - len) / 2 / step * step;
+    let excerpt = &dubbed[start..start + len];
+
+    let envelope = |samples: &[i16]| -> Vec<f64> {
+        let blocks: Vec<f64> = samples
+            .chunks(step)
+            .map(|block| block.iter().map(|&s| (s as f64).abs()).sum::<f64>() / block.len() as f64)
+            .collect();
+        let mean = blocks.iter().sum::<f64>() / blocks.len().max(1) as f64;
+        blocks.into_iter().map(|level| level - mean).collect()
+    };
+    let excerpt_envelope = envelope(excerpt);
+    let original_envelope = envelope(original);
+    let first_block = (start / step) as isize;
+    let max_blocks = (max_lag / step) as isize;
+    let coarse = (-max_blocks..=max_blocks)
+        .map(|lag| {
+            let corr: f64 = excerpt_envelope
+                .iter()
+                .enumerate()
+                .filter_map(|(i, &level)| {
+                    let j = first_block + i as isize + lag;
+                    (j >= 0).then(|| original_envelope.get(j as usize)).flatten().map(|&other| level * other)
+                })
+                .sum();
+            (lag, corr)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(lag, _)| lag * step as isize);
+
+    let max_lag = max_lag as isize;
+    let fine = (coarse - step as isize).max(-max_lag)..=(coarse + step as isize).min(max_lag);
+    fine.map(|lag| {
+        let corr: f64 = excerpt
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &sample)| {
+                let j = (start + i) as isize + lag;
+                (j >= 0).then(|| original.get(j as usize)).flatten().map(|&other| sample as f64 * other as f64)
+            })
+            .sum();
+        (lag, corr)
+    })
+    .max_by(|a, b| a.1.total_cmp(&b.1))
+    .map_or(coarse, |(lag, _)| lag)
+}
+
+/// Przesuń sygnał A względem C o `lag` próbek
+pub fn shift_signal(a: &[i16], lag: isize, target_len: usize) -> Vec<i16> {
+    if lag > 0 {
+        let lag = lag as usize;
+        let mut shifted = vec![0i16; lag];
+        shifted.extend_from_slice(&a[..target_len.saturating_sub(lag)]);
+        shifted.truncate(target_len);
+        shifted
+    } else {
+        let lag = (-lag) as usize;
+        let start = lag.min(a.len());
+        let mut shifted = a[start..].to_vec();
+        shifted.resize(target_len, 0);
+        shifted
+    }
+}
+
+/// Subtract the original from the dubbed track, leaving the lector. `lag` as returned by
+/// `find_lag`. The result is on the dubbed track's timeline and ends with the shorter track;
+/// where the original has no audio (before its start for a negative lag) it is silent.
+pub fn extract_lector(original: &[i16], dubbed: &[i16], lag: isize) -> Vec<i16> {
+    let len = dubbed.len().min((original.len() as isize - lag).max(0) as usize);
+    (0..len)
+        .map(|i| {
+            let j = i as isize + lag;
+            if j < 0 {
+                return 0;
+            }
+            dubbed[i] / 2 - original[j as usize] / 2
+        })
+        .collect()
+}
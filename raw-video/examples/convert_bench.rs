@@ -0,0 +1,62 @@
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use raw_video::{convert, ColorSpace, Converter, Options};
+
+/// Time the floating point reference against `Converter` on one and on all threads
+fn main() -> Result<(), String> {
+    let args: Vec<String> = env::args().collect();
+    let options = Options::parse(args[1..].iter().cloned(), &args[0], "")?;
+    let format = options.format;
+    let guess = ColorSpace::guess(&format);
+    let color = ColorSpace {
+        matrix: options.matrix.unwrap_or(guess.matrix),
+        range: options.range.unwrap_or(guess.range),
+        chroma_siting: options.chroma_siting.unwrap_or(guess.chroma_siting),
+    };
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+
+    // noise keeps the branch predictor honest in the clamps
+    let mut state = 1u32;
+    let frame: Vec<u8> = (0..format.frame_size())
+        .map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect();
+    let mut rgba = vec![0; format.width * format.height * 4];
+
+    println!("Converting {} frames, {}", format, color);
+    let reference = time(|| rgba = convert::to_rgba(&format, &color, &frame));
+    report("reference (f32)", reference, reference);
+    let converter = Converter::new(&format, &color);
+    report("fixed point", time(|| converter.to_rgba(&frame, &mut rgba)), reference);
+    if threads > 1 {
+        let converter = converter.with_threads(threads);
+        report(&format!("fixed point, {} threads", threads), time(|| converter.to_rgba(&frame, &mut rgba)), reference);
+    }
+    Ok(())
+}
+
+/// Average time per call over about a second
+fn time(mut convert: impl FnMut()) -> Duration {
+    convert();
+    let start = Instant::now();
+    let mut calls = 0;
+    while calls < 3 || start.elapsed() < Duration::from_secs(1) {
+        convert();
+        calls += 1;
+    }
+    start.elapsed() / calls
+}
+
+fn report(name: &str, per_frame: Duration, reference: Duration) {
+    println!(
+        "{:>28}: {:8.2} ms/frame, {:7.1} fps, {:5.1}x",
+        name,
+        per_frame.as_secs_f64() * 1000.0,
+        1.0 / per_frame.as_secs_f64(),
+        reference.as_secs_f64() / per_frame.as_secs_f64()
+    );
+}
@@ -0,0 +1,381 @@
+//! Playing an audio track through cpal, with its device as the playback clock
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::hint;
+use std::sync::atomic::{self, AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, StreamConfig};
+
+use crate::sync::Clock;
+
+/// A whole audio track in memory, interleaved samples in -1.0..1.0
+#[derive(Debug, Clone)]
+pub struct AudioTrack {
+    samples: Vec<f32>,
+    channels: usize,
+    sample_rate: u32,
+}
+
+impl AudioTrack {
+    /// Read a WAV file, recognised by its header, or else raw 16-bit little endian
+    /// PCM with `raw_rate` and `raw_channels`
+    pub fn open(path: impl AsRef<Path>, raw_rate: u32, raw_channels: u16) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        if file.fill_buf()?.starts_with(b"RIFF") {
+            return Self::read_wav(file);
+        }
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let samples = bytes.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / 32768.0).collect();
+        Ok(Self { samples, channels: raw_channels.max(1) as usize, sample_rate: raw_rate })
+    }
+
+    fn read_wav(file: impl Read) -> io::Result<Self> {
+        let invalid = |error: hound::Error| io::Error::new(io::ErrorKind::InvalidData, error.to_string());
+        let mut reader = hound::WavReader::new(file).map_err(invalid)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>().map_err(invalid)?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader.samples::<i32>().map(|sample| sample.map(|s| s as f32 * scale)).collect::<Result<_, _>>().map_err(invalid)?
+            }
+        };
+        Ok(Self { samples, channels: spec.channels.max(1) as usize, sample_rate: spec.sample_rate })
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / self.sample_rate as f64)
+    }
+
+    /// Samples per channel
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// Sample of `channel` at fractional frame `at`, interpolated linearly, silence
+    /// outside the track
+    fn sample(&self, at: f64, channel: usize) -> f32 {
+        let first = at.floor();
+        if first < 0.0 || first as usize >= self.frames() {
+            return 0.0;
+        }
+        let first = first as usize;
+        let second = (first + 1).min(self.frames() - 1);
+        let weight = (at - first as f64) as f32;
+        let a = self.samples[first * self.channels + channel];
+        let b = self.samples[second * self.channels + channel];
+        a + (b - a) * weight
+    }
+
+    /// One device frame of `out.len()` channels at `at`: channels map one to one, mono
+    /// goes to every channel, and a mono device gets the average
+    fn frame(&self, at: f64, out: &mut [f32]) {
+        if out.len() == 1 {
+            out[0] = (0..self.channels).map(|channel| self.sample(at, channel)).sum::<f32>() / self.channels as f32;
+            return;
+        }
+        for (channel, out) in out.iter_mut().enumerate() {
+            *out = match channel {
+                _ if self.channels == 1 => self.sample(at, 0),
+                channel if channel < self.channels => self.sample(at, channel),
+                _ => 0.0,
+            };
+        }
+    }
+}
+
+/// The last audio callback, published by it alone. `sequence` is odd while it writes,
+/// readers retry until they see the same even count before and after.
+#[derive(Debug)]
+struct LastCallback {
+    sequence: AtomicU64,
+    /// `AudioClock::generation` the callback played
+    generation: AtomicU64,
+    /// Track frames at the start of the callback and after it, as `f64` bits
+    start: AtomicU64,
+    end: AtomicU64,
+    /// When it ran, nanoseconds after `AudioClock::origin`
+    at: AtomicU64,
+    /// Nanoseconds until its first sample is heard
+    latency: AtomicU64,
+    /// Speed it played at, as `f64` bits
+    speed: AtomicU64,
+}
+
+/// What `LastCallback` holds
+#[derive(Debug, Clone, Copy)]
+struct Callback {
+    generation: u64,
+    start: f64,
+    end: f64,
+    at: u64,
+    latency: u64,
+    speed: f64,
+}
+
+impl LastCallback {
+    fn write(&self, callback: Callback) {
+        let sequence = self.sequence.load(Ordering::Relaxed);
+        self.sequence.store(sequence + 1, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+        self.generation.store(callback.generation, Ordering::Relaxed);
+        self.start.store(callback.start.to_bits(), Ordering::Relaxed);
+        self.end.store(callback.end.to_bits(), Ordering::Relaxed);
+        self.at.store(callback.at, Ordering::Relaxed);
+        self.latency.store(callback.latency, Ordering::Relaxed);
+        self.speed.store(callback.speed.to_bits(), Ordering::Relaxed);
+        self.sequence.store(sequence + 2, Ordering::Release);
+    }
+
+    fn read(&self) -> Callback {
+        loop {
+            let sequence = self.sequence.load(Ordering::Acquire);
+            let callback = Callback {
+                generation: self.generation.load(Ordering::Relaxed),
+                start: f64::from_bits(self.start.load(Ordering::Relaxed)),
+                end: f64::from_bits(self.end.load(Ordering::Relaxed)),
+                at: self.at.load(Ordering::Relaxed),
+                latency: self.latency.load(Ordering::Relaxed),
+                speed: f64::from_bits(self.speed.load(Ordering::Relaxed)),
+            };
+            atomic::fence(Ordering::Acquire);
+            if sequence.is_multiple_of(2) && self.sequence.load(Ordering::Relaxed) == sequence {
+                return callback;
+            }
+            hint::spin_loop();
+        }
+    }
+}
+
+/// `Clock` following what the audio device plays. The audio callback never waits on
+/// it: seeks, pauses and speed changes set where playback restarts and then bump
+/// `generation`, which the callback picks up on its next run.
+#[derive(Debug)]
+pub struct AudioClock {
+    sample_rate: u32,
+    origin: Instant,
+    /// Track frame to restart from, as `f64` bits; also the earliest position to
+    /// report while the device has not caught up with it
+    restart: AtomicU64,
+    generation: AtomicU64,
+    paused: AtomicBool,
+    /// Track frames played per frame of real time, as `f64` bits; the pitch changes
+    /// with it
+    speed: AtomicU64,
+    last_callback: LastCallback,
+    /// Serialises seeks, pauses and speed changes; never taken by the callback
+    control: Mutex<()>,
+}
+
+impl AudioClock {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            origin: Instant::now(),
+            restart: AtomicU64::new(0f64.to_bits()),
+            generation: AtomicU64::new(0),
+            paused: AtomicBool::new(true),
+            speed: AtomicU64::new(1f64.to_bits()),
+            last_callback: LastCallback {
+                sequence: AtomicU64::new(0),
+                // no callback yet
+                generation: AtomicU64::new(u64::MAX),
+                start: AtomicU64::new(0),
+                end: AtomicU64::new(0),
+                at: AtomicU64::new(0),
+                latency: AtomicU64::new(0),
+                speed: AtomicU64::new(1f64.to_bits()),
+            },
+            control: Mutex::new(()),
+        }
+    }
+
+    fn seconds(&self, frames: f64) -> Duration {
+        Duration::from_secs_f64((frames / self.sample_rate as f64).max(0.0))
+    }
+
+    fn nanos(&self) -> u64 {
+        self.origin.elapsed().as_nanos() as u64
+    }
+
+    /// Have the callback go on from `position`, after `change` has been made
+    fn restart(&self, position: Duration, change: impl FnOnce()) {
+        self.restart.store((position.as_secs_f64() * self.sample_rate as f64).to_bits(), Ordering::Relaxed);
+        change();
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl Clock for AudioClock {
+    fn position(&self) -> Duration {
+        let generation = self.generation.load(Ordering::Acquire);
+        let floor = self.seconds(f64::from_bits(self.restart.load(Ordering::Relaxed)));
+        let callback = self.last_callback.read();
+        if self.paused.load(Ordering::Relaxed) || callback.generation != generation {
+            return floor;
+        }
+        // at most up to what was written, should the callbacks stall
+        let elapsed = Duration::from_nanos(self.nanos().saturating_sub(callback.at));
+        let played = (self.seconds(callback.start) + elapsed.mul_f64(callback.speed)).min(self.seconds(callback.end));
+        played.saturating_sub(Duration::from_nanos(callback.latency).mul_f64(callback.speed)).max(floor)
+    }
+
+    fn seek(&self, position: Duration) {
+        let _control = self.control.lock().unwrap();
+        self.restart(position, || {});
+    }
+
+    fn set_paused(&self, paused: bool) {
+        let _control = self.control.lock().unwrap();
+        if self.paused.load(Ordering::Relaxed) == paused {
+            return;
+        }
+        // resume with what was heard last, not with what was already buffered
+        self.restart(self.position(), || self.paused.store(paused, Ordering::Relaxed));
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    fn set_speed(&self, speed: f64) {
+        let _control = self.control.lock().unwrap();
+        // the buffered samples were made at the old speed, carry on from what was heard
+        self.restart(self.position(), || self.speed.store(speed.to_bits(), Ordering::Relaxed));
+    }
+}
+
+/// An output stream playing an `AudioTrack`. The stream stops when this is dropped;
+/// it may have to stay on the thread that started it.
+pub struct AudioPlayer {
+    _stream: cpal::Stream,
+    clock: Arc<AudioClock>,
+    error: Arc<Mutex<Option<String>>>,
+    description: String,
+}
+
+impl AudioPlayer {
+    /// Play `track` on the default output device, at the track's sample rate if the
+    /// device takes it and resampled otherwise. Paused until the clock is started.
+    pub fn start(track: AudioTrack) -> Result<Self, Box<dyn Error>> {
+        let host = cpal::default_host();
+        let device = host.default_output_device().ok_or("No audio output device available")?;
+        let default = device.default_output_config()?;
+        let rate = cpal::SampleRate(track.sample_rate);
+        let config = device
+            .supported_output_configs()?
+            .filter(|range| range.channels() == default.channels() && range.sample_format() == default.sample_format())
+            .find_map(|range| range.try_with_sample_rate(rate))
+            .unwrap_or(default);
+        let description = format!(
+            "{} Hz {} channels on {}, played at {} Hz {} channels {}",
+            track.sample_rate,
+            track.channels,
+            device.name()?,
+            config.sample_rate().0,
+            config.channels(),
+            config.sample_format()
+        );
+
+        let clock = Arc::new(AudioClock::new(track.sample_rate));
+        let error = Arc::new(Mutex::new(None));
+        let stream_config = config.config();
+        let track = Arc::new(track);
+        let stream = match config.sample_format() {
+            SampleFormat::I8 => build_stream::<i8>(&device, &stream_config, track, &clock, &error),
+            SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, track, &clock, &error),
+            SampleFormat::I32 => build_stream::<i32>(&device, &stream_config, track, &clock, &error),
+            SampleFormat::U8 => build_stream::<u8>(&device, &stream_config, track, &clock, &error),
+            SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, track, &clock, &error),
+            SampleFormat::U32 => build_stream::<u32>(&device, &stream_config, track, &clock, &error),
+            SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, track, &clock, &error),
+            SampleFormat::F64 => build_stream::<f64>(&device, &stream_config, track, &clock, &error),
+            other => return Err(format!("Unsupported sample format {}", other).into()),
+        }?;
+        stream.play()?;
+        Ok(Self { _stream: stream, clock, error, description })
+    }
+
+    /// The clock to present video against
+    pub fn clock(&self) -> Arc<AudioClock> {
+        self.clock.clone()
+    }
+
+    /// The last error the stream reported, if any since the last call
+    pub fn take_error(&self) -> Option<String> {
+        self.error.lock().unwrap().take()
+    }
+
+    /// Track and device formats, for the console
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    track: Arc<AudioTrack>,
+    clock: &Arc<AudioClock>,
+    error: &Arc<Mutex<Option<String>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    // track frames per device frame
+    let step = track.sample_rate as f64 / config.sample_rate.0 as f64;
+    let clock = clock.clone();
+    let error = error.clone();
+    let mut frame = vec![0.0; channels];
+    // track frame of the next sample to write, and the generation it belongs to
+    let mut cursor = 0.0;
+    let mut generation = u64::MAX;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+            let timestamp = info.timestamp();
+            let latency = timestamp.playback.duration_since(&timestamp.callback).unwrap_or_default();
+            let current = clock.generation.load(Ordering::Acquire);
+            if clock.paused.load(Ordering::Relaxed) {
+                data.fill(T::EQUILIBRIUM);
+                return;
+            }
+            if current != generation {
+                generation = current;
+                cursor = f64::from_bits(clock.restart.load(Ordering::Relaxed));
+            }
+            let speed = f64::from_bits(clock.speed.load(Ordering::Relaxed));
+            let start = cursor;
+            for out in data.chunks_mut(channels) {
+                track.frame(cursor, &mut frame);
+                for (out, &sample) in out.iter_mut().zip(&frame) {
+                    *out = T::from_sample(sample);
+                }
+                cursor += step * speed;
+            }
+            clock.last_callback.write(Callback {
+                generation,
+                start,
+                end: cursor,
+                at: clock.nanos(),
+                latency: latency.as_nanos() as u64,
+                speed,
+            });
+        },
+        move |stream_error| *error.lock().unwrap() = Some(stream_error.to_string()),
+        None,
+    )
+}
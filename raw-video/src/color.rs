@@ -0,0 +1,257 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::VideoFormat;
+
+/// YUV to RGB matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Matrix {
+    Bt601,
+    Bt709,
+    Bt2020,
+}
+
+impl Matrix {
+    /// Luma weights of red and blue
+    fn kr_kb(self) -> (f32, f32) {
+        match self {
+            Matrix::Bt601 => (0.299, 0.114),
+            Matrix::Bt709 => (0.2126, 0.0722),
+            Matrix::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+impl FromStr for Matrix {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "601" | "bt601" | "bt.601" | "smpte170m" | "bt470bg" => Ok(Matrix::Bt601),
+            "709" | "bt709" | "bt.709" => Ok(Matrix::Bt709),
+            "2020" | "bt2020" | "bt.2020" | "bt2020nc" => Ok(Matrix::Bt2020),
+            _ => Err(format!("Unknown color matrix {}, expected 601, 709 or 2020", name)),
+        }
+    }
+}
+
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Matrix::Bt601 => "BT.601",
+            Matrix::Bt709 => "BT.709",
+            Matrix::Bt2020 => "BT.2020",
+        })
+    }
+}
+
+/// Which codes black and white get
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Range {
+    /// Luma 16-235, chroma 16-240 (at 8 bits), also called TV or MPEG range
+    Limited,
+    /// Every code is used, also called PC or JPEG range
+    Full,
+}
+
+impl FromStr for Range {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "limited" | "tv" | "mpeg" => Ok(Range::Limited),
+            "full" | "pc" | "jpeg" => Ok(Range::Full),
+            _ => Err(format!("Unknown color range {}, expected limited or full", name)),
+        }
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Range::Limited => "limited",
+            Range::Full => "full",
+        })
+    }
+}
+
+/// Where subsampled chroma sits relative to the luma samples, with ffmpeg's names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSiting {
+    /// Co-sited with the left luma sample, centered vertically (MPEG-2, H.264)
+    Left,
+    /// Centered between the luma samples (MPEG-1, JPEG)
+    Center,
+    /// Co-sited with the top left luma sample (PAL DV, BT.2020 UHD)
+    TopLeft,
+}
+
+impl ChromaSiting {
+    /// Offset of the first chroma sample from the first luma sample, in chroma
+    /// samples, horizontally and vertically
+    pub(crate) fn offset(self) -> (f32, f32) {
+        match self {
+            ChromaSiting::Left => (0.0, 0.25),
+            ChromaSiting::Center => (0.25, 0.25),
+            ChromaSiting::TopLeft => (0.0, 0.0),
+        }
+    }
+}
+
+impl FromStr for ChromaSiting {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "left" | "mpeg2" => Ok(ChromaSiting::Left),
+            "center" | "jpeg" => Ok(ChromaSiting::Center),
+            "topleft" | "top-left" | "dv" => Ok(ChromaSiting::TopLeft),
+            _ => Err(format!("Unknown chroma location {}, expected left, center or topleft", name)),
+        }
+    }
+}
+
+impl fmt::Display for ChromaSiting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChromaSiting::Left => "left",
+            ChromaSiting::Center => "center",
+            ChromaSiting::TopLeft => "topleft",
+        })
+    }
+}
+
+/// How YUV samples turn into RGB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorSpace {
+    pub matrix: Matrix,
+    pub range: Range,
+    pub chroma_siting: ChromaSiting,
+}
+
+impl ColorSpace {
+    /// What an untagged stream most likely is: BT.709 from 720p up and BT.601 below,
+    /// like ffmpeg-based players guess, limited range, MPEG-2 chroma siting
+    pub fn guess(format: &VideoFormat) -> Self {
+        let hd = format.width >= 1280 || format.height > 576;
+        Self {
+            matrix: if hd { Matrix::Bt709 } else { Matrix::Bt601 },
+            range: Range::Limited,
+            chroma_siting: ChromaSiting::Left,
+        }
+    }
+
+    /// Conversion factors for samples on the 8-bit scale
+    pub(crate) fn coefficients(&self) -> Coefficients {
+        let (kr, kb) = self.matrix.kr_kb();
+        let kg = 1.0 - kr - kb;
+        let (black, luma_scale, chroma_scale) = match self.range {
+            Range::Limited => (16.0, 255.0 / 219.0, 255.0 / 224.0),
+            Range::Full => (0.0, 1.0, 1.0),
+        };
+        Coefficients {
+            black,
+            luma_scale,
+            r_v: 2.0 * (1.0 - kr) * chroma_scale,
+            g_u: 2.0 * (1.0 - kb) * kb / kg * chroma_scale,
+            g_v: 2.0 * (1.0 - kr) * kr / kg * chroma_scale,
+            b_u: 2.0 * (1.0 - kb) * chroma_scale,
+        }
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} range, chroma {}", self.matrix, self.range, self.chroma_siting)
+    }
+}
+
+/// `ColorSpace` as multipliers for samples on the 8-bit scale
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Coefficients {
+    pub(crate) black: f32,
+    pub(crate) luma_scale: f32,
+    pub(crate) r_v: f32,
+    pub(crate) g_u: f32,
+    pub(crate) g_v: f32,
+    pub(crate) b_u: f32,
+}
+
+impl Coefficients {
+    pub(crate) fn to_rgb(self, y: f32, u: f32, v: f32) -> [u8; 3] {
+        let y = (y - self.black) * self.luma_scale;
+        let (u, v) = (u - 128.0, v - 128.0);
+        let r = y + self.r_v * v;
+        let g = y - self.g_u * u - self.g_v * v;
+        let b = y + self.b_u * u;
+        [r, g, b].map(|c| c.round().clamp(0.0, 255.0) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameRate, PixelFormat};
+
+    fn format(width: usize, height: usize) -> VideoFormat {
+        VideoFormat { width, height, pixel_format: PixelFormat::Yuv420p, frame_rate: FrameRate::new(25, 1) }
+    }
+
+    fn rgb(color: ColorSpace, yuv: [u8; 3]) -> [u8; 3] {
+        color.coefficients().to_rgb(yuv[0] as f32, yuv[1] as f32, yuv[2] as f32)
+    }
+
+    fn close(actual: [u8; 3], expected: [u8; 3]) -> bool {
+        actual.iter().zip(expected).all(|(&a, e)| a.abs_diff(e) <= 1)
+    }
+
+    #[test]
+    fn guess_by_size() {
+        for (width, height, matrix) in [
+            (720, 576, Matrix::Bt601),
+            (720, 480, Matrix::Bt601),
+            (1024, 576, Matrix::Bt601),
+            (1280, 720, Matrix::Bt709),
+            (960, 720, Matrix::Bt709),
+            (1920, 1080, Matrix::Bt709),
+            (3840, 2160, Matrix::Bt709),
+        ] {
+            let guess = ColorSpace::guess(&format(width, height));
+            assert_eq!(guess.matrix, matrix, "{}x{}", width, height);
+            assert_eq!((guess.range, guess.chroma_siting), (Range::Limited, ChromaSiting::Left));
+        }
+    }
+
+    #[test]
+    fn chroma_offsets() {
+        assert_eq!(ChromaSiting::Left.offset(), (0.0, 0.25));
+        assert_eq!(ChromaSiting::Center.offset(), (0.25, 0.25));
+        assert_eq!(ChromaSiting::TopLeft.offset(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn bt709_limited_range() {
+        let color = ColorSpace { matrix: Matrix::Bt709, range: Range::Limited, chroma_siting: ChromaSiting::Left };
+        assert_eq!(rgb(color, [16, 128, 128]), [0, 0, 0]);
+        assert_eq!(rgb(color, [235, 128, 128]), [255, 255, 255]);
+        assert_eq!(rgb(color, [126, 128, 128]), [128, 128, 128]);
+        // BT.709 75% bars, the codes of the reference tables
+        assert!(close(rgb(color, [51, 109, 212]), [191, 0, 0]), "{:?}", rgb(color, [51, 109, 212]));
+        assert!(close(rgb(color, [133, 63, 52]), [0, 191, 0]), "{:?}", rgb(color, [133, 63, 52]));
+        assert!(close(rgb(color, [28, 212, 120]), [0, 0, 191]), "{:?}", rgb(color, [28, 212, 120]));
+        // the same codes are another color in BT.601
+        let bt601 = ColorSpace { matrix: Matrix::Bt601, ..color };
+        assert!(!close(rgb(bt601, [133, 63, 52]), [0, 191, 0]));
+        // beyond the nominal range clamps
+        assert_eq!(rgb(color, [0, 128, 128]), [0, 0, 0]);
+        assert_eq!(rgb(color, [255, 128, 128]), [255, 255, 255]);
+    }
+
+    #[test]
+    fn full_range_is_not_stretched() {
+        let color = ColorSpace { matrix: Matrix::Bt709, range: Range::Full, chroma_siting: ChromaSiting::Left };
+        assert_eq!(rgb(color, [0, 128, 128]), [0, 0, 0]);
+        assert_eq!(rgb(color, [255, 128, 128]), [255, 255, 255]);
+        assert_eq!(rgb(color, [16, 128, 128]), [16, 16, 16]);
+    }
+}
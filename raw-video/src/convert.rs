@@ -0,0 +1,151 @@
+//! Frame to RGB conversion for every `PixelFormat`. `convert_frame` is the floating
+//! point reference, `Converter` the fast version for playback.
+
+mod fast;
+
+pub use fast::Converter;
+
+use crate::{ColorSpace, PixelFormat, VideoFormat};
+
+/// Convert a frame, handing each pixel to `write` with its index in the frame
+/// (`y * width + x`) and its R, G and B. `color` only matters for YUV formats.
+pub fn convert_frame(format: &VideoFormat, color: &ColorSpace, frame: &[u8], mut write: impl FnMut(usize, [u8; 3])) {
+    let planes = format.planes(frame);
+    let strides: Vec<usize> = format.plane_layouts().iter().map(|layout| layout.stride).collect();
+    let (width, height) = (format.width, format.height);
+    match format.pixel_format {
+        PixelFormat::Rgb24 => {
+            for y in 0..height {
+                for x in 0..width {
+                    let at = y * strides[0] + x * 3;
+                    write(y * width + x, [planes[0][at], planes[0][at + 1], planes[0][at + 2]]);
+                }
+            }
+        }
+        PixelFormat::Bgra => {
+            for y in 0..height {
+                for x in 0..width {
+                    let at = y * strides[0] + x * 4;
+                    write(y * width + x, [planes[0][at + 2], planes[0][at + 1], planes[0][at]]);
+                }
+            }
+        }
+        PixelFormat::Yuv420p | PixelFormat::Yuv422p | PixelFormat::Yuv444p => convert_yuv(
+            format,
+            color,
+            |x, y| planes[0][y * strides[0] + x] as f32,
+            |x, y| {
+                let at = y * strides[1] + x;
+                (planes[1][at] as f32, planes[2][at] as f32)
+            },
+            write,
+        ),
+        PixelFormat::Nv12 => convert_yuv(
+            format,
+            color,
+            |x, y| planes[0][y * strides[0] + x] as f32,
+            |x, y| {
+                let at = y * strides[1] + x * 2;
+                (planes[1][at] as f32, planes[1][at + 1] as f32)
+            },
+            write,
+        ),
+        // 10 bits down to the 8-bit scale
+        PixelFormat::Yuv420p10 => convert_yuv(
+            format,
+            color,
+            |x, y| sample10(planes[0], y * strides[0] + x * 2) / 4.0,
+            |x, y| {
+                let at = y * strides[1] + x * 2;
+                (sample10(planes[1], at) / 4.0, sample10(planes[2], at) / 4.0)
+            },
+            write,
+        ),
+        // 10 bits in the high bits, so on a 16-bit scale
+        PixelFormat::P010 => convert_yuv(
+            format,
+            color,
+            |x, y| sample16(planes[0], y * strides[0] + x * 2) / 256.0,
+            |x, y| {
+                let at = y * strides[1] + x * 4;
+                (sample16(planes[1], at) / 256.0, sample16(planes[1], at + 2) / 256.0)
+            },
+            write,
+        ),
+    }
+}
+
+/// RGBA bytes, as `pixels` wants them, with `convert_frame`
+pub fn to_rgba(format: &VideoFormat, color: &ColorSpace, frame: &[u8]) -> Vec<u8> {
+    let mut rgba = vec![255; format.width * format.height * 4];
+    convert_frame(format, color, frame, |index, rgb| rgba[index * 4..index * 4 + 3].copy_from_slice(&rgb));
+    rgba
+}
+
+/// `0x00RRGGBB` words, as `softbuffer` wants them, with `convert_frame`
+pub fn to_xrgb(format: &VideoFormat, color: &ColorSpace, frame: &[u8]) -> Vec<u32> {
+    let mut xrgb = vec![0; format.width * format.height];
+    convert_frame(format, color, frame, |index, [r, g, b]| {
+        xrgb[index] = ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+    });
+    xrgb
+}
+
+/// Where a luma sample falls between two chroma samples along one axis
+#[derive(Debug, Clone, Copy)]
+struct Tap {
+    first: usize,
+    second: usize,
+    /// Weight of `second`
+    weight: f32,
+}
+
+/// Chroma taps for each of `luma_len` luma samples, with chroma subsampled by
+/// `1 << shift` and its first sample `offset` chroma samples past the first luma sample
+fn taps(luma_len: usize, shift: u32, offset: f32) -> Vec<Tap> {
+    let chroma_len = luma_len.div_ceil(1 << shift);
+    (0..luma_len)
+        .map(|i| {
+            if shift == 0 {
+                return Tap { first: i, second: i, weight: 0.0 };
+            }
+            let at = (i as f32 / (1 << shift) as f32 - offset).clamp(0.0, (chroma_len - 1) as f32);
+            let first = at as usize;
+            Tap { first, second: (first + 1).min(chroma_len - 1), weight: at - first as f32 }
+        })
+        .collect()
+}
+
+/// YUV with chroma interpolated bilinearly at the luma positions. `luma` and `chroma`
+/// give samples on the 8-bit scale at luma and chroma coordinates.
+fn convert_yuv(
+    format: &VideoFormat,
+    color: &ColorSpace,
+    luma: impl Fn(usize, usize) -> f32,
+    chroma: impl Fn(usize, usize) -> (f32, f32),
+    mut write: impl FnMut(usize, [u8; 3]),
+) {
+    let coefficients = color.coefficients();
+    let (shift_x, shift_y) = format.pixel_format.chroma_shift();
+    let (offset_x, offset_y) = color.chroma_siting.offset();
+    let columns = taps(format.width, shift_x, offset_x);
+    let rows = taps(format.height, shift_y, offset_y);
+    let lerp = |a: (f32, f32), b: (f32, f32), t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+    for (y, row) in rows.iter().enumerate() {
+        for (x, column) in columns.iter().enumerate() {
+            let top = lerp(chroma(column.first, row.first), chroma(column.second, row.first), column.weight);
+            let bottom = lerp(chroma(column.first, row.second), chroma(column.second, row.second), column.weight);
+            let (u, v) = lerp(top, bottom, row.weight);
+            write(y * format.width + x, coefficients.to_rgb(luma(x, y), u, v));
+        }
+    }
+}
+
+fn sample16(plane: &[u8], at: usize) -> f32 {
+    u16::from_le_bytes([plane[at], plane[at + 1]]) as f32
+}
+
+/// 10 bits in the low bits of a 16-bit word; the high bits should be clear and are ignored
+fn sample10(plane: &[u8], at: usize) -> f32 {
+    (u16::from_le_bytes([plane[at], plane[at + 1]]) & 0x3ff) as f32
+}
@@ -0,0 +1,308 @@
+//! Fixed-point conversion, see `Converter`
+
+use std::thread;
+
+use super::{taps, Tap};
+use crate::{ColorSpace, PixelFormat, VideoFormat};
+
+/// Samples are carried as `i16` on the 8-bit scale with this many fraction bits,
+/// which keeps 10-bit input exact
+const SAMPLE_BITS: u32 = 6;
+/// Fraction bits of the chroma interpolation weights
+const WEIGHT_BITS: u32 = 8;
+/// Fraction bits of the `i16` matrix coefficients, as many as the largest (BT.2020
+/// blue in limited range, about 2.14) leaves room for
+const COEFFICIENT_BITS: u32 = 13;
+/// Pixels per step of the inner loop. Fixed size arrays of this many lanes are what
+/// the compiler turns into vector instructions on any target.
+const LANES: usize = 8;
+
+/// `Tap` with an integer weight of `WEIGHT_BITS` fraction bits
+#[derive(Debug, Clone, Copy)]
+struct FixedTap {
+    first: usize,
+    second: usize,
+    weight: i32,
+}
+
+impl From<Tap> for FixedTap {
+    fn from(tap: Tap) -> Self {
+        let weight = (tap.weight * (1 << WEIGHT_BITS) as f32).round() as i32;
+        Self { first: tap.first, second: tap.second, weight }
+    }
+}
+
+/// `Coefficients` with `COEFFICIENT_BITS` fraction bits, for samples with
+/// `SAMPLE_BITS` fraction bits
+#[derive(Debug, Clone, Copy)]
+struct FixedMatrix {
+    black: i16,
+    luma: i16,
+    r_v: i16,
+    g_u: i16,
+    g_v: i16,
+    b_u: i16,
+}
+
+impl FixedMatrix {
+    fn new(color: &ColorSpace) -> Self {
+        let coefficients = color.coefficients();
+        let fixed = |value: f32| (value * (1 << COEFFICIENT_BITS) as f32).round() as i16;
+        Self {
+            black: (coefficients.black * (1 << SAMPLE_BITS) as f32) as i16,
+            luma: fixed(coefficients.luma_scale),
+            r_v: fixed(coefficients.r_v),
+            g_u: fixed(coefficients.g_u),
+            g_v: fixed(coefficients.g_v),
+            b_u: fixed(coefficients.b_u),
+        }
+    }
+
+    /// R, G and B of one pixel. Products of 16-bit values summed in 32 bits, which
+    /// vector units do in one instruction.
+    #[inline(always)]
+    fn pixel(&self, y: i16, u: i16, v: i16) -> (u8, u8, u8) {
+        const SHIFT: u32 = SAMPLE_BITS + COEFFICIENT_BITS;
+        const HALF: i32 = 1 << (SHIFT - 1);
+        const CENTER: i16 = 128 << SAMPLE_BITS;
+        let luma = (y - self.black) as i32 * self.luma as i32 + HALF;
+        let (u, v) = ((u - CENTER) as i32, (v - CENTER) as i32);
+        let r = (luma + self.r_v as i32 * v) >> SHIFT;
+        let g = (luma - self.g_u as i32 * u - self.g_v as i32 * v) >> SHIFT;
+        let b = (luma + self.b_u as i32 * u) >> SHIFT;
+        (r.clamp(0, 255) as u8, g.clamp(0, 255) as u8, b.clamp(0, 255) as u8)
+    }
+}
+
+/// The last two chroma rows, upsampled to the frame width, so that the luma rows
+/// sharing them (both rows of a 4:2:0 pair and their neighbours) only load and
+/// interpolate them once
+struct ChromaRows {
+    index: [Option<usize>; 2],
+    u: [Vec<i32>; 2],
+    v: [Vec<i32>; 2],
+}
+
+impl ChromaRows {
+    fn new(width: usize) -> Self {
+        Self { index: [None; 2], u: [vec![0; width], vec![0; width]], v: [vec![0; width], vec![0; width]] }
+    }
+
+    /// Slot holding chroma row `index`, loaded with `load` unless cached; the row in
+    /// slot `keep` stays
+    fn slot(
+        &mut self,
+        index: usize,
+        keep: Option<usize>,
+        load: &mut impl FnMut(usize, &mut [i32], &mut [i32]),
+    ) -> usize {
+        if let Some(slot) = self.index.iter().position(|&cached| cached == Some(index)) {
+            return slot;
+        }
+        let slot = if keep == Some(0) { 1 } else { 0 };
+        load(index, &mut self.u[slot], &mut self.v[slot]);
+        self.index[slot] = Some(index);
+        slot
+    }
+}
+
+/// Converts frames of one format to RGB with integer arithmetic, on several threads
+/// if asked to. Stays within ±1 of `convert_frame`.
+#[derive(Debug, Clone)]
+pub struct Converter {
+    format: VideoFormat,
+    columns: Vec<FixedTap>,
+    rows: Vec<FixedTap>,
+    matrix: FixedMatrix,
+    threads: usize,
+}
+
+impl Converter {
+    pub fn new(format: &VideoFormat, color: &ColorSpace) -> Self {
+        let (shift_x, shift_y) = format.pixel_format.chroma_shift();
+        let (offset_x, offset_y) = color.chroma_siting.offset();
+        Self {
+            format: *format,
+            columns: taps(format.width, shift_x, offset_x).into_iter().map(FixedTap::from).collect(),
+            rows: taps(format.height, shift_y, offset_y).into_iter().map(FixedTap::from).collect(),
+            matrix: FixedMatrix::new(color),
+            threads: 1,
+        }
+    }
+
+    /// Convert bands of rows on `threads` threads, one by default
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn format(&self) -> &VideoFormat {
+        &self.format
+    }
+
+    /// Convert into RGBA bytes, as `pixels` wants them, `width * height * 4` of them
+    pub fn to_rgba(&self, frame: &[u8], rgba: &mut [u8]) {
+        let (pixels, _) = rgba.as_chunks_mut::<4>();
+        self.convert(frame, pixels, |r, g, b| [r, g, b, 255]);
+    }
+
+    /// Convert into `0x00RRGGBB` words, as `softbuffer` wants them
+    pub fn to_xrgb(&self, frame: &[u8], xrgb: &mut [u32]) {
+        self.convert(frame, xrgb, |r, g, b| ((r as u32) << 16) | ((g as u32) << 8) | b as u32);
+    }
+
+    fn convert<P: Send>(&self, frame: &[u8], out: &mut [P], pack: impl Fn(u8, u8, u8) -> P + Sync) {
+        let (width, height) = (self.format.width, self.format.height);
+        let out = &mut out[..width * height];
+        if self.threads == 1 {
+            self.convert_rows(frame, 0, out, &pack);
+            return;
+        }
+        let band_rows = height.div_ceil(self.threads);
+        thread::scope(|scope| {
+            for (band, rows) in out.chunks_mut(band_rows * width).enumerate() {
+                let pack = &pack;
+                scope.spawn(move || self.convert_rows(frame, band * band_rows, rows, pack));
+            }
+        });
+    }
+
+    /// Convert the rows from `first_row` on that fit in `out`
+    fn convert_rows<P>(&self, frame: &[u8], first_row: usize, out: &mut [P], pack: &impl Fn(u8, u8, u8) -> P) {
+        let planes = self.format.planes(frame);
+        let strides: Vec<usize> = self.format.plane_layouts().iter().map(|layout| layout.stride).collect();
+        let width = self.format.width;
+        let rows = (first_row..).zip(out.chunks_mut(width));
+        match self.format.pixel_format {
+            PixelFormat::Rgb24 => {
+                for (y, out) in rows {
+                    let row = &planes[0][y * strides[0]..][..width * 3];
+                    for (pixel, rgb) in out.iter_mut().zip(row.chunks_exact(3)) {
+                        *pixel = pack(rgb[0], rgb[1], rgb[2]);
+                    }
+                }
+            }
+            PixelFormat::Bgra => {
+                for (y, out) in rows {
+                    let row = &planes[0][y * strides[0]..][..width * 4];
+                    for (pixel, bgra) in out.iter_mut().zip(row.chunks_exact(4)) {
+                        *pixel = pack(bgra[2], bgra[1], bgra[0]);
+                    }
+                }
+            }
+            _ => {
+                let chroma_width = width.div_ceil(1 << self.format.pixel_format.chroma_shift().0);
+                let mut luma = vec![0; width];
+                let (mut u, mut v) = (vec![0; width], vec![0; width]);
+                let (mut native_u, mut native_v) = (vec![0; chroma_width], vec![0; chroma_width]);
+                let mut load = |index: usize, u: &mut [i32], v: &mut [i32]| {
+                    self.load_chroma(&planes, &strides, index, &mut native_u, &mut native_v);
+                    self.upsample(&native_u, u);
+                    self.upsample(&native_v, v);
+                };
+                let mut chroma = ChromaRows::new(width);
+                for (y, out) in rows {
+                    let tap = self.rows[y];
+                    let top = chroma.slot(tap.first, None, &mut load);
+                    let bottom = chroma.slot(tap.second, Some(top), &mut load);
+                    mix(&chroma.u[top], &chroma.u[bottom], tap.weight, &mut u);
+                    mix(&chroma.v[top], &chroma.v[bottom], tap.weight, &mut v);
+                    self.load_luma(&planes, &strides, y, &mut luma);
+                    self.pack_row(&luma, &u, &v, out, pack);
+                }
+            }
+        }
+    }
+
+    /// Luma of row `y` on the 8-bit scale with `SAMPLE_BITS` fraction bits
+    fn load_luma(&self, planes: &[&[u8]], strides: &[usize], y: usize, luma: &mut [i16]) {
+        let row = &planes[0][y * strides[0]..][..strides[0]];
+        match self.format.pixel_format {
+            PixelFormat::P010 => samples16(row, 2, luma, |sample| (sample >> (8 - SAMPLE_BITS)) as i16),
+            PixelFormat::Yuv420p10 => samples16(row, 2, luma, scale10),
+            _ => {
+                for (luma, &sample) in luma.iter_mut().zip(row) {
+                    *luma = (sample as i16) << SAMPLE_BITS;
+                }
+            }
+        }
+    }
+
+    /// Chroma row `y` at chroma resolution, scaled like `load_luma`
+    fn load_chroma(&self, planes: &[&[u8]], strides: &[usize], y: usize, u: &mut [i16], v: &mut [i16]) {
+        let row = |plane: usize| &planes[plane][y * strides[plane]..][..strides[plane]];
+        match self.format.pixel_format {
+            PixelFormat::Nv12 => {
+                for ((u, v), uv) in u.iter_mut().zip(v.iter_mut()).zip(row(1).chunks_exact(2)) {
+                    *u = (uv[0] as i16) << SAMPLE_BITS;
+                    *v = (uv[1] as i16) << SAMPLE_BITS;
+                }
+            }
+            PixelFormat::P010 => {
+                let scale = |sample: u16| (sample >> (8 - SAMPLE_BITS)) as i16;
+                samples16(row(1), 4, u, scale);
+                samples16(&row(1)[2..], 4, v, scale);
+            }
+            PixelFormat::Yuv420p10 => {
+                samples16(row(1), 2, u, scale10);
+                samples16(row(2), 2, v, scale10);
+            }
+            _ => {
+                for (out, plane) in [(u, 1), (v, 2)] {
+                    for (out, &sample) in out.iter_mut().zip(row(plane)) {
+                        *out = (sample as i16) << SAMPLE_BITS;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Interpolate a chroma row to the frame width, adding `WEIGHT_BITS` fraction bits
+    fn upsample(&self, native: &[i16], out: &mut [i32]) {
+        for (out, tap) in out.iter_mut().zip(&self.columns) {
+            *out = native[tap.first] as i32 * ((1 << WEIGHT_BITS) - tap.weight) + native[tap.second] as i32 * tap.weight;
+        }
+    }
+
+    fn pack_row<P>(&self, luma: &[i16], u: &[i16], v: &[i16], out: &mut [P], pack: &impl Fn(u8, u8, u8) -> P) {
+        let full = out.len() - out.len() % LANES;
+        let (out, rest) = out.split_at_mut(full);
+        let lanes = out.chunks_exact_mut(LANES).zip(luma.chunks_exact(LANES)).zip(u.chunks_exact(LANES)).zip(v.chunks_exact(LANES));
+        for (((out, luma), u), v) in lanes {
+            let out: &mut [P; LANES] = out.try_into().unwrap();
+            let (luma, u, v): (&[i16; LANES], &[i16; LANES], &[i16; LANES]) =
+                (luma.try_into().unwrap(), u.try_into().unwrap(), v.try_into().unwrap());
+            for lane in 0..LANES {
+                let (r, g, b) = self.matrix.pixel(luma[lane], u[lane], v[lane]);
+                out[lane] = pack(r, g, b);
+            }
+        }
+        // the last few pixels of a row
+        for (x, out) in (full..).zip(rest) {
+            let (r, g, b) = self.matrix.pixel(luma[x], u[x], v[x]);
+            *out = pack(r, g, b);
+        }
+    }
+}
+
+/// Vertical interpolation between two upsampled chroma rows, back to `SAMPLE_BITS`
+fn mix(top: &[i32], bottom: &[i32], weight: i32, out: &mut [i16]) {
+    const SHIFT: u32 = 2 * WEIGHT_BITS;
+    let top_weight = (1 << WEIGHT_BITS) - weight;
+    for ((out, &top), &bottom) in out.iter_mut().zip(top).zip(bottom) {
+        *out = ((top * top_weight + bottom * weight + (1 << (SHIFT - 1))) >> SHIFT) as i16;
+    }
+}
+
+/// A Yuv420p10 sample on the 8-bit scale. Set high bits, in a corrupt or mislabelled
+/// file, are dropped; kept they would overflow the arithmetic of `FixedMatrix::pixel`.
+fn scale10(sample: u16) -> i16 {
+    ((sample & 0x3ff) << (SAMPLE_BITS - 2)) as i16
+}
+
+/// Every `step` bytes a 16-bit little endian sample, through `scale`
+fn samples16(row: &[u8], step: usize, out: &mut [i16], scale: impl Fn(u16) -> i16) {
+    for (out, bytes) in out.iter_mut().zip(row.chunks(step)) {
+        *out = scale(u16::from_le_bytes([bytes[0], bytes[1]]));
+    }
+}
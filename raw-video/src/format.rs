@@ -0,0 +1,249 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How the pixels of a raw frame are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Planar Y, U and V, chroma halved in both directions (I420)
+    Yuv420p,
+    /// Planar Y, U and V, chroma halved horizontally
+    Yuv422p,
+    /// Planar Y, U and V at full resolution
+    Yuv444p,
+    /// Y plane, then one plane of interleaved U and V halved in both directions
+    Nv12,
+    /// Like NV12 with 16-bit little endian samples, 10 bits in the high bits
+    P010,
+    /// Like yuv420p with 16-bit little endian samples, 10 bits in the low bits
+    Yuv420p10,
+    /// Packed R, G, B bytes
+    Rgb24,
+    /// Packed B, G, R, A bytes
+    Bgra,
+}
+
+/// Size of one plane of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneLayout {
+    /// Bytes per row
+    pub stride: usize,
+    pub rows: usize,
+}
+
+impl PlaneLayout {
+    pub fn size(&self) -> usize {
+        self.stride * self.rows
+    }
+}
+
+impl PixelFormat {
+    pub const ALL: [PixelFormat; 8] = [
+        PixelFormat::Yuv420p,
+        PixelFormat::Yuv422p,
+        PixelFormat::Yuv444p,
+        PixelFormat::Nv12,
+        PixelFormat::P010,
+        PixelFormat::Yuv420p10,
+        PixelFormat::Rgb24,
+        PixelFormat::Bgra,
+    ];
+
+    /// Name as ffmpeg's `-pix_fmt` knows it
+    pub fn name(self) -> &'static str {
+        match self {
+            PixelFormat::Yuv420p => "yuv420p",
+            PixelFormat::Yuv422p => "yuv422p",
+            PixelFormat::Yuv444p => "yuv444p",
+            PixelFormat::Nv12 => "nv12",
+            PixelFormat::P010 => "p010le",
+            PixelFormat::Yuv420p10 => "yuv420p10le",
+            PixelFormat::Rgb24 => "rgb24",
+            PixelFormat::Bgra => "bgra",
+        }
+    }
+
+    /// Packed RGB rather than YUV
+    pub fn is_rgb(self) -> bool {
+        matches!(self, PixelFormat::Rgb24 | PixelFormat::Bgra)
+    }
+
+    /// Bits per sample
+    pub fn bit_depth(self) -> u32 {
+        match self {
+            PixelFormat::P010 | PixelFormat::Yuv420p10 => 10,
+            _ => 8,
+        }
+    }
+
+    /// How many bits chroma coordinates are shifted right, horizontally and vertically
+    pub fn chroma_shift(self) -> (u32, u32) {
+        match self {
+            PixelFormat::Yuv420p | PixelFormat::Nv12 | PixelFormat::P010 | PixelFormat::Yuv420p10 => (1, 1),
+            PixelFormat::Yuv422p => (1, 0),
+            PixelFormat::Yuv444p | PixelFormat::Rgb24 | PixelFormat::Bgra => (0, 0),
+        }
+    }
+
+    /// Layout of each plane of a `width` x `height` frame, rows back to back
+    pub fn plane_layouts(self, width: usize, height: usize) -> Vec<PlaneLayout> {
+        let (shift_x, shift_y) = self.chroma_shift();
+        let chroma_width = width.div_ceil(1 << shift_x);
+        let chroma_rows = height.div_ceil(1 << shift_y);
+        let plane = |stride, rows| PlaneLayout { stride, rows };
+        match self {
+            PixelFormat::Yuv420p | PixelFormat::Yuv422p | PixelFormat::Yuv444p => {
+                vec![plane(width, height), plane(chroma_width, chroma_rows), plane(chroma_width, chroma_rows)]
+            }
+            PixelFormat::Yuv420p10 => {
+                vec![plane(width * 2, height), plane(chroma_width * 2, chroma_rows), plane(chroma_width * 2, chroma_rows)]
+            }
+            PixelFormat::Nv12 => vec![plane(width, height), plane(chroma_width * 2, chroma_rows)],
+            PixelFormat::P010 => vec![plane(width * 2, height), plane(chroma_width * 4, chroma_rows)],
+            PixelFormat::Rgb24 => vec![plane(width * 3, height)],
+            PixelFormat::Bgra => vec![plane(width * 4, height)],
+        }
+    }
+
+    /// Size in bytes of each plane of a `width` x `height` frame
+    pub fn plane_sizes(self, width: usize, height: usize) -> Vec<usize> {
+        self.plane_layouts(width, height).iter().map(PlaneLayout::size).collect()
+    }
+}
+
+impl FromStr for PixelFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "yuv420p" | "i420" => Ok(PixelFormat::Yuv420p),
+            "yuv422p" => Ok(PixelFormat::Yuv422p),
+            "yuv444p" => Ok(PixelFormat::Yuv444p),
+            "nv12" => Ok(PixelFormat::Nv12),
+            "p010le" | "p010" => Ok(PixelFormat::P010),
+            "yuv420p10le" | "yuv420p10" => Ok(PixelFormat::Yuv420p10),
+            "rgb24" | "rgb" => Ok(PixelFormat::Rgb24),
+            "bgra" => Ok(PixelFormat::Bgra),
+            _ => {
+                let names: Vec<_> = Self::ALL.iter().map(|format| format.name()).collect();
+                Err(format!("Unknown pixel format {}, expected one of {}", name, names.join(", ")))
+            }
+        }
+    }
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Frames per second as a fraction, so NTSC rates like 30000/1001 stay exact
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRate {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl FrameRate {
+    pub fn new(num: u32, den: u32) -> Self {
+        Self { num, den }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// How long each frame is shown
+    pub fn frame_duration(self) -> Duration {
+        Duration::from_secs_f64(self.den as f64 / self.num as f64)
+    }
+
+    /// When frame `index` is due, exact rather than a sum of rounded frame durations
+    pub fn timestamp(self, index: usize) -> Duration {
+        let nanos = index as u128 * self.den as u128 * 1_000_000_000 / self.num as u128;
+        Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)
+    }
+
+    /// Index of the frame showing at `position`
+    pub fn frame_at(self, position: Duration) -> usize {
+        (position.as_nanos() * self.num as u128 / (self.den as u128 * 1_000_000_000)) as usize
+    }
+}
+
+impl FromStr for FrameRate {
+    type Err = String;
+
+    /// `25`, `29.97` or `30000/1001`
+    fn from_str(rate: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid frame rate {}, expected e.g. 25, 29.97 or 30000/1001", rate);
+        let (num, den) = match rate.split_once(['/', ':']) {
+            Some((num, den)) => (num.parse().map_err(|_| invalid())?, den.parse().map_err(|_| invalid())?),
+            None => match rate.parse::<u32>() {
+                Ok(num) => (num, 1),
+                Err(_) => {
+                    // decimals in thousandths, 29.97 becomes 29970/1000
+                    let fps: f64 = rate.parse().map_err(|_| invalid())?;
+                    ((fps * 1000.0).round() as u32, 1000)
+                }
+            },
+        };
+        if num == 0 || den == 0 {
+            return Err(invalid());
+        }
+        Ok(Self { num, den })
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.num.is_multiple_of(self.den) {
+            write!(f, "{}", self.num / self.den)
+        } else {
+            write!(f, "{:.3}", self.as_f64())
+        }
+    }
+}
+
+/// Everything needed to cut a raw stream into frames and show them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoFormat {
+    pub width: usize,
+    pub height: usize,
+    pub pixel_format: PixelFormat,
+    pub frame_rate: FrameRate,
+}
+
+impl VideoFormat {
+    pub fn plane_layouts(&self) -> Vec<PlaneLayout> {
+        self.pixel_format.plane_layouts(self.width, self.height)
+    }
+
+    pub fn plane_sizes(&self) -> Vec<usize> {
+        self.pixel_format.plane_sizes(self.width, self.height)
+    }
+
+    /// Bytes per frame
+    pub fn frame_size(&self) -> usize {
+        self.plane_sizes().iter().sum()
+    }
+
+    /// Split a frame into its planes
+    pub fn planes<'a>(&self, frame: &'a [u8]) -> Vec<&'a [u8]> {
+        let mut rest = frame;
+        self.plane_sizes()
+            .into_iter()
+            .map(|size| {
+                let (plane, tail) = rest.split_at(size.min(rest.len()));
+                rest = tail;
+                plane
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for VideoFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{} {} @ {}fps", self.width, self.height, self.pixel_format, self.frame_rate)
+    }
+}
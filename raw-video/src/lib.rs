@@ -0,0 +1,27 @@
+//! Shared parts of the raw video players: command line options, pixel formats, frame
+//! layout, reading raw or `.y4m` files, conversion to RGB with the right colorimetry
+//! and presenting frames in sync with the audio track, under keyboard control
+
+#[cfg(feature = "audio")]
+mod audio;
+mod color;
+pub mod convert;
+mod format;
+mod options;
+mod reader;
+mod sync;
+mod transport;
+pub mod y4m;
+
+#[cfg(feature = "audio")]
+pub use audio::{AudioClock, AudioPlayer, AudioTrack};
+pub use color::{ChromaSiting, ColorSpace, Matrix, Range};
+pub use convert::Converter;
+pub use format::{FrameRate, PixelFormat, PlaneLayout, VideoFormat};
+pub use options::Options;
+pub use reader::VideoReader;
+pub use sync::{Clock, Presenter, SystemClock, TimedFrame};
+pub use transport::{Action, Seek, Transport, SPEEDS};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,227 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{ChromaSiting, FrameRate, Matrix, PixelFormat, Range, VideoFormat};
+
+/// Command line of the players
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub path: PathBuf,
+    pub format: VideoFormat,
+    /// Color settings given on the command line, the rest comes from the file or is
+    /// guessed, see `VideoReader::color`
+    pub matrix: Option<Matrix>,
+    pub range: Option<Range>,
+    pub chroma_siting: Option<ChromaSiting>,
+    /// Audio track to play along, WAV or raw 16-bit PCM
+    pub audio: Option<PathBuf>,
+    /// Format of a raw PCM audio track
+    pub audio_rate: u32,
+    pub audio_channels: u16,
+    /// How far the arrow keys seek
+    pub seek_step: Duration,
+}
+
+impl Options {
+    pub fn usage(program: &str) -> String {
+        [
+            format!(
+                "Usage: {} [<file>] [--size <width>x<height>] [--fps <rate>] [--pix-fmt <format>] [--matrix <matrix>] [--range <range>] [--chroma-loc <location>] [--audio <file>]",
+                program
+            ),
+            "  <file>, -i, --input  raw or .y4m video to play; a .y4m header overrides the options below".to_string(),
+            "  -s, --size           frame size, or --width / --height (default 1920x1080)".to_string(),
+            "  -r, --fps            frame rate: 25, 29.97 or 30000/1001 (default 25)".to_string(),
+            "  --pix-fmt            yuv420p, yuv422p, yuv444p, nv12, p010le, yuv420p10le, rgb24 or bgra (default yuv420p)"
+                .to_string(),
+            "  --matrix             YUV matrix: 601, 709 or 2020 (default 709 from 720p up, 601 below)".to_string(),
+            "  --range              limited or full (default limited)".to_string(),
+            "  --chroma-loc         chroma siting: left, center or topleft (default left)".to_string(),
+            "  --audio              WAV or raw 16-bit PCM track to play along, the video follows it".to_string(),
+            "  --audio-rate         sample rate of a raw PCM track (default 48000)".to_string(),
+            "  --audio-channels     channels of a raw PCM track (default 2)".to_string(),
+            "  --seek-step          seconds the arrow keys seek (default 5)".to_string(),
+        ]
+        .join("\n")
+    }
+
+    /// Parse the arguments after the program name. `--help` and mistakes come back as
+    /// the message to print.
+    pub fn parse(args: impl IntoIterator<Item = String>, program: &str, default_path: &str) -> Result<Self, String> {
+        let mut path = None;
+        let mut format = VideoFormat {
+            width: 1920,
+            height: 1080,
+            pixel_format: PixelFormat::Yuv420p,
+            frame_rate: FrameRate::new(25, 1),
+        };
+        let (mut matrix, mut range, mut chroma_siting) = (None, None, None);
+        let (mut audio, mut audio_rate, mut audio_channels) = (None, 48000, 2);
+        let mut seek_step = Duration::from_secs(5);
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "-h" | "--help" => return Err(Self::usage(program)),
+                "-i" | "--input" => path = Some(PathBuf::from(value()?)),
+                "-s" | "--size" => {
+                    let size = value()?;
+                    let (width, height) = size.split_once('x').ok_or(format!("Invalid size {}, expected e.g. 1280x720", size))?;
+                    format.width = parse_dimension(width)?;
+                    format.height = parse_dimension(height)?;
+                }
+                "--width" => format.width = parse_dimension(&value()?)?,
+                "--height" => format.height = parse_dimension(&value()?)?,
+                "-r" | "--fps" => format.frame_rate = value()?.parse()?,
+                "--pix-fmt" | "--pixel-format" => format.pixel_format = value()?.parse()?,
+                "--matrix" | "--colorspace" => matrix = Some(value()?.parse()?),
+                "--range" | "--color-range" => range = Some(value()?.parse()?),
+                "--chroma-loc" | "--chroma-location" => chroma_siting = Some(value()?.parse()?),
+                "--audio" => audio = Some(PathBuf::from(value()?)),
+                "--audio-rate" => audio_rate = parse_count(&value()?)?,
+                "--audio-channels" => audio_channels = parse_count(&value()?)?,
+                "--seek-step" => {
+                    let value = value()?;
+                    seek_step = Duration::try_from_secs_f64(parse_count(&value)?).map_err(|_| format!("Invalid number {}", value))?
+                }
+                other if other.starts_with('-') => return Err(format!("Unknown option {}\n{}", other, Self::usage(program))),
+                _ if path.is_none() => path = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument {}\n{}", arg, Self::usage(program))),
+            }
+        }
+        Ok(Self {
+            path: path.unwrap_or_else(|| PathBuf::from(default_path)),
+            format,
+            matrix,
+            range,
+            chroma_siting,
+            audio,
+            audio_rate,
+            audio_channels,
+            seek_step,
+        })
+    }
+}
+
+fn parse_dimension(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(pixels) if pixels > 0 => Ok(pixels),
+        _ => Err(format!("Invalid dimension {}", value)),
+    }
+}
+
+fn parse_count<T: std::str::FromStr + Default + PartialOrd>(value: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(count) if count > T::default() => Ok(count),
+        _ => Err(format!("Invalid number {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::VideoReader;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()), "player", "default.yuv")
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.path, PathBuf::from("default.yuv"));
+        assert_eq!(options.format, VideoFormat {
+            width: 1920,
+            height: 1080,
+            pixel_format: PixelFormat::Yuv420p,
+            frame_rate: FrameRate::new(25, 1)
+        });
+        assert_eq!((options.matrix, options.range, options.chroma_siting), (None, None, None));
+        assert_eq!((options.audio, options.audio_rate, options.audio_channels), (None, 48000, 2));
+        assert_eq!(options.seek_step, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn every_option() {
+        let options = parse(&[
+            "clip.yuv", "-s", "1280x720", "-r", "30000/1001", "--pix-fmt", "nv12", "--matrix", "601", "--range", "full",
+            "--chroma-loc", "center", "--audio", "clip.wav", "--audio-rate", "44100", "--audio-channels", "1",
+            "--seek-step", "2.5",
+        ])
+        .unwrap();
+        assert_eq!(options.path, PathBuf::from("clip.yuv"));
+        assert_eq!(options.format, VideoFormat {
+            width: 1280,
+            height: 720,
+            pixel_format: PixelFormat::Nv12,
+            frame_rate: FrameRate::new(30000, 1001)
+        });
+        assert_eq!(options.matrix, Some(Matrix::Bt601));
+        assert_eq!(options.range, Some(Range::Full));
+        assert_eq!(options.chroma_siting, Some(ChromaSiting::Center));
+        assert_eq!(options.audio, Some(PathBuf::from("clip.wav")));
+        assert_eq!((options.audio_rate, options.audio_channels), (44100, 1));
+        assert_eq!(options.seek_step, Duration::from_millis(2500));
+
+        let options = parse(&["--width", "640", "--height", "360", "-i", "clip.y4m", "--fps", "29.97"]).unwrap();
+        assert_eq!((options.format.width, options.format.height), (640, 360));
+        assert_eq!(options.format.frame_rate, FrameRate::new(29970, 1000));
+        assert_eq!(options.path, PathBuf::from("clip.y4m"));
+    }
+
+    #[test]
+    fn mistakes() {
+        for (args, message) in [
+            (&["--size"][..], "--size needs a value"),
+            (&["clip.yuv", "-r"], "-r needs a value"),
+            (&["--frobnicate"], "Unknown option --frobnicate"),
+            (&["-x", "1"], "Unknown option -x"),
+            (&["a.yuv", "b.yuv"], "Unexpected argument b.yuv"),
+            (&["--size", "1280"], "Invalid size 1280"),
+            (&["--size", "1280x"], "Invalid dimension "),
+            (&["--size", "0x720"], "Invalid dimension 0"),
+            (&["--size", "-1x720"], "Invalid dimension -1"),
+            (&["--width", "wide"], "Invalid dimension wide"),
+            (&["--fps", "0"], "Invalid frame rate 0"),
+            (&["--fps", "fast"], "Invalid frame rate fast"),
+            (&["--fps", "30000/0"], "Invalid frame rate 30000/0"),
+            (&["--audio-rate", "0"], "Invalid number 0"),
+            (&["--seek-step", "-1"], "Invalid number -1"),
+            (&["--seek-step", "1e300"], "Invalid number 1e300"),
+        ] {
+            let error = parse(args).unwrap_err();
+            assert!(error.starts_with(message), "{:?}: {}", args, error);
+        }
+        assert!(parse(&["--pix-fmt", "yuv411p"]).is_err());
+        assert!(parse(&["--matrix", "240m"]).is_err());
+        assert!(parse(&["--range", "studio"]).is_err());
+        assert!(parse(&["--chroma-loc", "bottom"]).is_err());
+
+        // help is the usage
+        assert_eq!(parse(&["clip.yuv", "--help"]).unwrap_err(), Options::usage("player"));
+        assert!(parse(&["--frobnicate"]).unwrap_err().ends_with(&Options::usage("player")));
+    }
+
+    #[test]
+    fn y4m_header_overrides_the_format() {
+        let path = std::env::temp_dir().join(format!("raw-video-{}-options.y4m", std::process::id()));
+        fs::write(&path, b"YUV4MPEG2 W4 H2 F30:1 C420mpeg2 XCOLORRANGE=FULL\nFRAME\n000000000000").unwrap();
+        let args = [path.to_str().unwrap(), "--size", "1280x720", "--fps", "50", "--pix-fmt", "rgb24", "--chroma-loc", "topleft"];
+        let options = parse(&args).unwrap();
+        let reader = VideoReader::open(&options);
+        let _ = fs::remove_file(&path);
+        let reader = reader.unwrap();
+
+        assert_eq!(reader.format(), VideoFormat {
+            width: 4,
+            height: 2,
+            pixel_format: PixelFormat::Yuv420p,
+            frame_rate: FrameRate::new(30, 1)
+        });
+        // color settings given on the command line still win, the rest comes from the header
+        assert_eq!(reader.color().chroma_siting, ChromaSiting::TopLeft);
+        assert_eq!(reader.color().range, Range::Full);
+    }
+}
@@ -0,0 +1,290 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::y4m::{self, Header};
+use crate::{ColorSpace, Options, VideoFormat};
+
+/// Frames of a headerless raw file or of a `.y4m` stream
+pub struct VideoReader {
+    file: BufReader<File>,
+    format: VideoFormat,
+    header: Option<Header>,
+    color: ColorSpace,
+    /// Where the first frame starts, for `rewind`
+    data_start: u64,
+    /// Index of the frame `read_frame` reads next
+    next: usize,
+    /// Where the frames of a `.y4m` stream start, as far as they were looked up; frame
+    /// headers may carry parameters, so their length varies
+    index: Vec<u64>,
+    /// Where the frames in `index` end
+    indexed_to: u64,
+    /// Whether `index` has all of them
+    indexed: bool,
+}
+
+impl VideoReader {
+    /// Open `options.path`. A YUV4MPEG2 stream is recognised by its magic and brings
+    /// its own geometry, frame rate and pixel format; anything else is taken as raw
+    /// frames in `options.format`. Color settings given in `options` win over those of
+    /// the file, which win over `ColorSpace::guess`.
+    pub fn open(options: &Options) -> io::Result<Self> {
+        let mut reader = Self::open_with_format(&options.path, options.format)?;
+        let header = reader.header.as_ref();
+        let color = &mut reader.color;
+        color.range = options.range.or(header.and_then(|header| header.color_range)).unwrap_or(color.range);
+        color.chroma_siting =
+            options.chroma_siting.or(header.and_then(|header| header.chroma_siting)).unwrap_or(color.chroma_siting);
+        color.matrix = options.matrix.unwrap_or(color.matrix);
+        Ok(reader)
+    }
+
+    pub fn open_with_format(path: impl AsRef<Path>, raw_format: VideoFormat) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let (format, header) = if file.fill_buf()?.starts_with(y4m::MAGIC) {
+            let header = Header::read(&mut file)?;
+            (header.format(), Some(header))
+        } else {
+            (raw_format, None)
+        };
+        let data_start = file.stream_position()?;
+        let color = ColorSpace::guess(&format);
+        Ok(Self { file, format, header, color, data_start, next: 0, index: Vec::new(), indexed_to: data_start, indexed: false })
+    }
+
+    pub fn format(&self) -> VideoFormat {
+        self.format
+    }
+
+    /// How to turn the YUV of this stream into RGB
+    pub fn color(&self) -> ColorSpace {
+        self.color
+    }
+
+    /// Stream header when playing a `.y4m` file
+    pub fn y4m_header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Read the next frame into `frame`, which must be `format().frame_size()` long.
+    /// Returns `false` at the end of the stream; a trailing partial frame counts as
+    /// the end too.
+    pub fn read_frame(&mut self, frame: &mut [u8]) -> io::Result<bool> {
+        if self.header.is_some() && !y4m::read_frame_header(&mut self.file)? {
+            return Ok(false);
+        }
+        match self.file.read_exact(frame) {
+            Ok(()) => {
+                self.next += 1;
+                Ok(true)
+            }
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Go back to the first frame
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(self.data_start))?;
+        self.next = 0;
+        Ok(())
+    }
+
+    /// Index of the frame `read_frame` reads next
+    pub fn position(&self) -> usize {
+        self.next
+    }
+
+    /// Go to frame `index`, or to the last frame if it is past the end. Returns the
+    /// index gone to.
+    pub fn seek(&mut self, index: usize) -> io::Result<usize> {
+        let index = match self.offset(index)? {
+            Some(_) => index,
+            None => self.frame_count()?.saturating_sub(1),
+        };
+        match self.offset(index)? {
+            Some(offset) => self.file.seek(SeekFrom::Start(offset))?,
+            None => self.file.seek(SeekFrom::Start(self.data_start))?,
+        };
+        self.next = index;
+        Ok(index)
+    }
+
+    /// Whole frames in the stream. A `.y4m` stream is scanned for them the first time.
+    pub fn frame_count(&mut self) -> io::Result<usize> {
+        let file_size = self.file.get_ref().metadata()?.len();
+        if self.header.is_none() {
+            return Ok((file_size.saturating_sub(self.data_start) / self.format.frame_size() as u64) as usize);
+        }
+        let position = self.file.stream_position()?;
+        while self.offset(self.index.len())?.is_some() {}
+        self.file.seek(SeekFrom::Start(position))?;
+        Ok(self.index.len())
+    }
+
+    /// Where frame `index` starts, `None` if the stream ends before it
+    fn offset(&mut self, index: usize) -> io::Result<Option<u64>> {
+        let frame_size = self.format.frame_size() as u64;
+        let file_size = self.file.get_ref().metadata()?.len();
+        if self.header.is_none() {
+            let offset = (index as u64).checked_mul(frame_size).and_then(|offset| offset.checked_add(self.data_start));
+            return Ok(offset.filter(|offset| offset + frame_size <= file_size));
+        }
+        // scan on from the last frame known, skipping over the frame data; the read
+        // position is left anywhere, callers seek afterwards
+        while self.index.len() <= index && !self.indexed {
+            let start = self.indexed_to;
+            self.file.seek(SeekFrom::Start(start))?;
+            let data = match y4m::read_frame_header(&mut self.file)? {
+                true => self.file.stream_position()?,
+                false => file_size,
+            };
+            if data + frame_size > file_size {
+                self.indexed = true;
+            } else {
+                self.index.push(start);
+                self.indexed_to = data + frame_size;
+            }
+        }
+        Ok(self.index.get(index).copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{FrameRate, PixelFormat};
+
+    /// 4x2 yuv420p, 12 bytes a frame
+    const FORMAT: VideoFormat = VideoFormat { width: 4, height: 2, pixel_format: PixelFormat::Yuv420p, frame_rate: FrameRate { num: 25, den: 1 } };
+
+    /// File in the temporary directory, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("raw-video-{}-{}", std::process::id(), name));
+            fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+
+        fn open(&self) -> VideoReader {
+            VideoReader::open_with_format(&self.0, FORMAT).unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// Frame `index` is all `index`
+    fn frame(index: usize) -> Vec<u8> {
+        vec![index as u8; FORMAT.frame_size()]
+    }
+
+    /// `frames` frames and a partial one after them
+    fn raw(frames: usize) -> Vec<u8> {
+        let mut data: Vec<u8> = (0..frames).flat_map(frame).collect();
+        data.extend_from_slice(&frame(frames)[..5]);
+        data
+    }
+
+    /// The same as a `.y4m` stream, frame headers of varying length
+    fn y4m(frames: usize) -> Vec<u8> {
+        let mut data = b"YUV4MPEG2 W4 H2 F25:1 C420jpeg\n".to_vec();
+        for index in 0..frames {
+            data.extend_from_slice(if index % 2 == 0 { b"FRAME\n".as_slice() } else { b"FRAME Ip XKEY=1\n".as_slice() });
+            data.extend_from_slice(&frame(index));
+        }
+        data.extend_from_slice(b"FRAME\n");
+        data.extend_from_slice(&frame(frames)[..5]);
+        data
+    }
+
+    /// First byte of the next frame, `None` at the end
+    fn next(reader: &mut VideoReader) -> Option<u8> {
+        let mut buffer = vec![0; FORMAT.frame_size()];
+        reader.read_frame(&mut buffer).unwrap().then_some(buffer[0])
+    }
+
+    fn seek_around(reader: &mut VideoReader) {
+        assert_eq!(reader.seek(3).unwrap(), 3);
+        assert_eq!(reader.position(), 3);
+        assert_eq!(next(reader), Some(3));
+        assert_eq!(reader.position(), 4);
+        assert_eq!(reader.seek(1).unwrap(), 1);
+        assert_eq!(next(reader), Some(1));
+        assert_eq!(next(reader), Some(2));
+        assert_eq!(reader.seek(0).unwrap(), 0);
+        assert_eq!(next(reader), Some(0));
+
+        // past the end, including the partial frame, is the last frame
+        assert_eq!(reader.seek(5).unwrap(), 4);
+        assert_eq!(reader.seek(usize::MAX).unwrap(), 4);
+        assert_eq!(next(reader), Some(4));
+        assert_eq!(next(reader), None);
+
+        // counting leaves the position alone
+        reader.seek(2).unwrap();
+        assert_eq!(reader.frame_count().unwrap(), 5);
+        assert_eq!(next(reader), Some(2));
+    }
+
+    #[test]
+    fn seek_in_raw_files() {
+        let file = TempFile::new("seek.yuv", &raw(5));
+        let mut reader = file.open();
+        assert!(reader.y4m_header().is_none());
+        seek_around(&mut reader);
+    }
+
+    #[test]
+    fn seek_in_y4m_files() {
+        let file = TempFile::new("seek.y4m", &y4m(5));
+        let mut reader = file.open();
+        assert!(reader.y4m_header().is_some());
+        seek_around(&mut reader);
+    }
+
+    #[test]
+    fn y4m_index_is_built_as_far_as_needed() {
+        let file = TempFile::new("index.y4m", &y4m(5));
+        let mut reader = file.open();
+        assert_eq!(next(&mut reader), Some(0));
+        assert!(reader.index.is_empty());
+
+        reader.seek(2).unwrap();
+        assert_eq!((reader.index.len(), reader.indexed), (3, false));
+        assert_eq!(next(&mut reader), Some(2));
+        reader.seek(1).unwrap();
+        assert_eq!((reader.index.len(), reader.indexed), (3, false));
+        assert_eq!(next(&mut reader), Some(1));
+
+        reader.seek(10).unwrap();
+        assert_eq!((reader.index.len(), reader.indexed), (5, true));
+        assert_eq!(next(&mut reader), Some(4));
+        assert_eq!(next(&mut reader), None);
+
+        // after reading through, rewinding starts over
+        reader.rewind().unwrap();
+        assert_eq!(next(&mut reader), Some(0));
+    }
+
+    #[test]
+    fn seek_in_an_empty_file_stays_at_the_start() {
+        let file = TempFile::new("empty.y4m", b"YUV4MPEG2 W4 H2 F25:1\n");
+        let mut reader = file.open();
+        assert_eq!(reader.seek(3).unwrap(), 0);
+        assert_eq!(next(&mut reader), None);
+        let file = TempFile::new("empty.yuv", &[]);
+        let mut reader = file.open();
+        assert_eq!(reader.seek(3).unwrap(), 0);
+        assert_eq!(next(&mut reader), None);
+    }
+}
@@ -0,0 +1,305 @@
+//! Presenting frames by timestamp against a playback clock
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Where playback is, which video frames are presented against. With an audio track
+/// that is the audio device, see `AudioClock`, otherwise the wall clock.
+pub trait Clock: Send + Sync {
+    fn position(&self) -> Duration;
+
+    /// Jump to `position`
+    fn seek(&self, position: Duration);
+
+    /// Stop or resume the clock; clocks start paused so that nothing is late while
+    /// the first frame is being read
+    fn set_paused(&self, paused: bool);
+
+    fn is_paused(&self) -> bool;
+
+    /// Run `speed` times faster than real time, carrying on from the current position
+    fn set_speed(&self, speed: f64);
+}
+
+/// `Clock` on `Instant`, for video without audio
+#[derive(Debug)]
+pub struct SystemClock {
+    state: Mutex<SystemClockState>,
+}
+
+#[derive(Debug)]
+struct SystemClockState {
+    /// Position when last started, seeked or changed speed
+    base: Duration,
+    /// Since when it runs, `None` while paused
+    running_since: Option<Instant>,
+    speed: f64,
+}
+
+impl SystemClockState {
+    fn position(&self, now: Instant) -> Duration {
+        self.base + self.running_since.map_or(Duration::ZERO, |since| now.saturating_duration_since(since).mul_f64(self.speed))
+    }
+
+    /// Carry on from `now` with the position reached
+    fn rebase(&mut self, now: Instant) {
+        self.base = self.position(now);
+        self.running_since = self.running_since.map(|_| now);
+    }
+}
+
+impl SystemClock {
+    /// Paused at the start
+    pub fn new() -> Self {
+        Self { state: Mutex::new(SystemClockState { base: Duration::ZERO, running_since: None, speed: 1.0 }) }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn position(&self) -> Duration {
+        self.state.lock().unwrap().position(Instant::now())
+    }
+
+    fn seek(&self, position: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.base = position;
+        state.running_since = state.running_since.map(|_| Instant::now());
+    }
+
+    fn set_paused(&self, paused: bool) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match (paused, state.running_since) {
+            (true, Some(_)) => {
+                state.rebase(now);
+                state.running_since = None;
+            }
+            (false, None) => state.running_since = Some(now),
+            _ => {}
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().running_since.is_none()
+    }
+
+    fn set_speed(&self, speed: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.rebase(Instant::now());
+        state.speed = speed;
+    }
+}
+
+/// A frame on its way to the screen
+#[derive(Debug)]
+pub struct TimedFrame<F> {
+    /// Index in the file
+    pub index: usize,
+    /// When it is due on the playback timeline
+    pub timestamp: Duration,
+    /// Bumped by the reader on every seek, so frames read before it can be told apart
+    pub epoch: u64,
+    pub frame: F,
+}
+
+/// Picks the frame to show from those the reader sends: the newest one that is due.
+/// Frames that fell due together are dropped but the last, and the current frame is
+/// repeated until the next one is due.
+#[derive(Debug)]
+pub struct Presenter<F> {
+    current: Option<TimedFrame<F>>,
+    /// Received but not due yet
+    pending: Option<TimedFrame<F>>,
+    epoch: u64,
+    /// Show the next frame of `epoch` whenever it is due
+    seeking: bool,
+    dropped: usize,
+}
+
+impl<F> Presenter<F> {
+    pub fn new() -> Self {
+        Self { current: None, pending: None, epoch: 0, seeking: false, dropped: 0 }
+    }
+
+    /// Take the frames due at `position` from `receive`. Returns whether the frame to
+    /// show changed.
+    pub fn update(&mut self, position: Duration, mut receive: impl FnMut() -> Option<TimedFrame<F>>) -> bool {
+        let mut taken = 0usize;
+        while let Some(frame) = self.pending.take().or_else(&mut receive) {
+            if frame.epoch < self.epoch {
+                // read before a seek
+                continue;
+            }
+            if frame.epoch > self.epoch || self.seeking {
+                // the first frame after a seek shows right away, the clock was moved to it
+                self.epoch = frame.epoch;
+                self.seeking = false;
+                self.current = Some(frame);
+                taken += 1;
+                continue;
+            }
+            if frame.timestamp > position {
+                self.pending = Some(frame);
+                break;
+            }
+            self.current = Some(frame);
+            taken += 1;
+        }
+        self.dropped += taken.saturating_sub(1);
+        taken > 0
+    }
+
+    /// Forget the frame waiting to fall due and those read before `epoch`, whose first
+    /// frame shows right away. Without it a frame received before a seek holds the
+    /// frames behind it back until it is due.
+    pub fn seek(&mut self, epoch: u64) {
+        self.pending = None;
+        self.epoch = epoch;
+        self.seeking = true;
+    }
+
+    pub fn current(&self) -> Option<&TimedFrame<F>> {
+        self.current.as_ref()
+    }
+
+    /// When the next received frame falls due
+    pub fn next_due(&self) -> Option<Duration> {
+        self.pending.as_ref().map(|frame| frame.timestamp)
+    }
+
+    /// Frames that were never shown because a later one was already due
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl<F> Default for Presenter<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Frame `index` at 25 fps
+    fn frame(index: usize, epoch: u64) -> TimedFrame<()> {
+        TimedFrame { index, timestamp: ms(40 * index as u64), epoch, frame: () }
+    }
+
+    fn update(presenter: &mut Presenter<()>, position: Duration, queue: &mut VecDeque<TimedFrame<()>>) -> bool {
+        presenter.update(position, || queue.pop_front())
+    }
+
+    fn shown(presenter: &Presenter<()>) -> Option<(usize, u64)> {
+        presenter.current().map(|frame| (frame.index, frame.epoch))
+    }
+
+    #[test]
+    fn frames_due_together_show_the_last() {
+        let mut presenter = Presenter::new();
+        let mut queue: VecDeque<_> = (0..5).map(|index| frame(index, 0)).collect();
+
+        assert!(update(&mut presenter, ms(0), &mut queue));
+        assert_eq!(shown(&presenter), Some((0, 0)));
+        assert_eq!(presenter.next_due(), Some(ms(40)));
+
+        // 1, 2 and 3 are all due at 130 ms
+        assert!(update(&mut presenter, ms(130), &mut queue));
+        assert_eq!(shown(&presenter), Some((3, 0)));
+        assert_eq!(presenter.dropped(), 2);
+        assert_eq!(presenter.next_due(), Some(ms(160)));
+
+        // the current frame repeats until the next is due
+        assert!(!update(&mut presenter, ms(150), &mut queue));
+        assert_eq!(shown(&presenter), Some((3, 0)));
+        assert!(update(&mut presenter, ms(160), &mut queue));
+        assert_eq!(shown(&presenter), Some((4, 0)));
+        assert_eq!(presenter.next_due(), None);
+        assert_eq!(presenter.dropped(), 2);
+        assert!(!update(&mut presenter, ms(1000), &mut queue));
+    }
+
+    #[test]
+    fn frames_from_before_a_seek_are_discarded() {
+        let mut presenter = Presenter::new();
+        let mut queue: VecDeque<_> = (0..3).map(|index| frame(index, 0)).collect();
+        update(&mut presenter, ms(40), &mut queue);
+        assert_eq!(shown(&presenter), Some((1, 0)));
+        assert_eq!(presenter.next_due(), Some(ms(80)));
+
+        presenter.seek(1);
+        assert_eq!(presenter.next_due(), None);
+        // the frame on screen stays until the seek's first frame arrives
+        assert!(!update(&mut presenter, ms(80), &mut queue));
+        assert_eq!(shown(&presenter), Some((1, 0)));
+
+        // read before the reader got the seek: skipped without counting as dropped
+        queue.extend([frame(3, 0), frame(4, 0), frame(100, 1), frame(101, 1)]);
+        // the first frame after the seek shows right away, wherever the clock is
+        assert!(update(&mut presenter, ms(120), &mut queue));
+        assert_eq!(shown(&presenter), Some((100, 1)));
+        assert_eq!(presenter.dropped(), 1);
+        assert_eq!(presenter.next_due(), Some(ms(4040)));
+        assert!(update(&mut presenter, ms(4040), &mut queue));
+        assert_eq!(shown(&presenter), Some((101, 1)));
+    }
+
+    #[test]
+    fn seeks_before_any_frame_arrives() {
+        let mut presenter = Presenter::new();
+        presenter.seek(1);
+        presenter.seek(2);
+        let mut queue: VecDeque<_> = [frame(10, 1), frame(20, 2), frame(21, 2)].into();
+        assert!(update(&mut presenter, ms(0), &mut queue));
+        assert_eq!(shown(&presenter), Some((20, 2)));
+        assert_eq!(presenter.next_due(), Some(ms(840)));
+        assert_eq!(presenter.dropped(), 0);
+    }
+
+    #[test]
+    fn system_clock_state() {
+        let start = Instant::now();
+        let mut state = SystemClockState { base: ms(1000), running_since: None, speed: 2.0 };
+        // paused, time does not count
+        assert_eq!(state.position(start + ms(500)), ms(1000));
+
+        state.running_since = Some(start);
+        assert_eq!(state.position(start + ms(500)), ms(2000));
+        state.rebase(start + ms(500));
+        assert_eq!(state.running_since, Some(start + ms(500)));
+        state.speed = 0.5;
+        assert_eq!(state.position(start + ms(700)), ms(2100));
+        // an instant from before the last change is no time at all
+        assert_eq!(state.position(start), ms(2000));
+    }
+
+    #[test]
+    fn system_clock_is_paused_at_the_start() {
+        let clock = SystemClock::new();
+        assert!(clock.is_paused());
+        clock.seek(ms(500));
+        clock.set_speed(4.0);
+        assert_eq!(clock.position(), ms(500));
+        clock.set_paused(false);
+        assert!(!clock.is_paused());
+        clock.set_paused(true);
+        let position = clock.position();
+        assert!(position >= ms(500));
+        std::thread::sleep(ms(5));
+        assert_eq!(clock.position(), position);
+    }
+}
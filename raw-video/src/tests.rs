@@ -0,0 +1,127 @@
+//! `Converter` against the floating point `convert_frame`
+
+use crate::convert::{self, Converter};
+use crate::{ChromaSiting, ColorSpace, FrameRate, Matrix, PixelFormat, Range, VideoFormat};
+
+/// Odd sizes so that the last chroma sample and the padded lanes are covered
+const WIDTH: usize = 37;
+const HEIGHT: usize = 23;
+
+fn format(pixel_format: PixelFormat) -> VideoFormat {
+    VideoFormat { width: WIDTH, height: HEIGHT, pixel_format, frame_rate: FrameRate::new(25, 1) }
+}
+
+/// Noise with valid samples: 10-bit formats keep their unused bits clear
+fn frame(format: &VideoFormat, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (state >> 16) as u16
+    };
+    let size = format.frame_size();
+    match format.pixel_format {
+        PixelFormat::P010 => (0..size / 2).flat_map(|_| (next() & 0xffc0).to_le_bytes()).collect(),
+        PixelFormat::Yuv420p10 => (0..size / 2).flat_map(|_| (next() & 0x03ff).to_le_bytes()).collect(),
+        _ => (0..size).map(|_| next() as u8).collect(),
+    }
+}
+
+fn color_spaces() -> Vec<ColorSpace> {
+    let mut spaces = Vec::new();
+    for matrix in [Matrix::Bt601, Matrix::Bt709, Matrix::Bt2020] {
+        for range in [Range::Limited, Range::Full] {
+            for chroma_siting in [ChromaSiting::Left, ChromaSiting::Center, ChromaSiting::TopLeft] {
+                spaces.push(ColorSpace { matrix, range, chroma_siting });
+            }
+        }
+    }
+    spaces
+}
+
+/// Largest difference of any channel between `Converter` and the reference
+fn max_difference(format: &VideoFormat, color: &ColorSpace, frame: &[u8]) -> u8 {
+    let reference = convert::to_rgba(format, color, frame);
+    let mut fast = vec![0; reference.len()];
+    Converter::new(format, color).to_rgba(frame, &mut fast);
+    reference.iter().zip(&fast).map(|(a, b)| a.abs_diff(*b)).max().unwrap()
+}
+
+#[test]
+fn within_one_of_the_reference() {
+    for pixel_format in PixelFormat::ALL {
+        let format = format(pixel_format);
+        for (seed, color) in color_spaces().iter().enumerate() {
+            let difference = max_difference(&format, color, &frame(&format, seed as u32));
+            assert!(difference <= 1, "{} {}: off by {}", pixel_format, color, difference);
+        }
+    }
+}
+
+#[test]
+fn within_one_of_the_reference_at_the_extremes() {
+    // black, white and fully saturated chroma clip the hardest
+    let format = format(PixelFormat::Yuv444p);
+    for (seed, color) in color_spaces().iter().enumerate() {
+        let frame: Vec<u8> = frame(&format, seed as u32).iter().map(|sample| if sample & 1 == 0 { 0 } else { 255 }).collect();
+        let difference = max_difference(&format, color, &frame);
+        assert!(difference <= 1, "{}: off by {}", color, difference);
+    }
+}
+
+#[test]
+fn out_of_range_10_bit_samples() {
+    // words with bits set above the 10 a corrupt or mislabelled file may carry
+    let format = format(PixelFormat::Yuv420p10);
+    for word in [0x0800u16, 0xffff, 0xfc00, 0x8200] {
+        let frame: Vec<u8> = (0..format.frame_size() / 2).flat_map(|_| word.to_le_bytes()).collect();
+        for color in color_spaces() {
+            let difference = max_difference(&format, &color, &frame);
+            assert!(difference <= 1, "{:#06x} {}: off by {}", word, color, difference);
+        }
+    }
+    // mixed with valid samples
+    let frame: Vec<u8> = frame(&format, 5).chunks(2).enumerate().flat_map(|(i, bytes)| [bytes[0], bytes[1] | (i as u8 & 0xfc)]).collect();
+    let color = ColorSpace::guess(&format);
+    assert!(max_difference(&format, &color, &frame) <= 1);
+}
+
+#[test]
+fn rgb_formats_are_exact() {
+    let color = ColorSpace::guess(&format(PixelFormat::Rgb24));
+    for pixel_format in [PixelFormat::Rgb24, PixelFormat::Bgra] {
+        let format = format(pixel_format);
+        assert_eq!(max_difference(&format, &color, &frame(&format, 7)), 0);
+    }
+}
+
+#[test]
+fn threads_give_the_same_frame() {
+    for pixel_format in PixelFormat::ALL {
+        let format = format(pixel_format);
+        let color = ColorSpace::guess(&format);
+        let frame = frame(&format, 3);
+        let mut single = vec![0; WIDTH * HEIGHT];
+        Converter::new(&format, &color).to_xrgb(&frame, &mut single);
+        // more threads than rows leaves some without work
+        for threads in [2, 3, 8, HEIGHT + 5] {
+            let mut banded = vec![0; WIDTH * HEIGHT];
+            Converter::new(&format, &color).with_threads(threads).to_xrgb(&frame, &mut banded);
+            assert_eq!(single, banded, "{} on {} threads", pixel_format, threads);
+        }
+    }
+}
+
+#[test]
+fn xrgb_matches_rgba() {
+    let format = format(PixelFormat::Nv12);
+    let color = ColorSpace::guess(&format);
+    let frame = frame(&format, 11);
+    let converter = Converter::new(&format, &color);
+    let (mut rgba, mut xrgb) = (vec![0; WIDTH * HEIGHT * 4], vec![0; WIDTH * HEIGHT]);
+    converter.to_rgba(&frame, &mut rgba);
+    converter.to_xrgb(&frame, &mut xrgb);
+    for (rgba, xrgb) in rgba.chunks(4).zip(xrgb) {
+        assert_eq!(u32::from_be_bytes([0, rgba[0], rgba[1], rgba[2]]), xrgb);
+        assert_eq!(rgba[3], 255);
+    }
+}
@@ -0,0 +1,250 @@
+//! Keyboard transport: pausing, seeking, stepping and playback speed
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::sync::{Clock, Presenter};
+use crate::FrameRate;
+
+/// Playback speeds `Faster` and `Slower` go through
+pub const SPEEDS: [f64; 9] = [0.25, 0.5, 0.75, 1.0, 1.25, 1.5, 2.0, 3.0, 4.0];
+
+const NORMAL_SPEED: usize = 3;
+
+/// What a key asks for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    TogglePause,
+    /// Pause and move this many frames from the one on screen
+    Step(isize),
+    /// Move this many seek steps from the frame on screen
+    Seek(i32),
+    First,
+    Last,
+    Faster,
+    Slower,
+    NormalSpeed,
+}
+
+/// Where the reader is to go. The frames it reads from there carry `epoch`, which
+/// tells them apart from those read before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seek {
+    /// Frame index; past the end means the last frame
+    pub frame: usize,
+    pub epoch: u64,
+}
+
+/// Carries out `Action`s on the clock, and on the reader through the `Seek`s it hands
+/// out
+pub struct Transport {
+    clock: Arc<dyn Clock>,
+    frame_rate: FrameRate,
+    seek_step: Duration,
+    /// Index into `SPEEDS`
+    speed: usize,
+    epoch: u64,
+    /// Frame of the last seek, where to go on from until its first frame arrives
+    target: usize,
+}
+
+impl Transport {
+    pub fn new(clock: Arc<dyn Clock>, frame_rate: FrameRate, seek_step: Duration) -> Self {
+        Self { clock, frame_rate, seek_step, speed: NORMAL_SPEED, epoch: 0, target: 0 }
+    }
+
+    /// Carry out `action` from the frame `presenter` shows. Returns where the reader has
+    /// to go, if anywhere; `presenter` then waits for the frames from there.
+    pub fn apply<F>(&mut self, action: Action, presenter: &mut Presenter<F>) -> Option<Seek> {
+        let current = match presenter.current() {
+            Some(frame) if frame.epoch == self.epoch => frame.index,
+            _ => self.target,
+        };
+        let frame = match action {
+            Action::TogglePause => {
+                self.clock.set_paused(!self.clock.is_paused());
+                return None;
+            }
+            Action::Faster | Action::Slower | Action::NormalSpeed => {
+                self.speed = match action {
+                    Action::Faster => (self.speed + 1).min(SPEEDS.len() - 1),
+                    Action::Slower => self.speed.saturating_sub(1),
+                    _ => NORMAL_SPEED,
+                };
+                self.clock.set_speed(SPEEDS[self.speed]);
+                return None;
+            }
+            Action::Step(frames) => {
+                self.clock.set_paused(true);
+                current.saturating_add_signed(frames)
+            }
+            Action::Seek(steps) => {
+                let from = self.frame_rate.timestamp(current);
+                let by = self.seek_step * steps.unsigned_abs();
+                self.frame_rate.frame_at(if steps < 0 { from.saturating_sub(by) } else { from + by })
+            }
+            Action::First => 0,
+            Action::Last => usize::MAX,
+        };
+        self.epoch += 1;
+        self.target = frame;
+        presenter.seek(self.epoch);
+        Some(Seek { frame, epoch: self.epoch })
+    }
+
+    pub fn speed(&self) -> f64 {
+        SPEEDS[self.speed]
+    }
+}
+
+impl fmt::Display for Transport {
+    /// State for the console, like `paused at 12.480s, 0.5x`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = if self.clock.is_paused() { "paused" } else { "playing" };
+        write!(f, "{} at {:.3}s, {}x", state, self.clock.position().as_secs_f64(), self.speed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::TimedFrame;
+
+    /// Clock that only keeps what it is told
+    struct FakeClock {
+        /// Paused, speed
+        state: Mutex<(bool, f64)>,
+    }
+
+    impl Clock for FakeClock {
+        fn position(&self) -> Duration {
+            Duration::from_millis(12480)
+        }
+
+        fn seek(&self, _position: Duration) {}
+
+        fn set_paused(&self, paused: bool) {
+            self.state.lock().unwrap().0 = paused;
+        }
+
+        fn is_paused(&self) -> bool {
+            self.state.lock().unwrap().0
+        }
+
+        fn set_speed(&self, speed: f64) {
+            self.state.lock().unwrap().1 = speed;
+        }
+    }
+
+    /// Transport at 25 fps seeking by 5 s, and its clock, playing
+    fn transport() -> (Transport, Arc<FakeClock>) {
+        let clock = Arc::new(FakeClock { state: Mutex::new((false, 1.0)) });
+        let transport = Transport::new(Arc::clone(&clock) as Arc<dyn Clock>, FrameRate::new(25, 1), Duration::from_secs(5));
+        (transport, clock)
+    }
+
+    /// Hand the presenter frame `index` of `epoch`, as the reader would
+    fn receive(presenter: &mut Presenter<()>, index: usize, epoch: u64) {
+        let timestamp = FrameRate::new(25, 1).timestamp(index);
+        let mut frame = Some(TimedFrame { index, timestamp, epoch, frame: () });
+        presenter.update(timestamp, || frame.take());
+    }
+
+    fn shown(presenter: &Presenter<()>) -> Option<(usize, u64)> {
+        presenter.current().map(|frame| (frame.index, frame.epoch))
+    }
+
+    #[test]
+    fn step_from_the_frame_on_screen() {
+        let (mut transport, clock) = transport();
+        let mut presenter = Presenter::new();
+        receive(&mut presenter, 10, 0);
+
+        assert_eq!(transport.apply(Action::Step(1), &mut presenter), Some(Seek { frame: 11, epoch: 1 }));
+        assert!(clock.is_paused());
+        receive(&mut presenter, 11, 1);
+        assert_eq!(shown(&presenter), Some((11, 1)));
+        assert_eq!(transport.apply(Action::Step(-1), &mut presenter), Some(Seek { frame: 10, epoch: 2 }));
+        receive(&mut presenter, 10, 2);
+        assert_eq!(transport.apply(Action::Step(-1), &mut presenter), Some(Seek { frame: 9, epoch: 3 }));
+    }
+
+    #[test]
+    fn seek_by_steps_stops_at_zero() {
+        let (mut transport, clock) = transport();
+        let mut presenter = Presenter::new();
+        receive(&mut presenter, 50, 0);
+
+        // 2 s in, 5 s back
+        assert_eq!(transport.apply(Action::Seek(-1), &mut presenter), Some(Seek { frame: 0, epoch: 1 }));
+        receive(&mut presenter, 0, 1);
+        assert_eq!(transport.apply(Action::Step(-1), &mut presenter), Some(Seek { frame: 0, epoch: 2 }));
+        receive(&mut presenter, 0, 2);
+        assert_eq!(transport.apply(Action::Seek(2), &mut presenter), Some(Seek { frame: 250, epoch: 3 }));
+        // seeking leaves the clock running
+        clock.set_paused(false);
+        assert_eq!(transport.apply(Action::First, &mut presenter), Some(Seek { frame: 0, epoch: 4 }));
+        assert!(!clock.is_paused());
+    }
+
+    #[test]
+    fn last_goes_past_the_end() {
+        let (mut transport, _clock) = transport();
+        let mut presenter = Presenter::new();
+        receive(&mut presenter, 50, 0);
+
+        // the reader goes to its last frame instead, and goes on from the one it sends
+        assert_eq!(transport.apply(Action::Last, &mut presenter), Some(Seek { frame: usize::MAX, epoch: 1 }));
+        receive(&mut presenter, 99, 1);
+        assert_eq!(transport.apply(Action::Step(-1), &mut presenter), Some(Seek { frame: 98, epoch: 2 }));
+        receive(&mut presenter, 98, 2);
+        assert_eq!(transport.apply(Action::Seek(1000), &mut presenter).map(|seek| seek.frame), Some(98 + 125_000));
+    }
+
+    #[test]
+    fn seeks_before_the_first_frame_arrives_add_up() {
+        let (mut transport, _clock) = transport();
+        let mut presenter = Presenter::new();
+        receive(&mut presenter, 50, 0);
+
+        assert_eq!(transport.apply(Action::Seek(1), &mut presenter), Some(Seek { frame: 175, epoch: 1 }));
+        // frame 50 is still on screen, the next seek goes on from frame 175
+        assert_eq!(shown(&presenter), Some((50, 0)));
+        assert_eq!(transport.apply(Action::Seek(1), &mut presenter), Some(Seek { frame: 300, epoch: 2 }));
+        // a frame of the first seek arriving late is not shown and does not count
+        receive(&mut presenter, 175, 1);
+        assert_eq!(shown(&presenter), Some((50, 0)));
+        assert_eq!(transport.apply(Action::Step(1), &mut presenter), Some(Seek { frame: 301, epoch: 3 }));
+        receive(&mut presenter, 301, 3);
+        assert_eq!(transport.apply(Action::Step(1), &mut presenter), Some(Seek { frame: 302, epoch: 4 }));
+    }
+
+    #[test]
+    fn speed_stays_within_the_steps() {
+        let (mut transport, clock) = transport();
+        let mut presenter = Presenter::<()>::new();
+        for _ in 0..10 {
+            assert_eq!(transport.apply(Action::Slower, &mut presenter), None);
+        }
+        assert_eq!(transport.speed(), 0.25);
+        assert_eq!(clock.state.lock().unwrap().1, 0.25);
+
+        for _ in 0..20 {
+            transport.apply(Action::Faster, &mut presenter);
+        }
+        assert_eq!(transport.speed(), 4.0);
+        assert_eq!(clock.state.lock().unwrap().1, 4.0);
+        assert_eq!(transport.to_string(), "playing at 12.480s, 4x");
+
+        transport.apply(Action::NormalSpeed, &mut presenter);
+        assert_eq!(clock.state.lock().unwrap().1, 1.0);
+        transport.apply(Action::Slower, &mut presenter);
+        transport.apply(Action::TogglePause, &mut presenter);
+        assert_eq!(transport.to_string(), "paused at 12.480s, 0.75x");
+        // none of this seeks
+        assert!(presenter.current().is_none());
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+use std::io::{self, BufRead, Read};
+
+use crate::{ChromaSiting, FrameRate, PixelFormat, Range, VideoFormat};
+
+/// First bytes of every YUV4MPEG2 stream
+pub const MAGIC: &[u8] = b"YUV4MPEG2";
+
+/// Stream and frame headers are short; anything longer is not Y4M
+const MAX_HEADER_LEN: usize = 4096;
+
+/// How the fields of a frame were captured, the `I` tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interlacing {
+    Progressive,
+    TopFieldFirst,
+    BottomFieldFirst,
+    /// Given per frame in the `FRAME` headers
+    Mixed,
+    Unknown,
+}
+
+impl fmt::Display for Interlacing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Interlacing::Progressive => "progressive",
+            Interlacing::TopFieldFirst => "top field first",
+            Interlacing::BottomFieldFirst => "bottom field first",
+            Interlacing::Mixed => "mixed",
+            Interlacing::Unknown => "unknown",
+        })
+    }
+}
+
+/// Parameters from the stream header of a `.y4m` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub width: usize,
+    pub height: usize,
+    pub frame_rate: FrameRate,
+    pub interlacing: Interlacing,
+    /// Pixel aspect ratio, `(0, 0)` when unknown
+    pub pixel_aspect: (u32, u32),
+    /// The `C` tag as written, `420jpeg` when absent
+    pub colorspace: String,
+    pub pixel_format: PixelFormat,
+    /// Implied by the `C` tag for 4:2:0
+    pub chroma_siting: Option<ChromaSiting>,
+    /// From ffmpeg's `XCOLORRANGE` extension
+    pub color_range: Option<Range>,
+    /// `X` tags, vendor extensions such as `XYSCSS=420JPEG`
+    pub comments: Vec<String>,
+}
+
+impl Header {
+    /// Read and parse the stream header line
+    pub fn read(reader: &mut impl BufRead) -> io::Result<Self> {
+        let line = read_line(reader)?.ok_or_else(|| invalid("Empty file, expected a YUV4MPEG2 header".to_string()))?;
+        Self::parse(&line).map_err(invalid)
+    }
+
+    /// Parse the stream header line without its newline
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut tags = line.split(' ').filter(|tag| !tag.is_empty());
+        if tags.next().map(str::as_bytes) != Some(MAGIC) {
+            return Err("Not a YUV4MPEG2 stream".to_string());
+        }
+        let (mut width, mut height, mut frame_rate) = (None, None, None);
+        let mut interlacing = Interlacing::Unknown;
+        let mut pixel_aspect = (0, 0);
+        let mut colorspace = "420jpeg".to_string();
+        let mut comments = Vec::new();
+        for tag in tags {
+            let mut chars = tag.chars();
+            let kind = chars.next();
+            let value = chars.as_str();
+            match kind {
+                Some('W') => width = Some(parse_dimension(value)?),
+                Some('H') => height = Some(parse_dimension(value)?),
+                Some('F') => frame_rate = Some(value.parse()?),
+                Some('I') => {
+                    interlacing = match value {
+                        "p" => Interlacing::Progressive,
+                        "t" => Interlacing::TopFieldFirst,
+                        "b" => Interlacing::BottomFieldFirst,
+                        "m" => Interlacing::Mixed,
+                        _ => Interlacing::Unknown,
+                    }
+                }
+                Some('A') => pixel_aspect = parse_ratio(value).ok_or(format!("Invalid pixel aspect ratio {}", value))?,
+                Some('C') => colorspace = value.to_string(),
+                Some('X') => comments.push(value.to_string()),
+                // tags are single ASCII letters, anything else is a corrupt header
+                Some(kind) if !kind.is_ascii() => return Err(format!("Invalid YUV4MPEG2 tag {:?}", truncate(tag))),
+                // unknown tags are to be ignored
+                _ => {}
+            }
+        }
+        let pixel_format = colorspace_to_pixel_format(&colorspace)?;
+        let chroma_siting = match colorspace.as_str() {
+            // plain 420 is the default siting, that of 420jpeg
+            "420jpeg" | "420" => Some(ChromaSiting::Center),
+            "420mpeg2" => Some(ChromaSiting::Left),
+            "420paldv" => Some(ChromaSiting::TopLeft),
+            _ => None,
+        };
+        let color_range = comments.iter().find_map(|comment| match comment.as_str() {
+            "COLORRANGE=FULL" => Some(Range::Full),
+            "COLORRANGE=LIMITED" => Some(Range::Limited),
+            _ => None,
+        });
+        Ok(Self {
+            width: width.ok_or("YUV4MPEG2 header without width (W)")?,
+            height: height.ok_or("YUV4MPEG2 header without height (H)")?,
+            frame_rate: frame_rate.ok_or("YUV4MPEG2 header without frame rate (F)")?,
+            interlacing,
+            pixel_aspect,
+            colorspace,
+            pixel_format,
+            chroma_siting,
+            color_range,
+            comments,
+        })
+    }
+
+    pub fn format(&self) -> VideoFormat {
+        VideoFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: self.pixel_format,
+            frame_rate: self.frame_rate,
+        }
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}", self.format(), self.interlacing)?;
+        if self.pixel_aspect.0 != 0 && self.pixel_aspect.1 != 0 {
+            write!(f, ", pixel aspect {}:{}", self.pixel_aspect.0, self.pixel_aspect.1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Skip the `FRAME` header in front of each frame. Returns `false` at the end of the
+/// stream.
+pub fn read_frame_header(reader: &mut impl BufRead) -> io::Result<bool> {
+    match read_line(reader)? {
+        // frame parameters, if any, only repeat what the stream header says or
+        // concern interlacing, which is not rendered anyway
+        Some(line) if line == "FRAME" || line.starts_with("FRAME ") => Ok(true),
+        Some(line) => Err(invalid(format!("Expected a FRAME header, found {:?}", truncate(&line)))),
+        None => Ok(false),
+    }
+}
+
+fn colorspace_to_pixel_format(colorspace: &str) -> Result<PixelFormat, String> {
+    match colorspace {
+        "420jpeg" | "420paldv" | "420mpeg2" | "420" => Ok(PixelFormat::Yuv420p),
+        "422" => Ok(PixelFormat::Yuv422p),
+        "444" => Ok(PixelFormat::Yuv444p),
+        "420p10" => Ok(PixelFormat::Yuv420p10),
+        other => Err(format!("Unsupported YUV4MPEG2 colorspace {}", other)),
+    }
+}
+
+/// One line without its newline, `None` at the end of the stream
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    (&mut *reader).take(MAX_HEADER_LEN as u64 + 1).read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(invalid(if line.len() >= MAX_HEADER_LEN {
+            "YUV4MPEG2 header line too long".to_string()
+        } else {
+            "Truncated YUV4MPEG2 header".to_string()
+        }));
+    }
+    String::from_utf8(line).map(Some).map_err(|_| invalid("YUV4MPEG2 header is not text".to_string()))
+}
+
+fn parse_dimension(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(pixels) if pixels > 0 => Ok(pixels),
+        _ => Err(format!("Invalid dimension {}", value)),
+    }
+}
+
+fn parse_ratio(value: &str) -> Option<(u32, u32)> {
+    let (num, den) = value.split_once(':')?;
+    Some((num.parse().ok()?, den.parse().ok()?))
+}
+
+fn truncate(line: &str) -> &str {
+    line.get(..32).unwrap_or(line)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn parses_every_tag() {
+        let header = Header::parse("YUV4MPEG2 W1280 H720 F30000:1001 Ip A1:1 C420mpeg2 XCOLORRANGE=FULL XYSCSS=420MPEG2").unwrap();
+        assert_eq!((header.width, header.height), (1280, 720));
+        assert_eq!(header.frame_rate, FrameRate::new(30000, 1001));
+        assert_eq!(header.interlacing, Interlacing::Progressive);
+        assert_eq!(header.pixel_aspect, (1, 1));
+        assert_eq!(header.colorspace, "420mpeg2");
+        assert_eq!(header.pixel_format, PixelFormat::Yuv420p);
+        assert_eq!(header.chroma_siting, Some(ChromaSiting::Left));
+        assert_eq!(header.color_range, Some(Range::Full));
+        assert_eq!(header.comments, ["COLORRANGE=FULL", "YSCSS=420MPEG2"]);
+    }
+
+    #[test]
+    fn defaults_without_optional_tags() {
+        let header = Header::parse("YUV4MPEG2 W2 H2 F25:1").unwrap();
+        assert_eq!(header.interlacing, Interlacing::Unknown);
+        assert_eq!(header.pixel_aspect, (0, 0));
+        assert_eq!(header.colorspace, "420jpeg");
+        assert_eq!(header.chroma_siting, Some(ChromaSiting::Center));
+        assert_eq!(header.color_range, None);
+    }
+
+    #[test]
+    fn interlacing_and_colorspaces() {
+        for (tag, interlacing) in [
+            ("It", Interlacing::TopFieldFirst),
+            ("Ib", Interlacing::BottomFieldFirst),
+            ("Im", Interlacing::Mixed),
+            ("I?", Interlacing::Unknown),
+        ] {
+            assert_eq!(Header::parse(&format!("YUV4MPEG2 W2 H2 F25:1 {}", tag)).unwrap().interlacing, interlacing);
+        }
+        for (tag, pixel_format, siting) in [
+            ("C420paldv", PixelFormat::Yuv420p, Some(ChromaSiting::TopLeft)),
+            ("C420", PixelFormat::Yuv420p, Some(ChromaSiting::Center)),
+            ("C422", PixelFormat::Yuv422p, None),
+            ("C444", PixelFormat::Yuv444p, None),
+            ("C420p10", PixelFormat::Yuv420p10, None),
+        ] {
+            let header = Header::parse(&format!("YUV4MPEG2 W2 H2 F25:1 {}", tag)).unwrap();
+            assert_eq!((header.pixel_format, header.chroma_siting), (pixel_format, siting), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_colorspaces() {
+        let error = Header::parse("YUV4MPEG2 W2 H2 F25:1 Cmono").unwrap_err();
+        assert!(error.contains("mono"), "{}", error);
+    }
+
+    #[test]
+    fn ignores_unknown_tags() {
+        assert_eq!(Header::parse("YUV4MPEG2 W2 H2 F25:1 Zwhatever").unwrap().width, 2);
+    }
+
+    #[test]
+    fn requires_width_height_and_frame_rate() {
+        assert!(Header::parse("YUV4MPEG2 H2 F25:1").unwrap_err().contains("width"));
+        assert!(Header::parse("YUV4MPEG2 W2 F25:1").unwrap_err().contains("height"));
+        assert!(Header::parse("YUV4MPEG2 W2 H2").unwrap_err().contains("frame rate"));
+        assert!(Header::parse("YUV4MPEG2 W0 H2 F25:1").is_err());
+        assert!(Header::parse("YUV4MPEG2 W2 H2 F25:1 Ax").is_err());
+        assert!(Header::parse("YUV4MPEG W2 H2 F25:1").is_err());
+    }
+
+    #[test]
+    fn multibyte_tags_are_an_error() {
+        assert!(Header::parse("YUV4MPEG2 W2 H2 F25:1 ÿ1").is_err());
+        assert!(Header::parse("YUV4MPEG2 €W2 H2 F25:1").is_err());
+    }
+
+    #[test]
+    fn reads_frame_headers_with_parameters() {
+        let mut stream = Cursor::new(&b"FRAME\nFRAME Ip XTAG=1\nFRAMES\n"[..]);
+        assert!(read_frame_header(&mut stream).unwrap());
+        assert!(read_frame_header(&mut stream).unwrap());
+        assert!(read_frame_header(&mut stream).is_err());
+        assert!(!read_frame_header(&mut Cursor::new(&b""[..])).unwrap());
+        assert!(read_frame_header(&mut Cursor::new(&b"FRAME"[..])).is_err());
+    }
+
+    #[test]
+    fn reads_the_stream_header_line() {
+        let mut stream = Cursor::new(&b"YUV4MPEG2 W4 H2 F25:1 C444\nFRAME\n"[..]);
+        assert_eq!(Header::read(&mut stream).unwrap().pixel_format, PixelFormat::Yuv444p);
+        assert!(read_frame_header(&mut stream).unwrap());
+        let long = format!("YUV4MPEG2 W4 H2 F25:1 X{}\n", "a".repeat(MAX_HEADER_LEN));
+        assert!(Header::read(&mut Cursor::new(long.as_bytes())).is_err());
+    }
+}
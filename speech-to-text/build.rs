@@ -1,5 +1,85 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Directory containing libvosk, overrides every other lookup
+const VOSK_LIB_DIR: &str = "VOSK_LIB_DIR";
+
 fn main() {
-    //println!("cargo:rustc-link-search=native=vosk/vosk-api-0.3.50");
-    println!("cargo:rustc-link-search=native=vosk/vosk-osx-0.3.42");
-    println!("cargo:rustc-link-lib=dylib=vosk");
-}
\ No newline at end of file
+    println!("cargo:rerun-if-env-changed={}", VOSK_LIB_DIR);
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let lib_file = library_file_name(&target_os);
+
+    // 1. explicit directory from the environment
+    if let Some(dir) = env::var_os(VOSK_LIB_DIR) {
+        let dir = PathBuf::from(dir);
+        if !dir.join(lib_file).is_file() {
+            panic!(
+                "{} is set to {}, but {} was not found there",
+                VOSK_LIB_DIR,
+                dir.display(),
+                lib_file
+            );
+        }
+        link_from(&dir, &target_os);
+        return;
+    }
+
+    // 2. system wide install registered in pkg-config
+    if pkg_config::Config::new().probe("vosk").is_ok() {
+        return;
+    }
+
+    // 3. unpacked release archive next to the crate, e.g. vosk/vosk-linux-x86_64-0.3.45
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let default_dir = default_library_dir(&target_os, &target_arch).map(|dir| manifest_dir.join(dir));
+    if let Some(dir) = &default_dir {
+        if dir.join(lib_file).is_file() {
+            link_from(dir, &target_os);
+            return;
+        }
+    }
+
+    let tried = match &default_dir {
+        Some(dir) => format!("{} (not found)", dir.display()),
+        None => format!("no default directory for {}-{}", target_os, target_arch),
+    };
+    panic!(
+        "\n\nCould not find {lib_file}.\n\
+         Tried:\n  \
+           - ${VOSK_LIB_DIR} (not set)\n  \
+           - pkg-config vosk (not found)\n  \
+           - {tried}\n\
+         Download libvosk from https://github.com/alphacep/vosk-api/releases and either\n\
+         unpack it into the default directory or point {VOSK_LIB_DIR} at it.\n\n"
+    );
+}
+
+fn link_from(dir: &Path, target_os: &str) {
+    // the windows release ships libvosk.lib, everything else uses the lib prefix convention
+    let name = if target_os == "windows" { "libvosk" } else { "vosk" };
+    println!("cargo:rustc-link-search=native={}", dir.display());
+    println!("cargo:rustc-link-lib=dylib={}", name);
+}
+
+fn library_file_name(target_os: &str) -> &'static str {
+    match target_os {
+        "macos" => "libvosk.dylib",
+        "windows" => "libvosk.lib",
+        _ => "libvosk.so",
+    }
+}
+
+/// Where the per-platform release archive is expected, relative to the crate root
+fn default_library_dir(target_os: &str, target_arch: &str) -> Option<&'static str> {
+    match (target_os, target_arch) {
+        ("macos", _) => Some("vosk/vosk-osx-0.3.42"),
+        ("linux", "x86_64") => Some("vosk/vosk-linux-x86_64-0.3.45"),
+        ("linux", "aarch64") => Some("vosk/vosk-linux-aarch64-0.3.45"),
+        ("linux", "x86") => Some("vosk/vosk-linux-x86-0.3.45"),
+        ("windows", "x86_64") => Some("vosk/vosk-win64-0.3.45"),
+        _ => None,
+    }
+}
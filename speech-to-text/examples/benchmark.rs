@@ -0,0 +1,85 @@
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use speech_to_text::{AudioFile, Percentiles, SpeechToText};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} <model_path> <audio_file> [--chunk-ms <ms>] [--fast]", args[0]);
+        eprintln!("  --chunk-ms  audio pushed per call, like a sound card buffer (default 20)");
+        eprintln!("  --fast      push as fast as possible instead of at real-time pace");
+        std::process::exit(1);
+    }
+
+    let model_path = &args[1];
+    let filename = &args[2];
+    let mut chunk_ms = 20u64;
+    let mut paced = true;
+    let mut options = args[3..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--chunk-ms" => chunk_ms = options.next().ok_or("--chunk-ms needs a value")?.parse()?,
+            "--fast" => paced = false,
+            other => return Err(format!("Unknown option {}", other).into()),
+        }
+    }
+
+    let mut file = AudioFile::open(filename)?;
+    let sample_rate = file.sample_rate();
+    let mut stt = SpeechToText::new(model_path, sample_rate as f32)?;
+    stt.enable_metrics(true);
+
+    println!("Replaying {} ({} Hz) in {} ms chunks{}", filename, sample_rate, chunk_ms, if paced { " at real-time pace" } else { "" });
+
+    let chunk = (sample_rate as u64 * chunk_ms / 1000).max(1) as usize;
+    let mut samples = Vec::new();
+    let mut pushed = 0u64;
+    let start = Instant::now();
+    loop {
+        file.read_mono(&mut samples, chunk)?;
+        if samples.is_empty() {
+            break;
+        }
+        pushed += samples.len() as u64;
+        if paced {
+            // a sound card hands over a buffer once it has been recorded completely
+            let due = Duration::from_secs_f64(pushed as f64 / sample_rate as f64);
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        stt.push_audio_mono(&samples)?;
+    }
+    stt.finish()?;
+
+    let report = stt.metrics().map(|metrics| metrics.report()).unwrap_or_default();
+    println!();
+    println!("Audio:             {:8.2} s", report.audio);
+    println!("Processing:        {:8.2} s", report.processing);
+    println!("Real-time factor:  {:8.3}", report.real_time_factor);
+    println!();
+    println!("{:<22} {:>6} {:>9} {:>9} {:>9} {:>9}", "", "count", "p50", "p90", "p99", "max");
+    print_row("chunk processing [ms]", report.chunk_processing, 1000.0);
+    print_row("chunk real-time factor", report.chunk_real_time_factor, 1.0);
+    if paced {
+        print_row("first partial [ms]", report.first_partial_latency, 1000.0);
+        print_row("final result [ms]", report.final_result_latency, 1000.0);
+    } else {
+        println!("(latencies need real-time pacing, run without --fast)");
+    }
+    Ok(())
+}
+
+fn print_row(name: &str, p: Percentiles, scale: f32) {
+    println!(
+        "{:<22} {:>6} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+        name,
+        p.count,
+        p.p50 * scale,
+        p.p90 * scale,
+        p.p99 * scale,
+        p.max * scale
+    );
+}
@@ -0,0 +1,98 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use speech_to_text::wer::{align_chars, align_words, ErrorCounts};
+use speech_to_text::{AudioFile, Model, SpeechToText};
+
+/// Manifest format, one test file per line:
+///
+/// ```text
+/// # comment
+/// audio/001.wav<TAB>reference transcript of the first file
+/// audio/002.flac<TAB>reference transcript of the second file
+/// ```
+///
+/// Audio paths are relative to the manifest.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <model_path> <manifest.tsv>", args[0]);
+        std::process::exit(1);
+    }
+
+    let model_path = &args[1];
+    let manifest_path = Path::new(&args[2]);
+    let base_dir = manifest_path.parent().unwrap_or(Path::new("."));
+    let manifest = fs::read_to_string(manifest_path)?;
+
+    // one recognizer per sample rate, all on the same model
+    let model = Arc::new(Model::new(model_path.as_str()).ok_or("Model not found")?);
+    let mut recognizers: HashMap<u32, SpeechToText> = HashMap::new();
+    let mut words_total = ErrorCounts::default();
+    let mut chars_total = ErrorCounts::default();
+    let mut files = 0;
+
+    for (line_number, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((audio, reference)) = line.split_once('\t') else {
+            eprintln!("{}:{}: expected <audio path><TAB><reference>", manifest_path.display(), line_number + 1);
+            continue;
+        };
+
+        let audio_path = base_dir.join(audio);
+        let mut file = AudioFile::open(&audio_path)?;
+        let stt = match recognizers.entry(file.sample_rate()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(SpeechToText::from_model(Arc::clone(&model), file.sample_rate() as f32)?)
+            }
+        };
+        let hypothesis = stt.transcribe(&mut file, |_| {})?.text().replace('\n', " ");
+
+        let words = align_words(reference, &hypothesis);
+        let chars = align_chars(reference, &hypothesis);
+        words_total += words.counts;
+        chars_total += chars.counts;
+        files += 1;
+
+        println!("== {}", audio);
+        println!(
+            "WER {:6.2}%  CER {:6.2}%  (S {} I {} D {} / N {})",
+            words.counts.rate() * 100.0,
+            chars.counts.rate() * 100.0,
+            words.counts.substitutions,
+            words.counts.insertions,
+            words.counts.deletions,
+            words.counts.reference_len
+        );
+        let [reference_line, hypothesis_line, markers] = words.pretty();
+        println!("REF: {}", reference_line);
+        println!("HYP: {}", hypothesis_line);
+        println!("     {}", markers);
+        println!();
+    }
+
+    println!("== Total: {} files, {} reference words", files, words_total.reference_len);
+    println!(
+        "WER {:6.2}%  (S {} I {} D {})",
+        words_total.rate() * 100.0,
+        words_total.substitutions,
+        words_total.insertions,
+        words_total.deletions
+    );
+    println!(
+        "CER {:6.2}%  (S {} I {} D {})",
+        chars_total.rate() * 100.0,
+        chars_total.substitutions,
+        chars_total.insertions,
+        chars_total.deletions
+    );
+    Ok(())
+}
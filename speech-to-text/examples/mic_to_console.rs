@@ -1,132 +1,341 @@
-use std::sync::{Arc, Mutex};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
-use std::env;
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::Sample;
-use speech_to_text::SpeechToText;
+use cpal::SampleFormat;
+use rtrb::{Consumer, Producer, RingBuffer};
+use speech_to_text::{AudioSender, FrontEndConfig, SpeechEvent, SpeechToText, SpeechWorker, VadConfig};
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <model_path>", args[0]);
+    if args.len() < 2 {
+        eprintln!("Usage: {} <model_path> [--host <name>] [--device <name|index>] [--record <file.wav>] [--denoise]", args[0]);
+        eprintln!("       {} --list", args[0]);
+        eprintln!("  --list    show the audio hosts and their input devices");
+        eprintln!("  --host    audio host, e.g. ALSA or JACK (default: the system default)");
+        eprintln!("  --device  input device by index from --list or by (part of) its name");
+        eprintln!("  --record  also save the captured audio, to reproduce misrecognitions");
+        eprintln!("  --denoise high-pass, noise gate and gain control before recognition");
         std::process::exit(1);
     }
+    if args[1] == "--list" {
+        return list_devices();
+    }
 
     let model_path = &args[1];
-    let sample_rate = 48000.0;
-
-    // Create recognizer
-    let stt = Arc::new(Mutex::new(SpeechToText::new(model_path, sample_rate).expect("Failed to create recognizer")));
+    let mut host_name = None;
+    let mut device_name = None;
+    let mut record_path = None;
+    let mut denoise = false;
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let mut value = || options.next().ok_or(format!("{} needs a value", option));
+        match option.as_str() {
+            "--host" => host_name = Some(value()?),
+            "--device" => device_name = Some(value()?),
+            "--record" => record_path = Some(value()?),
+            "--denoise" => denoise = true,
+            other => return Err(format!("Unknown option {}", other).into()),
+        }
+    }
 
     // Set up audio input
-    let host = cpal::default_host();
-    let device = host.default_input_device().expect("No input device available");
-    let config = device.default_input_config().expect("Failed to get default input config");
-
-    // We'll downsample if needed, but expect 16kHz mono or stereo, i16
-    let stt_clone = stt.clone();
-    let err_fn = |err| eprintln!("Stream error: {}", err);
+    let host = match host_name {
+        Some(name) => select_host(name)?,
+        None => cpal::default_host(),
+    };
+    let device = match device_name {
+        Some(name) => select_device(&host, name)?,
+        None => host.default_input_device().ok_or("No input device available")?,
+    };
+    let config = device.default_input_config()?;
+    let sample_rate = config.sample_rate().0;
 
-    println!("Sample rate: {}", config.sample_rate().0);
-    println!("Sample format: {:?}", config.sample_format());
+    println!("Host: {}", host.id().name());
+    println!("Device: {}", device.name()?);
+    println!("Sample rate: {}", sample_rate);
+    println!("Sample format: {}", config.sample_format());
     println!("Channels: {}", config.channels());
     println!("Buffer size: {:?}", config.buffer_size());
 
-    let stream = match config.sample_format() {
-        cpal::SampleFormat::I16 => build_input_stream::<i16>(&device, &config.into(), stt_clone, err_fn),
-        cpal::SampleFormat::U16 => build_input_stream::<u16>(&device, &config.into(), stt_clone, err_fn),
-        cpal::SampleFormat::F32 => build_input_stream_f32_mono(&device, &config.into(), stt_clone, err_fn),
-        _ => panic!("Unsupported sample format"),
+    // Create recognizer, it gets the device rate so nothing needs resampling
+    let mut recognizer = SpeechToText::new(model_path, sample_rate as f32)?;
+    // skip silence instead of decoding it
+    recognizer.set_vad(Some(VadConfig::default()));
+    if denoise {
+        // the noise profile is learned from the first half second, best kept quiet
+        recognizer.set_front_end(Some(FrontEndConfig::default()));
+    }
+    // recognition runs on its own thread, the audio callback only queues samples
+    let (audio_sender, worker) = SpeechWorker::spawn(recognizer, sample_rate as usize * 2);
+
+    let (recorder, recording) = match record_path {
+        Some(path) => {
+            println!("Recording to {}", path);
+            let (recorder, recording) = Recorder::start(path, sample_rate)?;
+            (Some(recorder), Some(recording))
+        }
+        None => (None, None),
     };
 
-    stream.play().expect("Failed to start stream");
+    let stream_config = config.config();
+    let stream = match config.sample_format() {
+        SampleFormat::I8 => build_input_stream::<i8>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::I16 => build_input_stream::<i16>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::I24 => build_input_stream::<cpal::I24>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::I32 => build_input_stream::<i32>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::I64 => build_input_stream::<i64>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::U8 => build_input_stream::<u8>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::U16 => build_input_stream::<u16>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::U32 => build_input_stream::<u32>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::U64 => build_input_stream::<u64>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::F32 => build_input_stream::<f32>(&device, &stream_config, audio_sender, recording),
+        SampleFormat::F64 => build_input_stream::<f64>(&device, &stream_config, audio_sender, recording),
+        other => return Err(format!("Unsupported sample format {}", other).into()),
+    }?;
+
+    stream.play()?;
 
     println!("Listening... Press Ctrl+C to stop.");
+    let mut reported_overruns = 0;
+    let mut reported_recording_overruns = 0;
     loop {
-        thread::sleep(Duration::from_millis(500));
-        //let mut recognizer = stt.lock().unwrap();
+        match worker.events().recv_timeout(Duration::from_millis(500)) {
+            Ok(event) => print_event(event),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
 
-        // if let Some(partial) = recognizer.get_partial() {
-        //     if !partial.is_empty() {
-        //         println!("Partial: {}", partial);
-        //     }
-        // }
+        let stats = worker.stats();
+        if stats.overruns != reported_overruns {
+            reported_overruns = stats.overruns;
+            eprintln!("\rRecognizer is not keeping up: {} samples dropped in {} overruns", stats.dropped, stats.overruns);
+        }
+        if let Some(recorder) = &recorder {
+            let (dropped, overruns) = recorder.overruns();
+            if overruns != reported_recording_overruns {
+                reported_recording_overruns = overruns;
+                eprintln!("\rRecording is not keeping up: {} samples dropped in {} overruns", dropped, overruns);
+            }
+        }
+    }
 
-        //recognizer.get_result_wait();
+    // the stream holds the sending half of the recording
+    drop(stream);
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    Ok(())
+}
 
-        /*
-        if let Some(final_text) = recognizer.get_final_result() {
-            if !final_text.is_empty() {
-                println!("Final: {}", final_text);
+fn list_devices() -> Result<(), Box<dyn Error>> {
+    let default_host = cpal::default_host().id();
+    for id in cpal::available_hosts() {
+        let marker = if id == default_host { " (default)" } else { "" };
+        let host = match cpal::host_from_id(id) {
+            Ok(host) => host,
+            Err(err) => {
+                println!("{}{}: {}", id.name(), marker, err);
+                continue;
             }
+        };
+        println!("{}{}:", id.name(), marker);
+
+        let default_device = host.default_input_device().and_then(|device| device.name().ok());
+        for (index, device) in host.input_devices()?.enumerate() {
+            let name = device.name().unwrap_or_else(|_| "<unnamed>".to_string());
+            let marker = if Some(&name) == default_device.as_ref() { " (default)" } else { "" };
+            let config = match device.default_input_config() {
+                Ok(config) => format!("{} Hz, {} channels, {}", config.sample_rate().0, config.channels(), config.sample_format()),
+                Err(err) => err.to_string(),
+            };
+            println!("  {:>2}: {}{} [{}]", index, name, marker, config);
         }
-        */
     }
+    Ok(())
 }
 
-fn build_input_stream_f32_mono(
-    device: &cpal::Device,
-    config: &cpal::StreamConfig,
-    stt: Arc<Mutex<SpeechToText>>,
-    err_fn: fn(cpal::StreamError),
-) -> cpal::Stream {
-    device.build_input_stream(
-        config,
-        move |data: &[f32], _| {
-            let mut mono_samples = Vec::with_capacity(data.len());
-            for sample in data {
-                let s: i16 = f32::to_sample(*sample);
-                mono_samples.push(s);
-            }
-            let mut recognizer = stt.lock().unwrap();
-            recognizer.push_audio_mono(&mono_samples);
-        },
-        err_fn,
-        None,
-    ).expect("Failed to build input stream")
+fn select_host(name: &str) -> Result<cpal::Host, Box<dyn Error>> {
+    let id = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("No audio host named {}, see --list", name))?;
+    Ok(cpal::host_from_id(id)?)
 }
 
-    //T: cpal::Sample + Send + 'static,
+/// Device by its index in `--list`, by exact name, or by part of its name
+fn select_device(host: &cpal::Host, name: &str) -> Result<cpal::Device, Box<dyn Error>> {
+    if let Ok(index) = name.parse::<usize>() {
+        return host.input_devices()?.nth(index).ok_or_else(|| format!("No input device {}, see --list", index).into());
+    }
+
+    let devices: Vec<(String, cpal::Device)> = host
+        .input_devices()?
+        .filter_map(|device| Some((device.name().ok()?, device)))
+        .collect();
+    let wanted = name.to_lowercase();
+    let mut matching = devices.iter().filter(|(device_name, _)| device_name.to_lowercase().contains(&wanted));
+    let exact = devices.iter().find(|(device_name, _)| device_name == name);
+    match (exact, matching.next(), matching.next()) {
+        (Some((_, device)), _, _) | (None, Some((_, device)), None) => Ok(device.clone()),
+        (None, None, _) => Err(format!("No input device matching {}, see --list", name).into()),
+        (None, Some((first, _)), Some((second, _))) => {
+            Err(format!("{} matches several input devices ({}, {}, ...), be more specific", name, first, second).into())
+        }
+    }
+}
+
+/// Convert any sample format to i16, downmix to mono and hand the samples to the recognizer
+/// and, when recording, to the recorder
 fn build_input_stream<T>(
     device: &cpal::Device,
     config: &cpal::StreamConfig,
-    stt: Arc<Mutex<SpeechToText>>,
-    err_fn: fn(cpal::StreamError),
-) -> cpal::Stream
+    mut audio_sender: AudioSender,
+    mut recording: Option<RecordingSender>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
 where
-    T: cpal::SizedSample + cpal::FromSample<i16>, i16: cpal::FromSample<T>,
+    T: cpal::SizedSample,
+    i16: cpal::FromSample<T>,
 {
     let channels = config.channels as usize;
+    // a second of audio, more than any callback gets; the callback must not allocate
+    let mut mono_samples = Vec::with_capacity(config.sample_rate.0 as usize);
     device.build_input_stream(
         config,
         move |data: &[T], _| {
-            // Convert input to i16 mono
-            let mut mono_samples = Vec::with_capacity(data.len() / channels);
-            for sample in data {
-                let s = T::to_sample(*sample);
-                mono_samples.push(s);
-            }
-                /*
-            for frame in data.chunks(channels) {
-                let l = frame[0].to_signed_sample();
-                let sample = if channels > 1 {
-                    let r = frame[1].to_signed_sample();
-                    l.add_amp(amp)
-                    ((l + r) / 2) as i16
-                } else {
-                    l
-                };
-                mono_samples.push(sample);
-                mono_samples.push(frame[0].to_signed_sample());
+            mono_samples.clear();
+            mono_samples.extend(data.chunks(channels).map(|frame| {
+                let sum: i32 = frame.iter().map(|&sample| sample.to_sample::<i16>() as i32).sum();
+                (sum / frame.len() as i32) as i16
+            }));
+            audio_sender.push(&mono_samples);
+            if let Some(recording) = recording.as_mut() {
+                // the file is written on the recorder thread, never in the audio callback
+                recording.push(&mono_samples);
             }
-                */
-
-            let mut recognizer = stt.lock().unwrap();
-            recognizer.push_audio_mono(&mono_samples);
         },
-        err_fn,
+        |err| eprintln!("Stream error: {}", err),
         None,
-    ).expect("Failed to build input stream")
-}
\ No newline at end of file
+    )
+}
+
+/// Samples the recorder had no room for
+#[derive(Default)]
+struct Overruns {
+    dropped: AtomicU64,
+    count: AtomicU64,
+}
+
+/// Producing half of the recorder queue, for the audio callback. Like `AudioSender` it
+/// never blocks, locks or allocates: what does not fit is dropped and counted.
+struct RecordingSender {
+    producer: Producer<i16>,
+    overruns: Arc<Overruns>,
+}
+
+impl RecordingSender {
+    fn push(&mut self, samples: &[i16]) {
+        let n = samples.len().min(self.producer.slots());
+        if let Ok(chunk) = self.producer.write_chunk_uninit(n) {
+            chunk.fill_from_iter(samples.iter().copied());
+        }
+        if n < samples.len() {
+            self.overruns.dropped.fetch_add((samples.len() - n) as u64, Ordering::Relaxed);
+            self.overruns.count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Writes the captured audio to a mono 16-bit WAV file on its own thread
+struct Recorder {
+    overruns: Arc<Overruns>,
+    thread: thread::JoinHandle<Result<(), hound::Error>>,
+}
+
+impl Recorder {
+    /// Start writing, with two seconds of queue for when the disk is slow
+    fn start(path: &str, sample_rate: u32) -> Result<(Self, RecordingSender), hound::Error> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::new(BufWriter::new(File::create(path)?), spec)?;
+        let (producer, mut consumer) = RingBuffer::new(sample_rate as usize * 2);
+        let overruns = Arc::new(Overruns::default());
+        let thread = thread::spawn(move || {
+            let mut unflushed = 0;
+            let mut samples = Vec::new();
+            while next_samples(&mut consumer, &mut samples) {
+                for &sample in &samples {
+                    writer.write_sample(sample)?;
+                }
+                // keep the header up to date about once a second, so the file stays
+                // readable when the program is stopped with Ctrl+C
+                unflushed += samples.len();
+                if unflushed >= sample_rate as usize {
+                    writer.flush()?;
+                    unflushed = 0;
+                }
+            }
+            writer.finalize()
+        });
+        let sender = RecordingSender { producer, overruns: Arc::clone(&overruns) };
+        Ok((Self { overruns, thread }, sender))
+    }
+
+    /// Samples dropped and the number of times it happened
+    fn overruns(&self) -> (u64, u64) {
+        (self.overruns.dropped.load(Ordering::Relaxed), self.overruns.count.load(Ordering::Relaxed))
+    }
+
+    /// Wait for the rest to be written, once the `RecordingSender` is dropped
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.thread.join().map_err(|_| "Recorder thread panicked")??;
+        Ok(())
+    }
+}
+
+/// Replace `samples` with everything queued, waiting for more if there is none. False once
+/// the sender is gone and the queue is empty.
+fn next_samples(consumer: &mut Consumer<i16>, samples: &mut Vec<i16>) -> bool {
+    loop {
+        // checked before looking at the queue, so the last push before dropping is not missed
+        let abandoned = consumer.is_abandoned();
+        let available = consumer.slots();
+        if available > 0 {
+            if let Ok(chunk) = consumer.read_chunk(available) {
+                let (first, second) = chunk.as_slices();
+                samples.clear();
+                samples.extend_from_slice(first);
+                samples.extend_from_slice(second);
+                chunk.commit_all();
+                return true;
+            }
+        }
+        if abandoned {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn print_event(event: SpeechEvent) {
+    match event {
+        SpeechEvent::Partial(text) => {
+            print!("\rpartial:{}", text);
+            let _ = std::io::stdout().flush();
+        }
+        SpeechEvent::Result(utterance) => println!("\rresult: {:?}", utterance.text),
+        SpeechEvent::SpeechStarted { at } => println!("\r[{:.2}s] speech started", at),
+        SpeechEvent::SpeechEnded { at } => println!("\r[{:.2}s] speech ended", at),
+    }
+}
@@ -1,72 +1,115 @@
 use std::env;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use speech_to_text::SpeechToText;
+use std::io::{BufWriter, Write};
+use speech_to_text::{
+    AudioFile, DiarizationConfig, ExportFormat, FrontEndConfig, Language, Pipeline, SpeechEvent, SpeechToText,
+    VadConfig,
+};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: {} <model_path> <audio_file>", args[0]);
+    if args.len() < 3 {
+        eprintln!(
+            "Usage: {} <model_path> <audio_file> [--vad] [--denoise] [--postprocess en|pl] [--speaker-model <path>] [--format <format>] [--output <file>]",
+            args[0]
+        );
+        eprintln!("  --denoise high-pass, noise gate and gain control before recognition");
+        eprintln!("  --format  write the transcript as srt, jsonl, text or textgrid");
+        eprintln!("  --output  file for --format (default: standard output, without the live results)");
         std::process::exit(1);
     }
 
     let model_path = &args[1];
     let filename = &args[2];
+    let mut vad = false;
+    let mut denoise = false;
+    let mut post_processing = None;
+    let mut speaker_model = None;
+    let mut format = None;
+    let mut output = None;
+    let mut options = args[3..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--vad" => vad = true,
+            "--denoise" => denoise = true,
+            "--postprocess" => {
+                let language = match options.next().map(String::as_str) {
+                    Some("en") => Language::English,
+                    Some("pl") => Language::Polish,
+                    _ => return Err("--postprocess needs a language, en or pl".into()),
+                };
+                post_processing = Some(Pipeline::for_language(language));
+            }
+            "--speaker-model" => speaker_model = Some(options.next().ok_or("--speaker-model needs a path")?),
+            "--format" => format = Some(options.next().ok_or("--format needs a value")?.parse::<ExportFormat>()?),
+            "--output" => output = Some(options.next().ok_or("--output needs a path")?),
+            other => return Err(format!("Unknown option {}", other).into()),
+        }
+    }
 
-    // Create SpeechToText instance
-    let mut stt = SpeechToText::new(model_path, 48000.0)?;
-
-    // Open and read the audio file
-    let file = File::open(filename)?;
-    let mut reader = BufReader::new(file);
-
-    // Buffer to hold 48000 samples (48000 * 2 bytes per i16 sample)
-    let mut buffer = vec![0u8; 48000 * 2];
-    let mut samples = vec![0i16; 48000];
+    if output.is_some() && format.is_none() {
+        return Err("--output needs --format".into());
+    }
 
-    println!("Processing audio file: {}", filename);
-    println!("Reading 48000 samples at a time...");
+    // Sample rate and channel count come from the file header
+    let mut file = AudioFile::open(filename)?;
+    // with the transcript going to standard output nothing else may
+    let quiet = format.is_some() && output.is_none();
+    if !quiet {
+        println!("Processing audio file: {}", filename);
+        println!("Sample rate: {} Hz, channels: {}", file.sample_rate(), file.channels());
+    }
 
-    loop {
-        // Read raw bytes from file
-        let bytes_read = reader.read(&mut buffer)?;
+    // Create SpeechToText instance
+    let mut stt = SpeechToText::new(model_path, file.sample_rate() as f32)?;
+    if vad {
+        stt.set_vad(Some(VadConfig::default()));
+    }
+    if denoise {
+        stt.set_front_end(Some(FrontEndConfig::default()));
+    }
+    stt.set_post_processing(post_processing);
+    if let Some(path) = speaker_model {
+        // label every line with who said it
+        stt.set_speaker_model(path)?;
+        stt.set_diarization(Some(DiarizationConfig::default()));
+    }
 
-        // If no bytes read, we've reached end of file
-        if bytes_read == 0 {
-            break;
-        }
+    let transcript = stt.transcribe(&mut file, |event| if !quiet { print_event(event) })?;
 
-        // Convert bytes to i16 samples
-        for i in 0..(bytes_read / 2) {
-            if i < samples.len() {
-                let byte1 = buffer[i * 2] as u16;
-                let byte2 = buffer[i * 2 + 1] as u16;
-                samples[i] = ((byte2 << 8) | byte1) as i16;
-            }
+    match (format, output) {
+        (Some(format), Some(path)) => {
+            let mut out = BufWriter::new(File::create(path)?);
+            format.write(&transcript, &mut out)?;
+            out.flush()?;
+            println!("\nTranscript of {:.1}s of audio written to {}", transcript.duration, path);
         }
-
-        // Process the samples through SpeechToText
-        let actual_samples = &samples[..(bytes_read / 2)];
-        match stt.push_audio_mono(actual_samples) {
-            Ok(decoding_state) => {
-                match decoding_state {
-                    vosk::DecodingState::Running => {
-                        // Partial results are handled in push_audio_mono
-                    }
-                    vosk::DecodingState::Finalized => {
-                        // Final results are handled in push_audio_mono
-                    }
-                    vosk::DecodingState::Failed => {
-                        eprintln!("Decoding failed");
-                    }
+        (Some(format), None) => format.write(&transcript, std::io::stdout().lock())?,
+        (None, _) => {
+            println!("\nAudio processing complete! {:.1}s of audio", transcript.duration);
+            for utterance in &transcript.utterances {
+                let start = utterance.start().unwrap_or_default();
+                let end = utterance.end().unwrap_or_default();
+                match utterance.speaker_label() {
+                    Some(speaker) => println!("[{:8.2} - {:8.2}] {}: {}", start, end, speaker, utterance.text),
+                    None => println!("[{:8.2} - {:8.2}] {}", start, end, utterance.text),
                 }
             }
-            Err(e) => {
-                eprintln!("Error processing audio: {:?}", e);
-            }
         }
     }
-
-    println!("\nAudio processing complete!");
     Ok(())
-}
\ No newline at end of file
+}
+
+fn print_event(event: &SpeechEvent) {
+    match event {
+        SpeechEvent::Partial(text) => {
+            print!("\rpartial:{}", text);
+            let _ = std::io::stdout().flush();
+        }
+        SpeechEvent::Result(utterance) => {
+            println!("\rresult: {:?}", utterance.text);
+        }
+        SpeechEvent::SpeechStarted { at } => println!("\r[{:.2}s] speech started", at),
+        SpeechEvent::SpeechEnded { at } => println!("\r[{:.2}s] speech ended", at),
+    }
+}
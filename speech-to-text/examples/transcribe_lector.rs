@@ -0,0 +1,152 @@
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use speech_to_text::{AudioFile, ExportFormat, SpeechEvent, SpeechToText, Transcript, VadConfig};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        eprintln!(
+            "Usage: {} <model_path> <original track> <dubbed track> [--sample-rate <hz>] [--lag <samples>] [--max-lag-ms <ms>] [--vad] [--save-lector <file.pcm>] [--format <format>] [--output <file>]",
+            args[0]
+        );
+        eprintln!("  Tracks are WAV, FLAC or raw 16-bit little endian mono PCM (.pcm / .raw)");
+        eprintln!("  --sample-rate  rate of raw PCM tracks (default 48000)");
+        eprintln!("  --lag          dubbed sample i lines up with original sample i + lag (default: searched)");
+        eprintln!("  --max-lag-ms   how far apart the tracks may be for the search (default 1000)");
+        eprintln!("  --save-lector  also write the extracted lector voice as raw PCM");
+        eprintln!("  --format       write the transcript as srt, jsonl, text or textgrid");
+        eprintln!("  --output       file for --format (default: standard output)");
+        eprintln!("Timestamps are on the timeline of the original track, i.e. of the movie.");
+        std::process::exit(1);
+    }
+
+    let model_path = &args[1];
+    let mut sample_rate = 48000;
+    let mut lag = None;
+    let mut max_lag_ms = 1000;
+    let mut vad = false;
+    let mut save_lector = None;
+    let mut format = None;
+    let mut output = None;
+    let mut options = args[4..].iter();
+    while let Some(option) = options.next() {
+        let mut value = || options.next().ok_or(format!("{} needs a value", option));
+        match option.as_str() {
+            "--sample-rate" => sample_rate = value()?.parse()?,
+            "--lag" => lag = Some(value()?.parse::<isize>()?),
+            "--max-lag-ms" => max_lag_ms = value()?.parse()?,
+            "--vad" => vad = true,
+            "--save-lector" => save_lector = Some(value()?),
+            "--format" => format = Some(value()?.parse::<ExportFormat>()?),
+            "--output" => output = Some(value()?),
+            other => return Err(format!("Unknown option {}", other).into()),
+        }
+    }
+    if output.is_some() && format.is_none() {
+        return Err("--output needs --format".into());
+    }
+    // with the transcript going to standard output nothing else may
+    let quiet = format.is_some() && output.is_none();
+    let log = |message: String| if !quiet { println!("{}", message) };
+
+    let (original, original_rate) = load_track(&args[2], sample_rate)?;
+    let (dubbed, dubbed_rate) = load_track(&args[3], sample_rate)?;
+    if original_rate != dubbed_rate {
+        return Err(format!("Tracks differ in sample rate: {} Hz and {} Hz", original_rate, dubbed_rate).into());
+    }
+    let sample_rate = original_rate;
+    log(format!(
+        "Original: {:.1}s, dubbed: {:.1}s at {} Hz",
+        original.len() as f32 / sample_rate as f32,
+        dubbed.len() as f32 / sample_rate as f32,
+        sample_rate
+    ));
+
+    let lag = match lag {
+        Some(lag) => lag,
+        None => {
+            let max_lag = max_lag_ms * sample_rate as usize / 1000;
+            extract_lector::find_lag(&original, &dubbed, max_lag, sample_rate as usize)
+        }
+    };
+    let offset = lag as f32 / sample_rate as f32;
+    log(format!("Lag: {} samples ({:.1} ms)", lag, offset * 1000.0));
+
+    let lector = extract_lector::extract_lector(&original, &dubbed, lag);
+    if let Some(path) = save_lector {
+        extract_lector::write_pcm_i16(path, &lector)?;
+        log(format!("Lector voice saved to {}", path));
+    }
+
+    let mut stt = SpeechToText::new(model_path, sample_rate as f32)?;
+    if vad {
+        stt.set_vad(Some(VadConfig::default()));
+    }
+    let mut transcript = Transcript::default();
+    // a tenth of a second per call, like `SpeechToText::transcribe`
+    for chunk in lector.chunks(sample_rate as usize / 10) {
+        collect(stt.push_audio_mono(chunk)?, &mut transcript, quiet);
+    }
+    collect(stt.finish()?, &mut transcript, quiet);
+    transcript.duration = lector.len() as f32 / sample_rate as f32;
+    // the lector was cut on the dubbed track's timeline
+    transcript.shift(offset);
+
+    match (format, output) {
+        (Some(format), Some(path)) => {
+            let mut out = BufWriter::new(File::create(path)?);
+            format.write(&transcript, &mut out)?;
+            out.flush()?;
+            println!("\nTranscript written to {}", path);
+        }
+        (Some(format), None) => format.write(&transcript, std::io::stdout().lock())?,
+        (None, _) => {
+            println!();
+            for utterance in &transcript.utterances {
+                let start = utterance.start().unwrap_or_default();
+                let end = utterance.end().unwrap_or_default();
+                println!("[{:8.2} - {:8.2}] {}", start, end, utterance.text);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Samples and sample rate of a WAV / FLAC file, or of raw PCM at `raw_rate`
+fn load_track(path: &str, raw_rate: u32) -> Result<(Vec<i16>, u32), Box<dyn std::error::Error>> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or_default();
+    if matches!(extension.to_lowercase().as_str(), "pcm" | "raw") {
+        return Ok((extract_lector::read_pcm_i16(path)?, raw_rate));
+    }
+    let mut file = AudioFile::open(path)?;
+    let mut samples = Vec::new();
+    let mut block = Vec::new();
+    loop {
+        file.read_mono(&mut block, file.sample_rate() as usize)?;
+        if block.is_empty() {
+            return Ok((samples, file.sample_rate()));
+        }
+        samples.extend_from_slice(&block);
+    }
+}
+
+fn collect(events: Vec<SpeechEvent>, transcript: &mut Transcript, quiet: bool) {
+    for event in events {
+        match event {
+            SpeechEvent::Result(utterance) => {
+                if !quiet {
+                    println!("\rresult: {:?}", utterance.text);
+                }
+                transcript.utterances.push(utterance);
+            }
+            SpeechEvent::Partial(text) if !quiet => {
+                print!("\rpartial:{}", text);
+                let _ = std::io::stdout().flush();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::env;
+use std::sync::Arc;
+
+use speech_to_text::{Model, ServiceConfig, TranscriptionService, VadConfig};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <model_path> [--listen <addr:port>] [--max-sessions <n>] [--vad]", args[0]);
+        eprintln!("  --listen        address to serve on (default 127.0.0.1:2700)");
+        eprintln!("  --max-sessions  uploads and streams handled at once (default 4)");
+        eprintln!("  --vad           voice activity detection for streams");
+        std::process::exit(1);
+    }
+
+    let model_path = &args[1];
+    let mut listen = "127.0.0.1:2700".to_string();
+    let mut config = ServiceConfig::default();
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--listen" => listen = options.next().ok_or("--listen needs an address")?.clone(),
+            "--max-sessions" => config.max_sessions = options.next().ok_or("--max-sessions needs a value")?.parse()?,
+            "--vad" => config.vad = Some(VadConfig::default()),
+            other => return Err(format!("Unknown option {}", other).into()),
+        }
+    }
+
+    println!("Loading model {}", model_path);
+    let model = Arc::new(Model::new(model_path.as_str()).ok_or("Model not found")?);
+    let service = TranscriptionService::bind(listen.as_str(), model, config)?;
+    let addr = service.local_addr().ok_or("Not listening on an IP address")?;
+
+    println!("Listening on http://{}", addr);
+    println!("  curl --data-binary @audio.wav http://{}/transcribe", addr);
+    println!("  ws://{}/stream?sample_rate=16000, binary 16-bit mono audio, then {{\"eof\": true}}", addr);
+    service.run();
+    Ok(())
+}
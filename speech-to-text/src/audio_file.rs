@@ -0,0 +1,255 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+
+use claxon::FlacReader;
+use hound::{SampleFormat, WavReader};
+
+use crate::Error;
+
+type Source = Box<dyn Read + Send>;
+
+enum Decoder {
+    Wav(WavReader<Source>),
+    Flac { reader: FlacReader<Source>, buffer: Vec<i32> },
+}
+
+/// WAV or FLAC file read as 16 bit mono, whatever the stored format
+pub struct AudioFile {
+    decoder: Decoder,
+    sample_rate: u32,
+    channels: u16,
+    bits_per_sample: u16,
+    /// Total length in frames, if the header tells
+    frames: Option<u64>,
+}
+
+impl AudioFile {
+    /// Open a file, telling WAV and FLAC apart by their signature
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        Self::from_reader(BufReader::new(file)).map_err(|e| match e {
+            Error::InvalidAudio(reason) => Error::InvalidAudio(format!("{}: {}", path.display(), reason)),
+            e => e,
+        })
+    }
+
+    /// Read a WAV or FLAC stream, e.g. an upload held in memory
+    pub fn from_reader(mut reader: impl Read + Send + 'static) -> Result<Self, Error> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => Error::InvalidAudio("file too short".to_string()),
+            _ => Error::Io(e),
+        })?;
+        // put the signature back in front, the decoders want to see it
+        let source: Source = Box::new(Cursor::new(magic).chain(reader));
+
+        match &magic {
+            b"RIFF" => {
+                let reader = WavReader::new(source).map_err(wav_error)?;
+                let spec = reader.spec();
+                Ok(Self {
+                    frames: Some(reader.duration() as u64),
+                    sample_rate: spec.sample_rate,
+                    channels: spec.channels,
+                    bits_per_sample: spec.bits_per_sample,
+                    decoder: Decoder::Wav(reader),
+                })
+            }
+            b"fLaC" => {
+                let reader = FlacReader::new(source).map_err(flac_error)?;
+                let info = reader.streaminfo();
+                Ok(Self {
+                    frames: info.samples,
+                    sample_rate: info.sample_rate,
+                    channels: info.channels as u16,
+                    bits_per_sample: info.bits_per_sample as u16,
+                    decoder: Decoder::Flac { reader, buffer: Vec::new() },
+                })
+            }
+            _ => Err(Error::InvalidAudio("not a WAV or FLAC file".to_string())),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Channels stored in the file, `read_mono` mixes them down
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Length in seconds, if the header tells
+    pub fn duration(&self) -> Option<f32> {
+        self.frames.map(|frames| frames as f32 / self.sample_rate as f32)
+    }
+
+    /// Replace `out` with the next stretch of audio mixed down to mono: at most `max_frames`
+    /// frames of a WAV file, one block of a FLAC file. Leaves `out` empty at the end of the file.
+    pub fn read_mono(&mut self, out: &mut Vec<i16>, max_frames: usize) -> Result<(), Error> {
+        out.clear();
+        let channels = self.channels.max(1) as usize;
+        let bits = self.bits_per_sample;
+
+        match &mut self.decoder {
+            Decoder::Wav(reader) => {
+                let mut frame = Vec::with_capacity(channels);
+                let float = reader.spec().sample_format == SampleFormat::Float;
+                let wanted = max_frames * channels;
+                if float {
+                    for sample in reader.samples::<f32>().take(wanted) {
+                        frame.push((sample.map_err(wav_error)? * i16::MAX as f32) as i32);
+                        mix_frame(&mut frame, channels, 16, out);
+                    }
+                } else {
+                    for sample in reader.samples::<i32>().take(wanted) {
+                        frame.push(sample.map_err(wav_error)?);
+                        mix_frame(&mut frame, channels, bits, out);
+                    }
+                }
+            }
+            Decoder::Flac { reader, buffer } => {
+                // one FLAC block at a time, blocks are a few thousand frames
+                let mut blocks = reader.blocks();
+                let block = match blocks.read_next_or_eof(std::mem::take(buffer)).map_err(flac_error)? {
+                    Some(block) => block,
+                    None => return Ok(()),
+                };
+                let mut frame = Vec::with_capacity(channels);
+                for i in 0..block.duration() {
+                    for ch in 0..block.channels() {
+                        frame.push(block.sample(ch, i));
+                    }
+                    mix_frame(&mut frame, channels, bits, out);
+                }
+                *buffer = block.into_buffer();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Once `frame` holds a sample of every channel, average them, scale to 16 bits and push to `out`
+fn mix_frame(frame: &mut Vec<i32>, channels: usize, bits: u16, out: &mut Vec<i16>) {
+    if frame.len() < channels {
+        return;
+    }
+    let sum: i64 = frame.iter().map(|&s| s as i64).sum();
+    let avg = sum / channels as i64;
+    let scaled = if bits > 16 { avg >> (bits - 16) } else { avg << (16 - bits) };
+    out.push(scaled.clamp(i16::MIN as i64, i16::MAX as i64) as i16);
+    frame.clear();
+}
+
+fn wav_error(e: hound::Error) -> Error {
+    match e {
+        hound::Error::IoError(e) => Error::Io(e),
+        e => Error::InvalidAudio(e.to_string()),
+    }
+}
+
+fn flac_error(e: claxon::Error) -> Error {
+    match e {
+        claxon::Error::IoError(e) => Error::Io(e),
+        e => Error::InvalidAudio(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hound::{WavSpec, WavWriter};
+
+    use super::*;
+
+    fn spec(channels: u16, bits_per_sample: u16, sample_format: SampleFormat) -> WavSpec {
+        WavSpec { channels, sample_rate: 16000, bits_per_sample, sample_format }
+    }
+
+    /// WAV file in memory, `write` puts the samples in
+    fn wav(spec: WavSpec, write: impl FnOnce(&mut WavWriter<&mut Cursor<Vec<u8>>>)) -> AudioFile {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(&mut bytes, spec).unwrap();
+        write(&mut writer);
+        writer.finalize().unwrap();
+        AudioFile::from_reader(Cursor::new(bytes.into_inner())).unwrap()
+    }
+
+    fn read_all(file: &mut AudioFile) -> Vec<i16> {
+        let mut all = Vec::new();
+        let mut chunk = Vec::new();
+        loop {
+            file.read_mono(&mut chunk, 3).unwrap();
+            if chunk.is_empty() {
+                return all;
+            }
+            all.extend_from_slice(&chunk);
+        }
+    }
+
+    fn invalid(bytes: &[u8]) -> String {
+        match AudioFile::from_reader(Cursor::new(bytes.to_vec())) {
+            Err(Error::InvalidAudio(reason)) => reason,
+            Err(e) => panic!("{:?} instead of InvalidAudio", e),
+            Ok(_) => panic!("{:?} accepted", bytes),
+        }
+    }
+
+    #[test]
+    fn detects_the_format_by_signature() {
+        let mut file = wav(spec(1, 16, SampleFormat::Int), |w| w.write_sample(7i16).unwrap());
+        assert_eq!(read_all(&mut file), [7]);
+        assert_eq!(invalid(b"OggS\0\0\0\0"), "not a WAV or FLAC file");
+        assert_eq!(invalid(b"RIF"), "file too short");
+        // the signature alone is not enough, the decoders run out of header
+        assert!(AudioFile::from_reader(Cursor::new(b"RIFF\0\0\0\0WAVE".to_vec())).is_err());
+        assert!(AudioFile::from_reader(Cursor::new(b"fLaC\0\0\0\0".to_vec())).is_err());
+    }
+
+    #[test]
+    fn header_values() {
+        let file = wav(spec(2, 16, SampleFormat::Int), |w| (0..16000).for_each(|_| w.write_sample(0i16).unwrap()));
+        assert_eq!(file.sample_rate(), 16000);
+        assert_eq!(file.channels(), 2);
+        assert_eq!(file.duration(), Some(0.5));
+    }
+
+    #[test]
+    fn stereo_is_mixed_to_mono() {
+        let frames = [(1000i16, 3000i16), (-32768, -32768), (32767, -32768), (-5, 0), (100, 100)];
+        let mut file = wav(spec(2, 16, SampleFormat::Int), |w| {
+            for (left, right) in frames {
+                w.write_sample(left).unwrap();
+                w.write_sample(right).unwrap();
+            }
+        });
+        // rounded towards zero; chunks of 3 frames end on frame boundaries
+        assert_eq!(read_all(&mut file), [2000, -32768, 0, -2, 100]);
+    }
+
+    #[test]
+    fn integer_samples_are_scaled_to_16_bits() {
+        let mut file = wav(spec(1, 8, SampleFormat::Int), |w| [64i8, -128, 127].iter().for_each(|&s| w.write_sample(s).unwrap()));
+        assert_eq!(read_all(&mut file), [16384, -32768, 32512]);
+
+        let mut file = wav(spec(1, 24, SampleFormat::Int), |w| {
+            [0x40_0000, -0x80_0000, 0x7f_ffff, 0xff].iter().for_each(|&s: &i32| w.write_sample(s).unwrap())
+        });
+        assert_eq!(read_all(&mut file), [16384, -32768, 32767, 0]);
+
+        let mut file = wav(spec(1, 32, SampleFormat::Int), |w| {
+            [1 << 30, i32::MIN, i32::MAX].iter().for_each(|&s| w.write_sample(s).unwrap())
+        });
+        assert_eq!(read_all(&mut file), [16384, -32768, 32767]);
+    }
+
+    #[test]
+    fn float_samples_are_scaled_to_16_bits() {
+        let mut file = wav(spec(2, 32, SampleFormat::Float), |w| {
+            [0.5f32, 0.5, -1.0, -1.0, 1.0, 0.0, 2.0, 2.0].iter().for_each(|&s| w.write_sample(s).unwrap())
+        });
+        // out of range values are clamped
+        assert_eq!(read_all(&mut file), [16383, -32767, 16383, 32767]);
+    }
+}
@@ -0,0 +1,57 @@
+use vosk::{DecodingState, Recognizer, SpeakerModel};
+
+use crate::{Error, Utterance};
+
+/// What `SpeechToText` needs from a recognizer. Vosk's is the only real one, the trait
+/// exists so the stream handling can be tested without a model.
+pub(crate) trait Backend: Send {
+    fn accept_waveform(&mut self, samples: &[i16]) -> Result<DecodingState, Error>;
+
+    /// Hypothesis of the utterance in progress and the start of its first word,
+    /// in recognizer time
+    fn partial_result(&mut self) -> (String, Option<f32>);
+
+    /// Utterance Vosk finalized on its own, word times shifted by `offset`
+    fn result(&mut self, offset: f32) -> Option<Utterance>;
+
+    /// Finalize the utterance in progress, word times shifted by `offset`
+    fn final_result(&mut self, offset: f32) -> Option<Utterance>;
+
+    /// Drop the utterance in progress without decoding it
+    fn reset(&mut self);
+
+    fn set_max_alternatives(&mut self, max_alternatives: u16);
+
+    fn set_speaker_model(&mut self, speaker_model: &SpeakerModel);
+}
+
+impl Backend for Recognizer {
+    fn accept_waveform(&mut self, samples: &[i16]) -> Result<DecodingState, Error> {
+        Ok(Recognizer::accept_waveform(self, samples)?)
+    }
+
+    fn partial_result(&mut self) -> (String, Option<f32>) {
+        let partial = Recognizer::partial_result(self);
+        (partial.partial.to_string(), partial.partial_result.first().map(|word| word.start))
+    }
+
+    fn result(&mut self, offset: f32) -> Option<Utterance> {
+        Utterance::from_vosk(Recognizer::result(self), offset)
+    }
+
+    fn final_result(&mut self, offset: f32) -> Option<Utterance> {
+        Utterance::from_vosk(Recognizer::final_result(self), offset)
+    }
+
+    fn reset(&mut self) {
+        Recognizer::reset(self);
+    }
+
+    fn set_max_alternatives(&mut self, max_alternatives: u16) {
+        Recognizer::set_max_alternatives(self, max_alternatives);
+    }
+
+    fn set_speaker_model(&mut self, speaker_model: &SpeakerModel) {
+        Recognizer::set_speaker_model(self, speaker_model);
+    }
+}
@@ -0,0 +1,207 @@
+/// Speaker embedding Vosk computes for a final result when a speaker model is loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct XVector {
+    pub vector: Vec<f32>,
+    /// Number of 10 ms frames with speech the vector was computed from, short utterances
+    /// give unreliable vectors
+    pub frames: u16,
+}
+
+/// Settings of the online speaker clustering
+#[derive(Debug, Clone)]
+pub struct DiarizationConfig {
+    /// Cosine similarity to a known speaker from which an utterance is attributed to them,
+    /// below it a new speaker is started
+    pub similarity_threshold: f32,
+    /// Utterances with fewer speech frames are labeled but do not update the speaker profiles
+    pub min_frames: u16,
+    /// Once this many speakers are known every utterance goes to the closest one
+    pub max_speakers: Option<usize>,
+}
+
+impl Default for DiarizationConfig {
+    fn default() -> Self {
+        Self { similarity_threshold: 0.5, min_frames: 100, max_speakers: None }
+    }
+}
+
+/// Online clustering of x-vectors into speakers.
+///
+/// Each speaker is the frame weighted mean of the normalized vectors attributed to them.
+/// Labels are indices in the order speakers were first heard and never change, so they stay
+/// stable across a file; an utterance is never moved to another speaker afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct SpeakerClustering {
+    config: DiarizationConfig,
+    /// Unnormalized centroid and the frames it was built from
+    speakers: Vec<(Vec<f32>, u32)>,
+}
+
+impl SpeakerClustering {
+    pub fn new(config: DiarizationConfig) -> Self {
+        Self { config, speakers: Vec::new() }
+    }
+
+    pub fn config(&self) -> &DiarizationConfig {
+        &self.config
+    }
+
+    /// Number of speakers found so far
+    pub fn speakers(&self) -> usize {
+        self.speakers.len()
+    }
+
+    /// Forget all speakers, e.g. before the next file
+    pub fn reset(&mut self) {
+        self.speakers.clear();
+    }
+
+    /// Label of the speaker of `xvector`, starting a new speaker if it matches none
+    pub fn assign(&mut self, xvector: &XVector) -> usize {
+        let vector = normalized(&xvector.vector);
+        let closest = self
+            .speakers
+            .iter()
+            .enumerate()
+            .map(|(label, (centroid, _))| (label, cosine_similarity(&vector, centroid)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let full = self.config.max_speakers.is_some_and(|max| self.speakers.len() >= max);
+        let reliable = xvector.frames >= self.config.min_frames;
+        let label = match closest {
+            Some((label, similarity)) if similarity >= self.config.similarity_threshold || full || !reliable => label,
+            _ => {
+                self.speakers.push((vec![0.0; vector.len()], 0));
+                self.speakers.len() - 1
+            }
+        };
+
+        if reliable || self.speakers[label].1 == 0 {
+            let (centroid, frames) = &mut self.speakers[label];
+            for (c, v) in centroid.iter_mut().zip(&vector) {
+                *c += v * xvector.frames.max(1) as f32;
+            }
+            *frames += xvector.frames.max(1) as u32;
+        }
+        label
+    }
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|v| v / norm).collect()
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm = a.iter().map(|v| v * v).sum::<f32>().sqrt() * b.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        0.0
+    } else {
+        dot / norm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMENSIONS: usize = 16;
+
+    /// Vector at `degrees` in the plane of the first two dimensions, plus a
+    /// little of the `jitter` dimension so no two are identical
+    fn xvector(degrees: f32, jitter: usize, frames: u16) -> XVector {
+        let mut vector = vec![0.0; DIMENSIONS];
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        vector[0] = cos * 3.0;
+        vector[1] = sin * 3.0;
+        vector[2 + jitter % (DIMENSIONS - 2)] = 0.3;
+        XVector { vector, frames }
+    }
+
+    /// Vector along one dimension of its own, unlike any other
+    fn axis(dimension: usize, frames: u16) -> XVector {
+        let mut vector = vec![0.0; DIMENSIONS];
+        vector[dimension] = 1.0;
+        XVector { vector, frames }
+    }
+
+    #[test]
+    fn two_separated_speakers() {
+        let mut clustering = SpeakerClustering::new(DiarizationConfig::default());
+        // speaker one around 0°, speaker two around 90°
+        let labels: Vec<usize> =
+            (0..8).map(|i| clustering.assign(&xvector(if i % 3 == 0 { 90.0 } else { 0.0 } + i as f32, i, 300))).collect();
+        assert_eq!(labels, [0, 1, 1, 0, 1, 1, 0, 1]);
+        assert_eq!(clustering.speakers(), 2);
+    }
+
+    #[test]
+    fn vector_close_to_no_speaker_starts_a_new_one() {
+        let mut clustering = SpeakerClustering::new(DiarizationConfig::default());
+        assert_eq!(clustering.assign(&xvector(0.0, 0, 300)), 0);
+        assert_eq!(clustering.assign(&xvector(90.0, 1, 300)), 1);
+        assert_eq!(clustering.assign(&axis(15, 300)), 2);
+        assert_eq!(clustering.speakers(), 3);
+
+        // unless the maximum is reached, then the closest one takes it
+        let config = DiarizationConfig { max_speakers: Some(2), ..Default::default() };
+        let mut clustering = SpeakerClustering::new(config);
+        clustering.assign(&xvector(0.0, 0, 300));
+        clustering.assign(&xvector(90.0, 1, 300));
+        assert_eq!(clustering.assign(&xvector(80.0, 2, 300)), 1);
+        // cos 70° = 0.34 to the first speaker, a new one without the limit
+        assert_eq!(clustering.assign(&xvector(-70.0, 0, 300)), 0);
+        assert_eq!(clustering.speakers(), 2);
+    }
+
+    #[test]
+    fn similarity_threshold() {
+        // cos 55° = 0.57 and cos 65° = 0.42, on both sides of the default 0.5
+        let mut clustering = SpeakerClustering::new(DiarizationConfig::default());
+        clustering.assign(&axis(0, 300));
+        assert_eq!(clustering.assign(&xvector(65.0, 0, 300)), 1);
+
+        let mut clustering = SpeakerClustering::new(DiarizationConfig::default());
+        clustering.assign(&axis(0, 300));
+        assert_eq!(clustering.assign(&xvector(55.0, 0, 300)), 0);
+
+        let config = DiarizationConfig { similarity_threshold: 0.6, ..Default::default() };
+        let mut clustering = SpeakerClustering::new(config);
+        clustering.assign(&axis(0, 300));
+        assert_eq!(clustering.assign(&xvector(55.0, 0, 300)), 1);
+    }
+
+    #[test]
+    fn centroid_follows_the_speaker() {
+        let mut clustering = SpeakerClustering::new(DiarizationConfig::default());
+        clustering.assign(&axis(0, 300));
+        // at 75° far from the first vector (cos 0.26), but close to the mean of it and one at 55°
+        assert_eq!(clustering.assign(&xvector(55.0, 0, 300)), 0);
+        assert_eq!(clustering.assign(&xvector(75.0, 1, 300)), 0);
+        assert_eq!(clustering.speakers(), 1);
+    }
+
+    #[test]
+    fn short_utterances_do_not_move_the_centroid() {
+        let mut clustering = SpeakerClustering::new(DiarizationConfig::default());
+        clustering.assign(&axis(0, 300));
+        // too short to be reliable: labeled with the closest speaker rather than a new one...
+        assert_eq!(clustering.assign(&xvector(55.0, 0, 50)), 0);
+        assert_eq!(clustering.assign(&axis(15, 50)), 0);
+        // ...and the centroid stayed where it was
+        assert_eq!(clustering.assign(&xvector(75.0, 1, 300)), 1);
+
+        // the very first one still makes a speaker
+        let mut clustering = SpeakerClustering::new(DiarizationConfig::default());
+        assert_eq!(clustering.assign(&axis(0, 50)), 0);
+        assert_eq!(clustering.assign(&axis(0, 300)), 0);
+
+        clustering.reset();
+        assert_eq!(clustering.speakers(), 0);
+        assert_eq!(clustering.assign(&axis(15, 300)), 0);
+    }
+}
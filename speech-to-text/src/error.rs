@@ -0,0 +1,83 @@
+use std::fmt;
+
+use vosk::AcceptWaveformError;
+
+/// Everything that can go wrong in this crate
+#[derive(Debug)]
+pub enum Error {
+    /// Model or speaker model missing, incomplete, or rejected by Vosk when loading
+    ModelNotFound(String),
+    /// Vosk could not create a recognizer, e.g. for a grammar the model does not support
+    RecognizerCreation(String),
+    /// Audio that cannot be decoded or does not fit the recognizer
+    InvalidAudio(String),
+    /// Vosk failed while recognizing, or the recognition thread died
+    Backend(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ModelNotFound(reason) => write!(f, "model not found: {}", reason),
+            Error::RecognizerCreation(reason) => write!(f, "cannot create recognizer: {}", reason),
+            Error::InvalidAudio(reason) => write!(f, "invalid audio: {}", reason),
+            Error::Backend(reason) => write!(f, "recognition failed: {}", reason),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<AcceptWaveformError> for Error {
+    fn from(e: AcceptWaveformError) -> Self {
+        Error::Backend(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn messages_name_the_kind() {
+        let cases = [
+            (Error::ModelNotFound("models/en: no such directory".into()), "model not found: models/en: no such directory"),
+            (Error::RecognizerCreation("no lookahead graph".into()), "cannot create recognizer: no lookahead graph"),
+            (Error::InvalidAudio("8 channels".into()), "invalid audio: 8 channels"),
+            (Error::Backend("worker thread panicked".into()), "recognition failed: worker thread panicked"),
+        ];
+        for (error, message) in cases {
+            assert_eq!(error.to_string(), message);
+            assert!(error.source().is_none());
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "speech.wav"));
+        assert!(matches!(&error, Error::Io(e) if e.kind() == io::ErrorKind::NotFound));
+        assert_eq!(error.to_string(), "speech.wav");
+        assert_eq!(error.source().unwrap().to_string(), "speech.wav");
+
+        let error = Error::from(AcceptWaveformError::BufferTooLong(usize::MAX));
+        assert!(matches!(&error, Error::Backend(reason) if reason.starts_with("the length of the provided audio buffer")));
+    }
+}
@@ -0,0 +1,383 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+use crate::{SpeechEvent, Transcript, Utterance};
+
+/// File formats a `Transcript` can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// SubRip subtitles
+    Srt,
+    /// One JSON object per utterance and line, with per-word timings
+    JsonLines,
+    /// One `[start - end] text` line per utterance
+    Text,
+    /// Praat TextGrid with utterance and word tiers
+    TextGrid,
+}
+
+impl ExportFormat {
+    pub fn write(self, transcript: &Transcript, out: impl Write) -> io::Result<()> {
+        match self {
+            ExportFormat::Srt => write_srt(transcript, out),
+            ExportFormat::JsonLines => write_jsonl(transcript, out),
+            ExportFormat::Text => write_text(transcript, out),
+            ExportFormat::TextGrid => write_textgrid(transcript, out),
+        }
+    }
+
+    /// Usual file extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Srt => "srt",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Text => "txt",
+            ExportFormat::TextGrid => "TextGrid",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    /// `srt`, `jsonl`, `text` / `txt` or `textgrid`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "srt" => Ok(ExportFormat::Srt),
+            "jsonl" | "json-lines" => Ok(ExportFormat::JsonLines),
+            "text" | "txt" => Ok(ExportFormat::Text),
+            "textgrid" => Ok(ExportFormat::TextGrid),
+            _ => Err(format!("Unknown format {}, expected srt, jsonl, text or textgrid", name)),
+        }
+    }
+}
+
+/// Write the transcript as SubRip subtitles, one cue per utterance. With diarization
+/// enabled each cue starts with the speaker, "Speaker 1: ...".
+pub fn write_srt(transcript: &Transcript, mut out: impl Write) -> io::Result<()> {
+    let mut index = 0;
+    for utterance in &transcript.utterances {
+        let (Some(start), Some(end)) = (utterance.start(), utterance.end()) else { continue };
+        index += 1;
+        writeln!(out, "{}", index)?;
+        writeln!(out, "{} --> {}", clock(start, ','), clock(end, ','))?;
+        writeln!(out, "{}", labeled_text(utterance))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write one `utterance_json` object per line
+pub fn write_jsonl(transcript: &Transcript, mut out: impl Write) -> io::Result<()> {
+    for utterance in &transcript.utterances {
+        writeln!(out, "{}", utterance_json(utterance))?;
+    }
+    Ok(())
+}
+
+/// Write one `[HH:MM:SS.mmm - HH:MM:SS.mmm] text` line per utterance, with the speaker
+/// in front of the text when known
+pub fn write_text(transcript: &Transcript, mut out: impl Write) -> io::Result<()> {
+    for utterance in &transcript.utterances {
+        let start = utterance.start().unwrap_or_default();
+        let end = utterance.end().unwrap_or(start);
+        writeln!(out, "[{} - {}] {}", clock(start, '.'), clock(end, '.'), labeled_text(utterance))?;
+    }
+    Ok(())
+}
+
+/// Write a Praat TextGrid (long text format) with an "utterances" and a "words" interval
+/// tier, plus a "speakers" tier with diarization. Gaps become empty intervals, as Praat
+/// expects every tier to cover the whole file.
+pub fn write_textgrid(transcript: &Transcript, mut out: impl Write) -> io::Result<()> {
+    let end = transcript.utterances.iter().filter_map(Utterance::end).fold(transcript.duration, f32::max);
+
+    let utterances: Vec<(f32, f32, String)> = transcript
+        .utterances
+        .iter()
+        .filter_map(|u| Some((u.start()?, u.end()?, u.text.clone())))
+        .collect();
+    let words: Vec<(f32, f32, String)> =
+        transcript.utterances.iter().flat_map(|u| &u.words).map(|w| (w.start, w.end, w.word.clone())).collect();
+    let mut tiers = vec![("utterances", utterances), ("words", words)];
+    if transcript.utterances.iter().any(|u| u.speaker.is_some()) {
+        let speakers = transcript
+            .utterances
+            .iter()
+            .filter_map(|u| Some((u.start()?, u.end()?, u.speaker_label()?)))
+            .collect();
+        tiers.push(("speakers", speakers));
+    }
+
+    writeln!(out, "File type = \"ooTextFile\"")?;
+    writeln!(out, "Object class = \"TextGrid\"")?;
+    writeln!(out)?;
+    writeln!(out, "xmin = 0")?;
+    writeln!(out, "xmax = {}", end)?;
+    writeln!(out, "tiers? <exists>")?;
+    writeln!(out, "size = {}", tiers.len())?;
+    writeln!(out, "item []:")?;
+    for (number, (name, spans)) in tiers.into_iter().enumerate() {
+        let intervals = fill_gaps(spans, end);
+        writeln!(out, "    item [{}]:", number + 1)?;
+        writeln!(out, "        class = \"IntervalTier\"")?;
+        writeln!(out, "        name = \"{}\"", name)?;
+        writeln!(out, "        xmin = 0")?;
+        writeln!(out, "        xmax = {}", end)?;
+        writeln!(out, "        intervals: size = {}", intervals.len())?;
+        for (index, (start, end, text)) in intervals.iter().enumerate() {
+            writeln!(out, "        intervals [{}]:", index + 1)?;
+            writeln!(out, "            xmin = {}", start)?;
+            writeln!(out, "            xmax = {}", end)?;
+            writeln!(out, "            text = \"{}\"", text.replace('"', "\"\""))?;
+        }
+    }
+    Ok(())
+}
+
+/// Sorted, non-overlapping intervals from 0 to `end`, empty ones filling the gaps
+fn fill_gaps(spans: Vec<(f32, f32, String)>, end: f32) -> Vec<(f32, f32, String)> {
+    let mut intervals = Vec::with_capacity(spans.len() * 2 + 1);
+    let mut at = 0.0;
+    for (start, stop, text) in spans {
+        // overlaps are cut off, Praat rejects them
+        let start = start.max(at);
+        if stop <= start {
+            continue;
+        }
+        if start > at {
+            intervals.push((at, start, String::new()));
+        }
+        intervals.push((start, stop, text));
+        at = stop;
+    }
+    if end > at || intervals.is_empty() {
+        intervals.push((at, end.max(at), String::new()));
+    }
+    intervals
+}
+
+fn labeled_text(utterance: &Utterance) -> String {
+    match utterance.speaker_label() {
+        Some(speaker) => format!("{}: {}", speaker, utterance.text),
+        None => utterance.text.clone(),
+    }
+}
+
+/// `HH:MM:SS,mmm` for SubRip, `HH:MM:SS.mmm` otherwise
+fn clock(seconds: f32, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/// Utterance as JSON: text, start / end, speaker if known, timed words and the n-best list
+pub fn utterance_json(utterance: &Utterance) -> Value {
+    let mut value = json!({
+        "text": utterance.text,
+        "start": utterance.start().map(round),
+        "end": utterance.end().map(round),
+        "words": utterance
+            .words
+            .iter()
+            .map(|w| json!({ "word": w.word, "start": round(w.start), "end": round(w.end), "conf": round(w.conf) }))
+            .collect::<Vec<_>>(),
+        "alternatives": utterance
+            .alternatives
+            .iter()
+            .map(|a| json!({ "text": a.text, "confidence": round(a.confidence) }))
+            .collect::<Vec<_>>(),
+    });
+    if let Some(speaker) = utterance.speaker {
+        value["speaker"] = json!(speaker);
+    }
+    value
+}
+
+/// Whole transcript as one JSON object
+pub fn transcript_json(transcript: &Transcript) -> Value {
+    json!({
+        "duration": round(transcript.duration),
+        "text": transcript.text(),
+        "utterances": transcript.utterances.iter().map(utterance_json).collect::<Vec<_>>(),
+    })
+}
+
+/// Event as JSON tagged with its `type`: speech_started, speech_ended, partial or result
+pub fn event_json(event: &SpeechEvent) -> Value {
+    match event {
+        SpeechEvent::SpeechStarted { at } => json!({ "type": "speech_started", "at": round(*at) }),
+        SpeechEvent::SpeechEnded { at } => json!({ "type": "speech_ended", "at": round(*at) }),
+        SpeechEvent::Partial(text) => json!({ "type": "partial", "text": text }),
+        SpeechEvent::Result(utterance) => {
+            let mut value = utterance_json(utterance);
+            value["type"] = json!("result");
+            value
+        }
+    }
+}
+
+/// Millisecond precision, so f32 values don't print as 0.8999999761581421
+fn round(value: f32) -> f64 {
+    (value as f64 * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Word;
+
+    fn utterance(words: &[(&str, f32, f32)], speaker: Option<usize>) -> Utterance {
+        let words: Vec<Word> =
+            words.iter().map(|&(word, start, end)| Word { word: word.to_string(), start, end, conf: 0.9 }).collect();
+        let text = words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" ");
+        Utterance { text, words, speaker, ..Default::default() }
+    }
+
+    fn written(format: ExportFormat, transcript: &Transcript) -> String {
+        let mut out = Vec::new();
+        format.write(transcript, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn span(start: f32, end: f32, text: &str) -> (f32, f32, String) {
+        (start, end, text.to_string())
+    }
+
+    #[test]
+    fn clock_rounds_to_milliseconds() {
+        assert_eq!(clock(0.0, ','), "00:00:00,000");
+        assert_eq!(clock(1.2344, ','), "00:00:01,234");
+        assert_eq!(clock(1.2346, '.'), "00:00:01.235");
+        assert_eq!(clock(59.9996, ','), "00:01:00,000");
+        assert_eq!(clock(3723.5, ','), "01:02:03,500");
+        assert_eq!(clock(-0.5, ','), "00:00:00,000");
+    }
+
+    #[test]
+    fn srt() {
+        let transcript = Transcript {
+            utterances: vec![
+                utterance(&[("hello", 0.5, 0.9), ("world", 1.0, 1.25)], None),
+                // no words, no timing: skipped without using up a cue number
+                Utterance { text: "lost".to_string(), ..Default::default() },
+                utterance(&[("bye", 3661.0, 3661.75)], Some(1)),
+            ],
+            duration: 3662.0,
+        };
+        assert_eq!(
+            written(ExportFormat::Srt, &transcript),
+            "1\n00:00:00,500 --> 00:00:01,250\nhello world\n\n2\n01:01:01,000 --> 01:01:01,750\nSpeaker 2: bye\n\n"
+        );
+    }
+
+    #[test]
+    fn text() {
+        let transcript = Transcript { utterances: vec![utterance(&[("hello", 0.5, 0.9)], Some(0))], duration: 1.0 };
+        assert_eq!(written(ExportFormat::Text, &transcript), "[00:00:00.500 - 00:00:00.900] Speaker 1: hello\n");
+    }
+
+    #[test]
+    fn jsonl_escapes_text() {
+        let transcript = Transcript {
+            utterances: vec![utterance(&[("say", 0.0, 0.25), ("\"hi\"\\", 0.25, 0.5)], None), utterance(&[("zażółć", 1.0, 1.5)], Some(0))],
+            duration: 2.0,
+        };
+        let out = written(ExportFormat::JsonLines, &transcript);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"alternatives":[],"end":0.5,"start":0.0,"text":"say \"hi\"\\","words":[{"conf":0.9,"end":0.25,"start":0.0,"word":"say"},{"conf":0.9,"end":0.5,"start":0.25,"word":"\"hi\"\\"}]}"#
+        );
+        let second: Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(second["text"], "zażółć");
+        assert_eq!(second["speaker"], 0);
+    }
+
+    #[test]
+    fn gaps_are_filled_before_between_and_after() {
+        let intervals = fill_gaps(vec![span(0.5, 1.0, "a"), span(1.0, 1.5, "b"), span(2.0, 2.5, "c")], 3.0);
+        assert_eq!(intervals, [
+            span(0.0, 0.5, ""),
+            span(0.5, 1.0, "a"),
+            span(1.0, 1.5, "b"),
+            span(1.5, 2.0, ""),
+            span(2.0, 2.5, "c"),
+            span(2.5, 3.0, ""),
+        ]);
+    }
+
+    #[test]
+    fn gaps_without_spans_and_overlaps() {
+        assert_eq!(fill_gaps(Vec::new(), 2.0), [span(0.0, 2.0, "")]);
+        assert_eq!(fill_gaps(Vec::new(), 0.0), [span(0.0, 0.0, "")]);
+        // the overlapping start is cut off, a span swallowed whole is dropped
+        let intervals = fill_gaps(vec![span(0.0, 1.0, "a"), span(0.5, 1.5, "b"), span(1.2, 1.4, "c")], 1.5);
+        assert_eq!(intervals, [span(0.0, 1.0, "a"), span(1.0, 1.5, "b")]);
+    }
+
+    #[test]
+    fn textgrid() {
+        let transcript = Transcript { utterances: vec![utterance(&[("say", 0.5, 1.0), ("\"hi\"", 1.0, 1.5)], None)], duration: 2.0 };
+        let out = written(ExportFormat::TextGrid, &transcript);
+        let expected = r#"File type = "ooTextFile"
+Object class = "TextGrid"
+
+xmin = 0
+xmax = 2
+tiers? <exists>
+size = 2
+item []:
+    item [1]:
+        class = "IntervalTier"
+        name = "utterances"
+        xmin = 0
+        xmax = 2
+        intervals: size = 3
+        intervals [1]:
+            xmin = 0
+            xmax = 0.5
+            text = ""
+        intervals [2]:
+            xmin = 0.5
+            xmax = 1.5
+            text = "say ""hi"""
+        intervals [3]:
+            xmin = 1.5
+            xmax = 2
+            text = ""
+    item [2]:
+        class = "IntervalTier"
+        name = "words"
+        xmin = 0
+        xmax = 2
+        intervals: size = 4
+        intervals [1]:
+            xmin = 0
+            xmax = 0.5
+            text = ""
+        intervals [2]:
+            xmin = 0.5
+            xmax = 1
+            text = "say"
+        intervals [3]:
+            xmin = 1
+            xmax = 1.5
+            text = """hi"""
+        intervals [4]:
+            xmin = 1.5
+            xmax = 2
+            text = ""
+"#;
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use super::{db_to_gain, smoothing, AgcConfig};
+
+/// Gain follower bringing the RMS level to the target
+pub(super) struct Agc {
+    target: f32,
+    max_gain: f32,
+    noise_floor: f32,
+    attack: f32,
+    release: f32,
+    // smoothing of the squared signal
+    level_smoothing: f32,
+    power: f32,
+    gain: f32,
+}
+
+impl Agc {
+    pub fn new(config: AgcConfig, sample_rate: f32) -> Self {
+        Self {
+            target: db_to_gain(config.target_db),
+            max_gain: db_to_gain(config.max_gain_db),
+            noise_floor: db_to_gain(config.noise_floor_db),
+            attack: smoothing(config.attack, sample_rate),
+            release: smoothing(config.release, sample_rate),
+            // about a syllable, short enough to catch onsets
+            level_smoothing: smoothing(Duration::from_millis(50), sample_rate),
+            power: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            self.power += self.level_smoothing * (*sample * *sample - self.power);
+            let level = self.power.sqrt();
+            // below the floor the gain is held, pauses are not turned up
+            if level > self.noise_floor {
+                let wanted = (self.target / level).min(self.max_gain);
+                let speed = if wanted < self.gain { self.attack } else { self.release };
+                self.gain += speed * (wanted - self.gain);
+            }
+            *sample *= self.gain;
+        }
+    }
+}
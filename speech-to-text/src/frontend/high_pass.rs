@@ -0,0 +1,37 @@
+use std::f64::consts::PI;
+
+/// Second order Butterworth high-pass (RBJ cookbook biquad)
+pub(super) struct HighPass {
+    b: [f64; 3],
+    a: [f64; 2],
+    // last two inputs and outputs
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl HighPass {
+    pub fn new(cutoff: f32, sample_rate: f32) -> Self {
+        // keep the cutoff below Nyquist, where the formulas break down
+        let cutoff = (cutoff as f64).clamp(1.0, sample_rate as f64 * 0.45);
+        let w0 = 2.0 * PI * cutoff / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * std::f64::consts::FRAC_1_SQRT_2);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b: [(1.0 + cos) / 2.0 / a0, -(1.0 + cos) / a0, (1.0 + cos) / 2.0 / a0],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            let x = *sample as f64;
+            let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+            self.x = [x, self.x[0]];
+            self.y = [y, self.y[0]];
+            *sample = y as f32;
+        }
+    }
+}
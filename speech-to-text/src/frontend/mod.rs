@@ -0,0 +1,308 @@
+use std::time::Duration;
+
+mod agc;
+mod fft;
+mod high_pass;
+mod noise_gate;
+
+use agc::Agc;
+use high_pass::HighPass;
+use noise_gate::NoiseGate;
+
+/// Spectral noise gate settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseGateConfig {
+    /// Audio at the start of the stream taken as noise to build the profile from, unless
+    /// `FrontEnd::learn_noise` was given a recording of the room
+    pub learn: Duration,
+    /// Frequency bins this much louder than the noise profile (dB) pass unchanged
+    pub threshold_db: f32,
+    /// How much quieter the gated bins get (dB)
+    pub reduction_db: f32,
+    /// Keep updating the profile from frames that are mostly noise, for noise that
+    /// changes over time or speech that started before learning was done
+    pub adapt: bool,
+}
+
+impl Default for NoiseGateConfig {
+    fn default() -> Self {
+        Self { learn: Duration::from_millis(500), threshold_db: 6.0, reduction_db: 18.0, adapt: true }
+    }
+}
+
+/// Automatic gain control settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgcConfig {
+    /// Level the signal is brought to (dBFS RMS)
+    pub target_db: f32,
+    /// Most the signal is amplified (dB)
+    pub max_gain_db: f32,
+    /// Input below this level (dBFS) keeps the current gain, so pauses don't pump up the noise
+    pub noise_floor_db: f32,
+    /// How fast the gain comes down when the input gets louder
+    pub attack: Duration,
+    /// How fast the gain goes up when the input gets quieter
+    pub release: Duration,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        Self {
+            target_db: -20.0,
+            max_gain_db: 30.0,
+            noise_floor_db: -55.0,
+            attack: Duration::from_millis(10),
+            release: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Stages of the `FrontEnd`, each one off with `None`. The default enables all three.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontEndConfig {
+    /// Cutoff (Hz) of a high-pass filter removing rumble, hum and DC offset
+    pub high_pass: Option<f32>,
+    pub noise_gate: Option<NoiseGateConfig>,
+    pub agc: Option<AgcConfig>,
+}
+
+impl Default for FrontEndConfig {
+    fn default() -> Self {
+        Self { high_pass: Some(80.0), noise_gate: Some(NoiseGateConfig::default()), agc: Some(AgcConfig::default()) }
+    }
+}
+
+/// Clean-up of the audio before it reaches the recognizer: high-pass filter, spectral noise
+/// gate and automatic gain control, in that order.
+///
+/// The noise gate works on overlapping blocks and holds back `latency()` samples, so
+/// `process` returns fewer samples than it was given at first. The output stays aligned
+/// with the input, sample n out is sample n in, and `flush` returns what is held back.
+pub struct FrontEnd {
+    config: FrontEndConfig,
+    sample_rate: f32,
+    high_pass: Option<HighPass>,
+    noise_gate: Option<NoiseGate>,
+    agc: Option<Agc>,
+}
+
+impl FrontEnd {
+    pub fn new(config: FrontEndConfig, sample_rate: f32) -> Self {
+        Self {
+            high_pass: config.high_pass.map(|cutoff| HighPass::new(cutoff, sample_rate)),
+            noise_gate: config.noise_gate.clone().map(|gate| NoiseGate::new(gate, sample_rate)),
+            agc: config.agc.clone().map(|agc| Agc::new(agc, sample_rate)),
+            config,
+            sample_rate,
+        }
+    }
+
+    pub fn config(&self) -> &FrontEndConfig {
+        &self.config
+    }
+
+    /// Samples held back by the noise gate, 0 without it
+    pub fn latency(&self) -> usize {
+        self.noise_gate.as_ref().map_or(0, NoiseGate::latency)
+    }
+
+    /// Build the noise profile from a recording of the room without speech, instead of
+    /// taking the start of the stream. Does nothing without the noise gate.
+    pub fn learn_noise(&mut self, noise: &[i16]) {
+        let Some(gate) = self.noise_gate.as_mut() else { return };
+        let mut samples = to_float(noise);
+        // the profile has to match what the gate gets to see, through a filter of its own
+        // so the stream's filter state is not disturbed
+        if let Some(cutoff) = self.config.high_pass {
+            HighPass::new(cutoff, self.sample_rate).process(&mut samples);
+        }
+        gate.learn(&samples);
+    }
+
+    pub fn process(&mut self, samples: &[i16]) -> Vec<i16> {
+        let mut samples = to_float(samples);
+        if let Some(high_pass) = self.high_pass.as_mut() {
+            high_pass.process(&mut samples);
+        }
+        if let Some(gate) = self.noise_gate.as_mut() {
+            samples = gate.process(&samples);
+        }
+        self.finish_chain(samples)
+    }
+
+    /// Samples held back by the noise gate, e.g. at the end of a stream. Processing can
+    /// go on afterwards, the noise profile is kept.
+    pub fn flush(&mut self) -> Vec<i16> {
+        let samples = match self.noise_gate.as_mut() {
+            Some(gate) => gate.flush(),
+            None => return Vec::new(),
+        };
+        self.finish_chain(samples)
+    }
+
+    fn finish_chain(&mut self, mut samples: Vec<f32>) -> Vec<i16> {
+        if let Some(agc) = self.agc.as_mut() {
+            agc.process(&mut samples);
+        }
+        samples.iter().map(|&s| (s * 32768.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16).collect()
+    }
+}
+
+fn to_float(samples: &[i16]) -> Vec<f32> {
+    samples.iter().map(|&s| s as f32 / 32768.0).collect()
+}
+
+/// dB to a linear amplitude factor
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Coefficient of a one-pole smoother reaching ~63% of a step after `time`
+fn smoothing(time: Duration, rate: f32) -> f32 {
+    let samples = time.as_secs_f32() * rate;
+    if samples <= 0.0 {
+        return 1.0;
+    }
+    1.0 - (-1.0 / samples).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 16000.0;
+
+    fn tone(frequency: f32, amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len).map(|i| amplitude * (2.0 * PI * frequency * i as f32 / SAMPLE_RATE).sin()).collect()
+    }
+
+    /// Uniform noise in ±`amplitude`, from a fixed seed
+    fn noise(amplitude: f32, len: usize) -> Vec<f32> {
+        let mut state = 0x2545_f491_u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                amplitude * ((state >> 8) as f32 / (1 << 23) as f32 - 1.0)
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// Amplitude of the `frequency` component, over whole periods
+    fn amplitude_at(samples: &[f32], frequency: f32) -> f32 {
+        let (mut sin, mut cos) = (0.0, 0.0);
+        for (i, sample) in samples.iter().enumerate() {
+            let (s, c) = (2.0 * PI * frequency * i as f32 / SAMPLE_RATE).sin_cos();
+            sin += sample * s;
+            cos += sample * c;
+        }
+        2.0 * sin.hypot(cos) / samples.len() as f32
+    }
+
+    /// Everything through the gate in uneven chunks, then flushed
+    fn gate_all(gate: &mut NoiseGate, samples: &[f32]) -> Vec<f32> {
+        let mut out = Vec::new();
+        let mut rest = samples;
+        for size in [1, 100, 333, 256, 7].into_iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at(size.min(rest.len()));
+            out.extend(gate.process(chunk));
+            rest = tail;
+        }
+        out.extend(gate.flush());
+        out
+    }
+
+    #[test]
+    fn high_pass_removes_dc() {
+        let mut filter = HighPass::new(80.0, SAMPLE_RATE);
+        let mut samples: Vec<f32> = tone(1000.0, 0.25, 16000).iter().map(|s| s + 0.5).collect();
+        filter.process(&mut samples);
+        let settled = &samples[8000..];
+        let mean = settled.iter().sum::<f32>() / settled.len() as f32;
+        assert!(mean.abs() < 1e-3, "DC left: {}", mean);
+        // far above the cutoff the tone passes
+        assert!((amplitude_at(settled, 1000.0) - 0.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn noise_gate_output_is_aligned_with_the_input() {
+        // while learning the gate lets everything through, so out has to equal in
+        let config = NoiseGateConfig { learn: Duration::from_secs(10), ..Default::default() };
+        let mut gate = NoiseGate::new(config, SAMPLE_RATE);
+        let input: Vec<f32> = tone(440.0, 0.3, 5000).iter().zip(noise(0.05, 5000)).map(|(t, n)| t + n).collect();
+        let out = gate_all(&mut gate, &input);
+        assert_eq!(out.len(), input.len());
+        for (i, (out, input)) in out.iter().zip(&input).enumerate() {
+            assert!((out - input).abs() < 1e-4, "sample {}: {} instead of {}", i, out, input);
+        }
+
+        // after a flush the gate starts over and stays aligned
+        let out = gate_all(&mut gate, &input[..1234]);
+        assert_eq!(out.len(), 1234);
+        assert!((out[1000] - input[1000]).abs() < 1e-4);
+        assert!(gate.flush().is_empty());
+    }
+
+    #[test]
+    fn noise_gate_turns_down_noise_and_passes_a_tone() {
+        let config = NoiseGateConfig::default();
+        let len = SAMPLE_RATE as usize * 2;
+        let steady = noise(0.02, len);
+        let out = gate_all(&mut NoiseGate::new(config.clone(), SAMPLE_RATE), &steady);
+        // learned during the first 0.5 s, well gated in the last one
+        let reduction = rms(&out[len / 2..]) / rms(&steady[len / 2..]);
+        assert!(reduction < db_to_gain(-12.0), "noise only down to {}", reduction);
+
+        // the tone starts after learning
+        let mut with_tone = steady.clone();
+        for (sample, tone) in with_tone[len / 2..].iter_mut().zip(tone(1000.0, 0.2, len / 2)) {
+            *sample += tone;
+        }
+        let out = gate_all(&mut NoiseGate::new(config, SAMPLE_RATE), &with_tone);
+        let amplitude = amplitude_at(&out[len / 2 + 4000..len / 2 + 8000], 1000.0);
+        assert!((amplitude - 0.2).abs() < 0.02, "tone at {}", amplitude);
+    }
+
+    #[test]
+    fn agc_brings_the_level_to_the_target() {
+        let config = AgcConfig::default();
+        for amplitude in [db_to_gain(-40.0), db_to_gain(-6.0)] {
+            let mut samples = tone(500.0, amplitude, 32000);
+            Agc::new(config.clone(), SAMPLE_RATE).process(&mut samples);
+            let level = rms(&samples[24000..]) * 2f32.sqrt();
+            let target = db_to_gain(config.target_db) * 2f32.sqrt();
+            assert!((level / target - 1.0).abs() < 0.1, "{} brought to {}", amplitude, level);
+        }
+    }
+
+    #[test]
+    fn agc_gain_stays_within_its_limits() {
+        let config = AgcConfig::default();
+        let max_gain = db_to_gain(config.max_gain_db);
+
+        // -52 dBFS RMS, above the noise floor but 32 dB below the target: amplified by
+        // no more than the maximum
+        let input = tone(500.0, db_to_gain(-49.0), 48000);
+        let mut samples = input.clone();
+        Agc::new(config.clone(), SAMPLE_RATE).process(&mut samples);
+        for (out, input) in samples.iter().zip(&input) {
+            assert!(out.abs() <= input.abs() * max_gain * 1.001);
+        }
+        let gain = amplitude_at(&samples[40000..], 500.0) / amplitude_at(&input[40000..], 500.0);
+        assert!((gain / max_gain - 1.0).abs() < 0.01, "gain {}", gain);
+
+        // below the noise floor the gain is held, silence is not turned up
+        let input = noise(db_to_gain(-70.0), 16000);
+        let mut samples = input.clone();
+        Agc::new(config, SAMPLE_RATE).process(&mut samples);
+        assert_eq!(samples, input);
+    }
+}
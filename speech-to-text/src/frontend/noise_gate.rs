@@ -0,0 +1,196 @@
+use std::f32::consts::PI;
+
+use super::fft::fft;
+use super::{db_to_gain, NoiseGateConfig};
+
+/// Spectral gate: frequency bins not clearly above the learned noise profile are turned
+/// down. Works on half-overlapping blocks of ~32 ms with a square-root Hann window on both
+/// analysis and synthesis, which adds back up to the input where nothing is gated.
+pub(super) struct NoiseGate {
+    config: NoiseGateConfig,
+    hop: usize,
+    window: Vec<f32>,
+    // the current block: the second half of the previous one followed by new samples
+    input: Vec<f32>,
+    // new samples in `input`
+    filled: usize,
+    // overlap-add of the processed blocks
+    output: Vec<f32>,
+    // the first hop out is the silence the buffers start with
+    primed: bool,
+    // mean magnitude of the noise per bin
+    profile: Vec<f32>,
+    // blocks still to be taken as noise
+    learn_blocks: usize,
+    learned_blocks: usize,
+    threshold: f32,
+    floor: f32,
+    // per block decay of the gains, the gate closes over ~100 ms instead of chopping
+    decay: f32,
+    gains: Vec<f32>,
+    re: Vec<f32>,
+    im: Vec<f32>,
+}
+
+impl NoiseGate {
+    pub fn new(config: NoiseGateConfig, sample_rate: f32) -> Self {
+        let block = ((sample_rate * 0.032) as usize).next_power_of_two().max(16);
+        let hop = block / 2;
+        let bins = block / 2 + 1;
+        let floor = db_to_gain(-config.reduction_db.abs());
+        let blocks_per_second = sample_rate / hop as f32;
+        Self {
+            hop,
+            window: (0..block).map(|i| (0.5 - 0.5 * (2.0 * PI * i as f32 / block as f32).cos()).sqrt()).collect(),
+            input: vec![0.0; block],
+            filled: 0,
+            output: vec![0.0; block],
+            primed: false,
+            profile: vec![0.0; bins],
+            learn_blocks: ((config.learn.as_secs_f32() * blocks_per_second).ceil() as usize).max(1),
+            learned_blocks: 0,
+            threshold: db_to_gain(config.threshold_db),
+            floor,
+            decay: floor.powf(1.0 / (0.1 * blocks_per_second).max(1.0)),
+            gains: vec![1.0; bins],
+            re: vec![0.0; block],
+            im: vec![0.0; block],
+            config,
+        }
+    }
+
+    pub fn latency(&self) -> usize {
+        self.hop
+    }
+
+    /// Replace the profile with the mean spectrum of `noise`
+    pub fn learn(&mut self, noise: &[f32]) {
+        let block = self.input.len();
+        let mut sum = vec![0.0; self.profile.len()];
+        let mut blocks = 0;
+        for start in (0..noise.len().saturating_sub(block - 1)).step_by(self.hop) {
+            self.spectrum(&noise[start..start + block]);
+            for (bin, total) in sum.iter_mut().enumerate() {
+                *total += self.re[bin].hypot(self.im[bin]);
+            }
+            blocks += 1;
+        }
+        if blocks == 0 {
+            return;
+        }
+        self.profile = sum.into_iter().map(|total| total / blocks as f32).collect();
+        self.learned_blocks = blocks;
+        self.learn_blocks = 0;
+    }
+
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.feed(samples, true)
+    }
+
+    /// Push out the held back samples by padding with silence, then start over with empty
+    /// buffers. The padding is not learned from.
+    pub fn flush(&mut self) -> Vec<f32> {
+        let held = self.filled + if self.primed { self.hop } else { 0 };
+        if held == 0 {
+            return Vec::new();
+        }
+        let padding = vec![0.0; self.hop - self.filled + self.hop];
+        let mut out = self.feed(&padding, false);
+        out.truncate(held);
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.filled = 0;
+        self.primed = false;
+        out
+    }
+
+    fn feed(&mut self, mut samples: &[f32], learn: bool) -> Vec<f32> {
+        let mut out = Vec::with_capacity(samples.len() + self.hop);
+        while !samples.is_empty() {
+            let take = (self.hop - self.filled).min(samples.len());
+            let at = self.hop + self.filled;
+            self.input[at..at + take].copy_from_slice(&samples[..take]);
+            self.filled += take;
+            samples = &samples[take..];
+            if self.filled == self.hop {
+                self.process_block(learn);
+                if self.primed {
+                    out.extend_from_slice(&self.output[..self.hop]);
+                }
+                self.primed = true;
+                self.output.copy_within(self.hop.., 0);
+                let hop = self.hop;
+                self.output[hop..].fill(0.0);
+                self.input.copy_within(hop.., 0);
+                self.filled = 0;
+            }
+        }
+        out
+    }
+
+    /// Windowed FFT of `block` into `re` / `im`
+    fn spectrum(&mut self, block: &[f32]) {
+        for ((re, im), (&sample, &window)) in self.re.iter_mut().zip(&mut self.im).zip(block.iter().zip(&self.window)) {
+            *re = sample * window;
+            *im = 0.0;
+        }
+        fft(&mut self.re, &mut self.im, false);
+    }
+
+    fn process_block(&mut self, learn: bool) {
+        let block = std::mem::take(&mut self.input);
+        self.spectrum(&block);
+        self.input = block;
+
+        let len = self.re.len();
+        let magnitudes: Vec<f32> = (0..self.profile.len()).map(|bin| self.re[bin].hypot(self.im[bin])).collect();
+
+        if self.learn_blocks > 0 {
+            // still learning, the audio passes as is
+            if learn {
+                let n = self.learned_blocks as f32;
+                for (profile, magnitude) in self.profile.iter_mut().zip(&magnitudes) {
+                    *profile = (*profile * n + magnitude) / (n + 1.0);
+                }
+                self.learned_blocks += 1;
+                self.learn_blocks -= 1;
+            }
+        } else {
+            // a bin is judged with its neighbours, single bins of noise poking out of
+            // the profile would otherwise keep the gate fluttering
+            let bins = magnitudes.len();
+            let mut open = vec![false; bins];
+            for (bin, (open, gain)) in open.iter_mut().zip(&mut self.gains).enumerate() {
+                let around = bin.saturating_sub(1)..(bin + 2).min(bins);
+                let level = magnitudes[around.clone()].iter().sum::<f32>();
+                let noise = self.profile[around].iter().sum::<f32>();
+                *open = level > noise * self.threshold;
+                let wanted = if *open { 1.0 } else { self.floor };
+                *gain = (*gain * self.decay).max(wanted);
+            }
+            // a block with barely any bin above the noise is noise itself, the profile
+            // follows it where the gate stayed closed
+            if learn && self.config.adapt && open.iter().filter(|&&open| open).count() * 10 < bins {
+                for ((profile, &magnitude), open) in self.profile.iter_mut().zip(&magnitudes).zip(&open) {
+                    if !open {
+                        *profile = 0.95 * *profile + 0.05 * magnitude;
+                    }
+                }
+            }
+            for (bin, &gain) in self.gains.iter().enumerate() {
+                self.re[bin] *= gain;
+                self.im[bin] *= gain;
+                // the mirrored half of a real signal's spectrum
+                if bin > 0 && bin < len / 2 {
+                    self.re[len - bin] *= gain;
+                    self.im[len - bin] *= gain;
+                }
+            }
+        }
+
+        fft(&mut self.re, &mut self.im, true);
+        for ((output, re), window) in self.output.iter_mut().zip(&self.re).zip(&self.window) {
+            *output += re * window;
+        }
+    }
+}
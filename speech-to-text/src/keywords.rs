@@ -0,0 +1,174 @@
+use crate::Utterance;
+
+/// Restricts what the recognizer can output
+#[derive(Debug, Clone, PartialEq)]
+pub enum Grammar {
+    /// Phrases the recognizer may return, e.g. `["turn on", "turn off", "[unk]"]`
+    Phrases(Vec<String>),
+    /// The same list in vosk's JSON form, e.g. `["turn on", "turn off", "[unk]"]`
+    Json(String),
+}
+
+impl Grammar {
+    pub(crate) fn phrases(&self) -> Result<Vec<String>, serde_json::Error> {
+        match self {
+            Grammar::Phrases(phrases) => Ok(phrases.clone()),
+            Grammar::Json(json) => serde_json::from_str(json),
+        }
+    }
+}
+
+/// Grammar of a keyword spotter: the phrases, and `[unk]` for everything else
+pub(crate) fn keyword_grammar(phrases: &[impl AsRef<str>]) -> Grammar {
+    let mut grammar: Vec<String> =
+        phrases.iter().map(|phrase| phrase.as_ref().trim()).filter(|phrase| !phrase.is_empty()).map(str::to_string).collect();
+    if !grammar.iter().any(|phrase| phrase == "[unk]") {
+        grammar.push("[unk]".to_string());
+    }
+    Grammar::Phrases(grammar)
+}
+
+/// Configured phrase found in a final result
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeywordHit {
+    pub phrase: String,
+    /// Start of the first word of the phrase on the stream timeline (seconds)
+    pub start: f32,
+    /// End of the last word of the phrase (seconds)
+    pub end: f32,
+    /// Lowest word confidence within the phrase
+    pub confidence: f32,
+}
+
+pub(crate) struct KeywordSpotter {
+    // phrase as configured and split into lowercase words
+    phrases: Vec<(String, Vec<String>)>,
+    threshold: f32,
+    callback: Box<dyn FnMut(&KeywordHit) + Send>,
+}
+
+impl KeywordSpotter {
+    pub(crate) fn new(
+        phrases: &[impl AsRef<str>],
+        threshold: f32,
+        callback: impl FnMut(&KeywordHit) + Send + 'static,
+    ) -> Self {
+        let phrases = phrases
+            .iter()
+            .map(|phrase| phrase.as_ref())
+            .filter(|phrase| !phrase.trim().is_empty())
+            .map(|phrase| (phrase.to_string(), phrase.split_whitespace().map(str::to_lowercase).collect()))
+            .collect();
+        Self { phrases, threshold, callback: Box::new(callback) }
+    }
+
+    /// Call the callback for every configured phrase in the utterance
+    pub(crate) fn spot(&mut self, utterance: &Utterance) {
+        let words = &utterance.words;
+        for (phrase, phrase_words) in &self.phrases {
+            if phrase_words.len() > words.len() {
+                continue;
+            }
+            for start in 0..=words.len() - phrase_words.len() {
+                let candidate = &words[start..start + phrase_words.len()];
+                let matches = candidate
+                    .iter()
+                    .zip(phrase_words)
+                    .all(|(word, expected)| word.word.to_lowercase() == *expected);
+                if !matches {
+                    continue;
+                }
+                let confidence = candidate.iter().map(|w| w.conf).fold(f32::INFINITY, f32::min);
+                if confidence >= self.threshold {
+                    (self.callback)(&KeywordHit {
+                        phrase: phrase.clone(),
+                        start: candidate[0].start,
+                        end: candidate[candidate.len() - 1].end,
+                        confidence,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::Word;
+
+    fn utterance(words: &[(&str, f32)]) -> Utterance {
+        let words: Vec<Word> = words
+            .iter()
+            .enumerate()
+            .map(|(i, &(word, conf))| Word { word: word.to_string(), start: i as f32, end: i as f32 + 0.5, conf })
+            .collect();
+        let text = words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" ");
+        Utterance { text, words, ..Default::default() }
+    }
+
+    /// Spotter collecting its hits
+    fn spotter(phrases: &[&str], threshold: f32) -> (KeywordSpotter, Arc<Mutex<Vec<KeywordHit>>>) {
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&hits);
+        let spotter = KeywordSpotter::new(phrases, threshold, move |hit| sink.lock().unwrap().push(hit.clone()));
+        (spotter, hits)
+    }
+
+    fn phrases(hits: &Mutex<Vec<KeywordHit>>) -> Vec<String> {
+        hits.lock().unwrap().iter().map(|hit| hit.phrase.clone()).collect()
+    }
+
+    #[test]
+    fn keyword_grammar_ends_with_unk() {
+        let grammar = keyword_grammar(&["turn on", " turn off ", "", "   "]);
+        assert_eq!(grammar, Grammar::Phrases(vec!["turn on".into(), "turn off".into(), "[unk]".into()]));
+        // given already, it is not repeated
+        let grammar = keyword_grammar(&["[unk]", "stop"]);
+        assert_eq!(grammar.phrases().unwrap(), ["[unk]", "stop"]);
+    }
+
+    #[test]
+    fn json_grammar() {
+        let grammar = Grammar::Json(r#"["turn on", "don't stop", "say \"cheese\"", "zażółć", "[unk]"]"#.to_string());
+        assert_eq!(grammar.phrases().unwrap(), ["turn on", "don't stop", "say \"cheese\"", "zażółć", "[unk]"]);
+
+        // phrases written as JSON come back the same
+        let Grammar::Phrases(phrases) = keyword_grammar(&["say \"cheese\"", "back\\slash"]) else { unreachable!() };
+        let json = serde_json::to_string(&phrases).unwrap();
+        assert_eq!(json, r#"["say \"cheese\"","back\\slash","[unk]"]"#);
+        assert_eq!(Grammar::Json(json).phrases().unwrap(), phrases);
+
+        assert!(Grammar::Json("[\"unterminated]".to_string()).phrases().is_err());
+        assert!(Grammar::Json(r#"{"phrases": ["a"]}"#.to_string()).phrases().is_err());
+    }
+
+    #[test]
+    fn phrases_are_matched_in_the_words() {
+        let (mut spotter, hits) = spotter(&["Lights On", "on", "lights on now please", ""], 0.0);
+        spotter.spot(&utterance(&[("turn", 1.0), ("lights", 0.9), ("on", 0.8), ("and", 1.0), ("on", 1.0)]));
+        // case insensitive, every occurrence, none longer than the utterance
+        assert_eq!(phrases(&hits), ["Lights On", "on", "on"]);
+        let hit = hits.lock().unwrap()[0].clone();
+        assert_eq!((hit.start, hit.end), (1.0, 2.5));
+        assert_eq!(hit.confidence, 0.8);
+
+        hits.lock().unwrap().clear();
+        spotter.spot(&utterance(&[("lights", 1.0), ("off", 1.0)]));
+        spotter.spot(&utterance(&[]));
+        assert!(phrases(&hits).is_empty());
+    }
+
+    #[test]
+    fn hits_below_the_threshold_are_dropped() {
+        let (mut spotter, hits) = spotter(&["open door", "stop"], 0.7);
+        spotter.spot(&utterance(&[("open", 0.95), ("door", 0.6), ("stop", 0.7)]));
+        // the weakest word counts, reaching the threshold is enough
+        assert_eq!(phrases(&hits), ["stop"]);
+        spotter.spot(&utterance(&[("open", 0.71), ("door", 0.9)]));
+        assert_eq!(phrases(&hits), ["stop", "open door"]);
+        assert_eq!(hits.lock().unwrap()[1].confidence, 0.71);
+    }
+}
@@ -1,27 +1,276 @@
-use std::io::Write;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use vosk::{AcceptWaveformError, CompleteResult, DecodingState, Model, Recognizer};
+use backend::Backend;
+use keywords::{keyword_grammar, KeywordSpotter};
+use vosk::{Recognizer, SpeakerModel};
+
+mod audio_file;
+mod backend;
+mod diarization;
+mod error;
+mod export;
+mod frontend;
+mod keywords;
+mod metrics;
+mod models;
+mod postprocess;
+mod result;
+#[cfg(feature = "service")]
+mod service;
+mod vad;
+mod worker;
+pub mod wer;
+
+pub use audio_file::AudioFile;
+pub use diarization::{DiarizationConfig, SpeakerClustering, XVector};
+pub use error::Error;
+pub use export::{
+    event_json, transcript_json, utterance_json, write_jsonl, write_srt, write_text, write_textgrid, ExportFormat,
+};
+pub use frontend::{AgcConfig, FrontEnd, FrontEndConfig, NoiseGateConfig};
+pub use keywords::{Grammar, KeywordHit};
+pub use metrics::{Metrics, MetricsReport, Percentiles};
+pub use models::ModelRegistry;
+pub use postprocess::{Capitalization, InverseTextNormalization, Language, PausePunctuation, Pipeline, PostProcessor};
+pub use result::{Alternative, SpeechEvent, Transcript, Utterance, Word};
+#[cfg(feature = "service")]
+pub use service::{ServiceConfig, TranscriptionService};
+pub use vad::{Vad, VadConfig, VadTransition};
+pub use worker::{AudioSender, SpeechWorker, WorkerStats};
+pub use vosk::{DecodingState, Model};
 
 pub struct SpeechToText {
-    recognizer: Recognizer,
+    // the recognizer holds on to the model, kept here to build new recognizers from.
+    // Only the test backend runs without one.
+    model: Option<Arc<Model>>,
+    // settings to carry over when the recognizer is replaced by `set_model`
+    grammar: Option<Vec<String>>,
+    max_alternatives: u16,
+    speaker_model: Option<Arc<SpeakerModel>>,
+    recognizer: Box<dyn Backend>,
+    sample_rate: f32,
+    front_end: Option<FrontEnd>,
     last_decoding_state: DecodingState,
     partial_text: String,
-    // Optionally store model if needed for lifetime
+    vad: Option<Vad>,
+    // samples not filling a whole VAD frame yet
+    vad_pending: Vec<i16>,
+    // recent silence, fed to the recognizer once speech starts
+    pre_roll: VecDeque<i16>,
+    // samples pushed by the caller / samples passed to the recognizer
+    samples_seen: u64,
+    samples_fed: u64,
+    // shift from recognizer time to stream time, grows as silence is skipped
+    time_offset: f32,
+    keywords: Option<KeywordSpotter>,
+    metrics: Option<Metrics>,
+    post_processing: Option<Pipeline>,
+    speakers: Option<SpeakerClustering>,
 }
 
 impl SpeechToText {
     /// Create a new SpeechToText recognizer
-    pub fn new(model_path: &str, sample_rate: f32) -> Result<Self,std::io::Error> {
-        let model = Model::new(model_path).ok_or(std::io::Error::new(std::io::ErrorKind::Other, "Model not found"))?;
-        let mut recognizer = Recognizer::new(&model, sample_rate).ok_or(std::io::Error::new(std::io::ErrorKind::Other, "Recognizer not found"))?;
-        recognizer.set_max_alternatives(0);
-        recognizer.set_words(true);
-        recognizer.set_partial_words(true);
-        Ok(Self { recognizer, last_decoding_state: DecodingState::Running, partial_text: String::new() })
+    pub fn new(model_path: &str, sample_rate: f32) -> Result<Self, Error> {
+        Self::from_model(Arc::new(load_model(model_path)?), sample_rate)
+    }
+
+    /// Create a recognizer on a model that is already loaded. Loading takes seconds and
+    /// a lot of memory, so recognizers running side by side should share one `Model`.
+    pub fn from_model(model: Arc<Model>, sample_rate: f32) -> Result<Self, Error> {
+        Self::build(model, sample_rate, None)
+    }
+
+    /// Create a recognizer that only outputs phrases from `grammar`.
+    ///
+    /// Add `"[unk]"` to the phrases to let anything else through as `[unk]` instead of
+    /// forcing it onto the closest phrase. Only models with a lookahead graph support this.
+    pub fn new_with_grammar(model_path: &str, sample_rate: f32, grammar: &Grammar) -> Result<Self, Error> {
+        let phrases = grammar.phrases().map_err(|e| Error::RecognizerCreation(format!("invalid grammar: {}", e)))?;
+        Self::build(Arc::new(load_model(model_path)?), sample_rate, Some(phrases))
+    }
+
+    /// Create a recognizer for voice commands: only `phrases` are recognized, anything
+    /// else becomes `[unk]`, and `callback` fires for each phrase recognized with a
+    /// confidence of at least `threshold`.
+    pub fn new_keyword_spotter(
+        model_path: &str,
+        sample_rate: f32,
+        phrases: &[impl AsRef<str>],
+        threshold: f32,
+        callback: impl FnMut(&KeywordHit) + Send + 'static,
+    ) -> Result<Self, Error> {
+        let mut stt = Self::new_with_grammar(model_path, sample_rate, &keyword_grammar(phrases))?;
+        stt.spot_keywords(phrases, threshold, callback);
+        Ok(stt)
+    }
+
+    fn build(model: Arc<Model>, sample_rate: f32, grammar: Option<Vec<String>>) -> Result<Self, Error> {
+        let recognizer = create_recognizer(&model, sample_rate, grammar.as_deref())?;
+        Ok(Self::with_backend(Some(model), Box::new(recognizer), sample_rate, grammar))
+    }
+
+    fn with_backend(
+        model: Option<Arc<Model>>,
+        recognizer: Box<dyn Backend>,
+        sample_rate: f32,
+        grammar: Option<Vec<String>>,
+    ) -> Self {
+        Self {
+            model,
+            grammar,
+            max_alternatives: 0,
+            speaker_model: None,
+            recognizer,
+            sample_rate,
+            front_end: None,
+            last_decoding_state: DecodingState::Running,
+            partial_text: String::new(),
+            vad: None,
+            vad_pending: Vec::new(),
+            pre_roll: VecDeque::new(),
+            samples_seen: 0,
+            samples_fed: 0,
+            time_offset: 0.0,
+            keywords: None,
+            metrics: None,
+            post_processing: None,
+            speakers: None,
+        }
+    }
+
+    /// Call `callback` whenever a final result contains one of `phrases` with every word
+    /// recognized with a confidence of at least `threshold`. Works with any recognizer,
+    /// though a grammar restricted one is both faster and more accurate.
+    pub fn spot_keywords(
+        &mut self,
+        phrases: &[impl AsRef<str>],
+        threshold: f32,
+        callback: impl FnMut(&KeywordHit) + Send + 'static,
+    ) {
+        self.keywords = Some(KeywordSpotter::new(phrases, threshold, callback));
+    }
+
+    /// Load a Vosk speaker model (e.g. vosk-model-spk-0.4), after which every final result
+    /// carries an `XVector`. Works with grammar recognizers too, but not with n-best results.
+    pub fn set_speaker_model(&mut self, speaker_model_path: &str) -> Result<(), Error> {
+        let speaker_model = SpeakerModel::new(speaker_model_path)
+            .ok_or_else(|| Error::ModelNotFound(format!("{}: Vosk could not load the speaker model", speaker_model_path)))?;
+        self.recognizer.set_speaker_model(&speaker_model);
+        self.speaker_model = Some(Arc::new(speaker_model));
+        Ok(())
+    }
+
+    /// Label final results with the speaker, clustering their x-vectors as they come in,
+    /// or stop labeling with `None`. Needs `set_speaker_model`.
+    pub fn set_diarization(&mut self, config: Option<DiarizationConfig>) {
+        self.speakers = config.map(SpeakerClustering::new);
+    }
+
+    /// Speakers found so far, when diarization is enabled
+    pub fn speaker_clustering(&self) -> Option<&SpeakerClustering> {
+        self.speakers.as_ref()
+    }
+
+    /// Run final results through `pipeline` before they are reported, `None` to report them
+    /// as recognized. Keyword spotting still sees the raw words.
+    pub fn set_post_processing(&mut self, pipeline: Option<Pipeline>) {
+        self.post_processing = pipeline;
+    }
+
+    /// Ask Vosk for up to `max_alternatives` hypotheses per utterance, 0 for just the best one.
+    /// They end up in `Utterance::alternatives`, sorted by confidence.
+    pub fn set_max_alternatives(&mut self, max_alternatives: u16) {
+        self.recognizer.set_max_alternatives(max_alternatives);
+        self.max_alternatives = max_alternatives;
+    }
+
+    /// Continue with another model, e.g. another language from a `ModelRegistry`, without
+    /// reloading anything. The utterance in progress is finalized with the old model and
+    /// returned; settings, grammar and speaker model carry over and timestamps keep counting
+    /// from the start of the stream.
+    pub fn set_model(&mut self, model: Arc<Model>) -> Result<Vec<SpeechEvent>, Error> {
+        // build first so a failure leaves the old model in place
+        let mut recognizer = create_recognizer(&model, self.sample_rate, self.grammar.as_deref())?;
+        recognizer.set_max_alternatives(self.max_alternatives);
+        if let Some(speaker_model) = &self.speaker_model {
+            recognizer.set_speaker_model(speaker_model);
+        }
+
+        let mut events = Vec::new();
+        self.finalize(&mut events);
+        self.recognizer = Box::new(recognizer);
+        self.model = Some(model);
+        self.last_decoding_state = DecodingState::Running;
+        // the new recognizer's clock starts now
+        self.samples_fed = 0;
+        self.time_offset = self.samples_seen as f32 / self.sample_rate;
+        Ok(events)
+    }
+
+    /// Model the recognizer runs on
+    pub fn model(&self) -> Option<&Arc<Model>> {
+        self.model.as_ref()
+    }
+
+    /// Clean up the audio before anything else sees it, or pass it on as is with `None`.
+    /// Best set before the first audio: samples the old front-end still holds are dropped
+    /// and a new noise profile is learned.
+    pub fn set_front_end(&mut self, config: Option<FrontEndConfig>) {
+        if let Some(front_end) = self.front_end.as_mut() {
+            self.samples_seen += front_end.flush().len() as u64;
+        }
+        self.front_end = config.map(|config| FrontEnd::new(config, self.sample_rate));
+    }
+
+    /// Build the front-end's noise profile from a recording of the room without speech,
+    /// rather than from the start of the stream
+    pub fn learn_noise(&mut self, noise: &[i16]) {
+        if let Some(front_end) = self.front_end.as_mut() {
+            front_end.learn_noise(noise);
+        }
+    }
+
+    /// Enable voice activity detection, or disable it with `None`.
+    ///
+    /// With VAD enabled silence is not passed to the recognizer, utterances are finalized
+    /// after `max_pause` of silence or `max_utterance` of speech, and `SpeechStarted` /
+    /// `SpeechEnded` events are reported. Without it Vosk's own endpointer decides.
+    pub fn set_vad(&mut self, config: Option<VadConfig>) {
+        self.vad = config.map(|config| Vad::new(config, self.sample_rate));
+        self.vad_pending.clear();
+        self.pre_roll.clear();
+    }
+
+    /// Change endpointing or silence settings mid-stream, e.g. a shorter `max_pause` or a
+    /// higher `energy_threshold_db` in a noisy room. Unlike `set_vad` the utterance in
+    /// progress carries on. Enables VAD with the default settings first if it is off.
+    pub fn configure_vad(&mut self, change: impl FnOnce(&mut VadConfig)) {
+        let vad = self.vad.get_or_insert_with(|| Vad::new(VadConfig::default(), self.sample_rate));
+        let mut config = vad.config().clone();
+        change(&mut config);
+        vad.set_config(config, self.sample_rate);
+    }
+
+    /// Settings of the voice activity detector, `None` when it is off
+    pub fn vad_config(&self) -> Option<&VadConfig> {
+        self.vad.as_ref().map(Vad::config)
+    }
+
+    /// Start collecting processing time and latency figures, or stop with `false`.
+    /// Enabling again starts over.
+    pub fn enable_metrics(&mut self, enable: bool) {
+        self.metrics = enable.then(Metrics::default);
+    }
+
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
     }
 
     /// Push a vector of interleaved stereo samples (i16: L, R, L, R, ...)
-    pub fn push_audio(&mut self, stereo_samples: &[i16]) -> Result<DecodingState, AcceptWaveformError> {
+    pub fn push_audio(&mut self, stereo_samples: &[i16]) -> Result<Vec<SpeechEvent>, Error> {
         // Downmix stereo to mono by averaging L and R
         let mut mono_samples = Vec::with_capacity(stereo_samples.len() / 2);
         for chunk in stereo_samples.chunks(2) {
@@ -32,90 +281,280 @@ impl SpeechToText {
                 mono_samples.push(avg);
             }
         }
-        let decoding_state = self.recognizer.accept_waveform(&mono_samples)?;
-        self.last_decoding_state = decoding_state;
-        Ok(decoding_state)
+        self.push_audio_mono(&mono_samples)
     }
 
     /// Push a vector of mono samples (i16)
-    pub fn push_audio_mono(&mut self, mono_samples: &[i16]) -> Result<DecodingState, AcceptWaveformError> {
-        let decoding_state = self.recognizer.accept_waveform(mono_samples)?;
-        match decoding_state {
-            DecodingState::Running => {
-                let partial = self.recognizer.partial_result();
-                let str = partial.partial.to_string();
-                if str != self.partial_text {
-                    self.partial_text = str;
-                    print!("\rpartial:{}", partial.partial);
-                    std::io::stdout().flush().unwrap();
+    pub fn push_audio_mono(&mut self, mono_samples: &[i16]) -> Result<Vec<SpeechEvent>, Error> {
+        let audio = Duration::from_secs_f64(mono_samples.len() as f64 / self.sample_rate as f64);
+        let started = self.metrics.as_mut().map(|metrics| metrics.start_chunk(Instant::now(), audio));
+        let events = match self.front_end.as_mut() {
+            Some(front_end) => {
+                let cleaned = front_end.process(mono_samples);
+                self.process(&cleaned)
+            }
+            None => self.process(mono_samples),
+        };
+        if let (Some(metrics), Some(started)) = (self.metrics.as_mut(), started) {
+            metrics.end_chunk(started, Instant::now(), audio);
+        }
+        events
+    }
+
+    fn process(&mut self, mono_samples: &[i16]) -> Result<Vec<SpeechEvent>, Error> {
+        let mut events = Vec::new();
+        let Some(frame_len) = self.vad.as_ref().map(Vad::frame_len) else {
+            self.samples_seen += mono_samples.len() as u64;
+            self.accept(mono_samples, &mut events)?;
+            return Ok(events);
+        };
+
+        self.vad_pending.extend_from_slice(mono_samples);
+        let pending = std::mem::take(&mut self.vad_pending);
+        let mut frames = pending.chunks_exact(frame_len);
+        // speech collected for the recognizer, passed in one call where possible
+        let mut speech = Vec::new();
+
+        for frame in &mut frames {
+            self.samples_seen += frame_len as u64;
+            let Some(vad) = self.vad.as_mut() else { break };
+            let was_in_speech = vad.in_speech();
+            let transition = vad.process_frame(frame);
+
+            if !was_in_speech {
+                self.pre_roll.extend(frame);
+                let keep = vad.pre_roll_len();
+                if self.pre_roll.len() > keep {
+                    self.pre_roll.drain(..self.pre_roll.len() - keep);
                 }
+            } else {
+                speech.extend_from_slice(frame);
             }
-            DecodingState::Finalized => {
-                match self.recognizer.result() {
-                    CompleteResult::Single(result) => {
-                        println!("\rresult: {:?}", result.text);
-                    }
-                    CompleteResult::Multiple(result) => {
-                        result.alternatives.clone().sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
-                        if result.alternatives.len() == 0 {
-                            ()
-                        }
-
-                        let text = result.alternatives[0].text;
-                        println!("text: {:?} confidence: {:?} alternatives: {:?}", text, result.alternatives[0].confidence, result.alternatives.len());
-                    }
+
+            match transition {
+                Some(VadTransition::SpeechStarted) => {
+                    let at = (self.samples_seen - vad.utterance_len() as u64) as f32 / self.sample_rate;
+                    // the pre-roll starts the recognizer's next stretch of audio
+                    let first_sample = self.samples_seen - self.pre_roll.len() as u64;
+                    self.time_offset = (first_sample as f32 - self.samples_fed as f32) / self.sample_rate;
+                    speech.extend(self.pre_roll.drain(..));
+                    events.push(SpeechEvent::SpeechStarted { at });
+                }
+                Some(VadTransition::SpeechEnded | VadTransition::UtteranceTooLong) => {
+                    self.accept(&speech, &mut events)?;
+                    speech.clear();
+                    self.finalize(&mut events);
+                    events.push(SpeechEvent::SpeechEnded { at: self.samples_seen as f32 / self.sample_rate });
                 }
+                None => {}
             }
-            DecodingState::Failed => {
-                println!("Failed");
+        }
+
+        self.vad_pending = frames.remainder().to_vec();
+        self.accept(&speech, &mut events)?;
+        Ok(events)
+    }
+
+    /// Finalize everything pushed so far, e.g. at the end of a file or stream
+    pub fn finish(&mut self) -> Result<Vec<SpeechEvent>, Error> {
+        self.force_finalize()
+    }
+
+    /// End the utterance in progress now instead of waiting for the endpointer, e.g. when
+    /// the push-to-talk button is released. Its result is returned, followed by
+    /// `SpeechEnded` if the VAD had detected speech. The stream itself carries on.
+    pub fn force_finalize(&mut self) -> Result<Vec<SpeechEvent>, Error> {
+        let mut events = match self.front_end.as_mut().map(FrontEnd::flush) {
+            Some(held_back) => self.process(&held_back)?,
+            None => Vec::new(),
+        };
+        let pending = std::mem::take(&mut self.vad_pending);
+        self.samples_seen += pending.len() as u64;
+        let in_speech = self.vad.as_ref().is_some_and(Vad::in_speech);
+        if in_speech {
+            self.accept(&pending, &mut events)?;
+        }
+        self.finalize(&mut events);
+        if let Some(vad) = self.vad.as_mut() {
+            if in_speech {
+                events.push(SpeechEvent::SpeechEnded { at: self.samples_seen as f32 / self.sample_rate });
             }
+            vad.reset();
         }
-        self.last_decoding_state = decoding_state;
-        Ok(decoding_state)
+        self.pre_roll.clear();
+        Ok(events)
     }
 
-    /// Get the latest partial result (words recognized so far)
-    pub fn get_partial(&mut self) -> Option<String> {
-        let partial = self.recognizer.partial_result();
-        println!("partial: {:?}", partial);
-        let val = partial.partial; // .get("partial")?.as_str()?;
-        if val.is_empty() { None } else { Some(val.to_string()) }
+    /// Drop the utterance in progress without reporting it, e.g. when push-to-talk is
+    /// pressed again or the user cancels. Settings are kept and timestamps keep counting
+    /// from the start of the stream.
+    pub fn reset(&mut self) {
+        self.recognizer.reset();
+        self.last_decoding_state = DecodingState::Running;
+        self.partial_text.clear();
+        // the dropped samples still happened on the stream's timeline
+        if let Some(front_end) = self.front_end.as_mut() {
+            self.samples_seen += front_end.flush().len() as u64;
+        }
+        self.samples_seen += self.vad_pending.len() as u64;
+        self.vad_pending.clear();
+        self.pre_roll.clear();
+        if let Some(vad) = self.vad.as_mut() {
+            vad.reset();
+        }
+    }
+
+    /// True while an utterance is open: the VAD has detected speech or the recognizer has
+    /// a partial hypothesis that was not finalized yet
+    pub fn is_in_utterance(&self) -> bool {
+        self.vad.as_ref().is_some_and(Vad::in_speech) || !self.partial_text.is_empty()
     }
 
-    pub fn get_result_wait(&mut self) {
-        let result = self.recognizer.result();
+    /// What the recognizer reported for the last audio: `Running` within an utterance,
+    /// `Finalized` once one was closed, by Vosk's endpointer, the VAD or `force_finalize`
+    pub fn decoding_state(&self) -> DecodingState {
+        self.last_decoding_state
+    }
 
-        match result {
-            CompleteResult::Single(result) => {
-                println!("result: {:?}", result.text);
-            }
-            CompleteResult::Multiple(result) => {
-                result.alternatives.clone().sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
-                if result.alternatives.len() == 0 {
-                    return;
-                }
+    /// Hypothesis of the utterance in progress as last reported by `Partial`, empty between
+    /// utterances
+    pub fn partial_text(&self) -> &str {
+        &self.partial_text
+    }
+
+    /// Run a whole file through the recognizer, calling `on_event` as results come in.
+    /// The file has to have the sample rate the recognizer was created with.
+    pub fn transcribe(&mut self, file: &mut AudioFile, mut on_event: impl FnMut(&SpeechEvent)) -> Result<Transcript, Error> {
+        if file.sample_rate() as f32 != self.sample_rate {
+            return Err(Error::InvalidAudio(format!(
+                "file is sampled at {} Hz, recognizer expects {} Hz",
+                file.sample_rate(),
+                self.sample_rate
+            )));
+        }
 
-                let text = result.alternatives[0].text;
-                println!("text: {:?} confidence: {:?} alternatives: {:?}", text, result.alternatives[0].confidence, result.alternatives.len());
+        let start = self.samples_seen;
+        let mut transcript = Transcript::default();
+        let mut samples = Vec::new();
+        // a tenth of a second per call, the same granularity Vosk's examples use
+        let chunk = (self.sample_rate / 10.0) as usize;
+        loop {
+            file.read_mono(&mut samples, chunk)?;
+            let events = if samples.is_empty() { self.finish() } else { self.push_audio_mono(&samples) };
+            for event in events? {
+                on_event(&event);
+                if let SpeechEvent::Result(utterance) = event {
+                    transcript.utterances.push(utterance);
+                }
+            }
+            if samples.is_empty() {
+                break;
             }
         }
+        transcript.duration = (self.samples_seen - start) as f32 / self.sample_rate;
+        Ok(transcript)
     }
 
-    /// Get the final result (words recognized in completed utterance)
-    pub fn get_final_result(&mut self) -> Option<String> {
-        let final_result = self.recognizer.final_result();
-        println!("final_result: {:?}", final_result);
-        match final_result {
-            CompleteResult::Single(result) => {
-                let val = result.text;
-                if val.is_empty() { None } else { Some(val.to_string()) }
-            }
-            CompleteResult::Multiple(result) => {
-                if result.alternatives.is_empty() { None } else {
-                    let val = result.alternatives[0].text;
-                    if val.is_empty() { None } else { Some(val.to_string()) }
+    /// Pass samples to the recognizer and collect what it reports
+    fn accept(&mut self, samples: &[i16], events: &mut Vec<SpeechEvent>) -> Result<(), Error> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        let decoding_state = self.recognizer.accept_waveform(samples)?;
+        self.samples_fed += samples.len() as u64;
+        match decoding_state {
+            DecodingState::Running => {
+                let (partial, first_word) = self.recognizer.partial_result();
+                if partial != self.partial_text {
+                    if let Some(metrics) = self.metrics.as_mut().filter(|_| self.partial_text.is_empty()) {
+                        let first_word = match first_word {
+                            Some(start) => start + self.time_offset,
+                            None => self.samples_seen as f32 / self.sample_rate,
+                        };
+                        metrics.first_partial(Instant::now(), first_word);
+                    }
+                    self.partial_text = partial;
+                    events.push(SpeechEvent::Partial(self.partial_text.clone()));
                 }
             }
+            DecodingState::Finalized => {
+                self.partial_text.clear();
+                let utterance = self.recognizer.result(self.time_offset);
+                self.emit_result(utterance, events);
+            }
+            DecodingState::Failed => {}
         }
+        self.last_decoding_state = decoding_state;
+        Ok(())
+    }
+
+    /// Flush the recognizer and report whatever it has
+    fn finalize(&mut self, events: &mut Vec<SpeechEvent>) {
+        self.partial_text.clear();
+        self.last_decoding_state = DecodingState::Finalized;
+        let utterance = self.recognizer.final_result(self.time_offset);
+        self.emit_result(utterance, events);
+    }
+
+    fn emit_result(&mut self, utterance: Option<Utterance>, events: &mut Vec<SpeechEvent>) {
+        let Some(mut utterance) = utterance else { return };
+        if let (Some(metrics), Some(end)) = (self.metrics.as_mut(), utterance.end()) {
+            metrics.final_result(Instant::now(), end);
+        }
+        if let (Some(speakers), Some(xvector)) = (self.speakers.as_mut(), utterance.xvector.as_ref()) {
+            utterance.speaker = Some(speakers.assign(xvector));
+        }
+        if let Some(keywords) = self.keywords.as_mut() {
+            keywords.spot(&utterance);
+        }
+        if let Some(pipeline) = &self.post_processing {
+            pipeline.apply(&mut utterance);
+        }
+        events.push(SpeechEvent::Result(utterance));
+    }
+
+    /// Get the latest partial result (words recognized so far)
+    pub fn get_partial(&mut self) -> Option<String> {
+        let (partial, _) = self.recognizer.partial_result();
+        if partial.is_empty() { None } else { Some(partial) }
+    }
+
+    /// Get the result of the utterance Vosk has finalized, once `DecodingState::Finalized` was reached
+    pub fn get_result_wait(&mut self) -> Option<Utterance> {
+        self.recognizer.result(self.time_offset)
+    }
+
+    /// Get the final result (words recognized in completed utterance)
+    pub fn get_final_result(&mut self) -> Option<String> {
+        self.recognizer.final_result(self.time_offset).map(|utterance| utterance.text)
     }
 }
+
+/// Load a model, checking the directory first for a clearer error than Vosk gives
+pub(crate) fn load_model(model_path: &str) -> Result<Model, Error> {
+    ModelRegistry::validate(Path::new(model_path))?;
+    Model::new(model_path).ok_or_else(|| Error::ModelNotFound(format!("{}: Vosk could not load the model", model_path)))
+}
+
+/// Recognizer with word timings, optionally restricted to `grammar`
+fn create_recognizer(model: &Model, sample_rate: f32, grammar: Option<&[String]>) -> Result<Recognizer, Error> {
+    let mut recognizer = match grammar {
+        Some(phrases) => Recognizer::new_with_grammar(model, sample_rate, phrases),
+        None => Recognizer::new(model, sample_rate),
+    }
+    .ok_or_else(|| Error::RecognizerCreation(format!("Vosk rejected the model or grammar at {} Hz", sample_rate)))?;
+    recognizer.set_max_alternatives(0);
+    recognizer.set_words(true);
+    recognizer.set_partial_words(true);
+    Ok(recognizer)
+}
+
+/// Transcribe a WAV or FLAC file, taking sample rate and channel count from its header
+pub fn transcribe_file(model_path: &str, path: impl AsRef<Path>) -> Result<Transcript, Error> {
+    let mut file = AudioFile::open(path)?;
+    let mut stt = SpeechToText::new(model_path, file.sample_rate() as f32)?;
+    stt.transcribe(&mut file, |_| {})
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+/// Timing collected while audio is pushed through `SpeechToText`.
+///
+/// Latencies are measured against the audio clock: audio time 0 is when the first chunk
+/// began recording, its push less its length, since a sound card hands a buffer over once
+/// it is full. They are only meaningful when audio arrives at real-time pace, from a
+/// microphone or a paced replay. Pushing faster than real time gives negative values.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    started: Option<Instant>,
+    audio: Duration,
+    processing: Duration,
+    /// Wall time spent in each push, in seconds
+    chunk_times: Vec<f32>,
+    /// Audio length of each push, in seconds
+    chunk_audio: Vec<f32>,
+    /// From the first word of an utterance until its first partial, in seconds
+    first_partial: Vec<f32>,
+    /// From the last word of an utterance until its final result, in seconds
+    final_result: Vec<f32>,
+}
+
+/// Distribution of a measured value, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Percentiles {
+    pub count: usize,
+    pub p50: f32,
+    pub p90: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Percentiles {
+    pub fn of(values: &[f32]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        // nearest-rank percentile
+        let at = |p: f32| sorted[((p * sorted.len() as f32).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Self { count: sorted.len(), p50: at(0.5), p90: at(0.9), p99: at(0.99), max: sorted[sorted.len() - 1] }
+    }
+}
+
+/// Summary of `Metrics`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MetricsReport {
+    /// Seconds of audio pushed
+    pub audio: f32,
+    /// Seconds spent recognizing it
+    pub processing: f32,
+    /// Processing time over audio time, below 1.0 keeps up with live audio
+    pub real_time_factor: f32,
+    /// Processing time per push
+    pub chunk_processing: Percentiles,
+    /// Real-time factor per push
+    pub chunk_real_time_factor: Percentiles,
+    pub first_partial_latency: Percentiles,
+    pub final_result_latency: Percentiles,
+}
+
+impl Metrics {
+    /// Seconds of wall clock at `now` since audio time 0, i.e. the live audio position
+    fn position(&self, now: Instant) -> f32 {
+        self.started.map_or(0.0, |started| now.saturating_duration_since(started).as_secs_f32())
+    }
+
+    /// A push of `audio` worth of samples at `now`
+    pub(crate) fn start_chunk(&mut self, now: Instant, audio: Duration) -> Instant {
+        self.started.get_or_insert(now.checked_sub(audio).unwrap_or(now));
+        now
+    }
+
+    pub(crate) fn end_chunk(&mut self, started: Instant, now: Instant, audio: Duration) {
+        let processing = now.saturating_duration_since(started);
+        self.audio += audio;
+        self.processing += processing;
+        self.chunk_times.push(processing.as_secs_f32());
+        self.chunk_audio.push(audio.as_secs_f32());
+    }
+
+    /// First partial, at `now`, of an utterance whose first word starts at `audio_time`
+    pub(crate) fn first_partial(&mut self, now: Instant, audio_time: f32) {
+        self.first_partial.push(self.position(now) - audio_time);
+    }
+
+    /// Final result, at `now`, of an utterance whose last word ends at `audio_time`
+    pub(crate) fn final_result(&mut self, now: Instant, audio_time: f32) {
+        self.final_result.push(self.position(now) - audio_time);
+    }
+
+    pub fn report(&self) -> MetricsReport {
+        let audio = self.audio.as_secs_f32();
+        let processing = self.processing.as_secs_f32();
+        let chunk_rtf: Vec<f32> = self
+            .chunk_times
+            .iter()
+            .zip(&self.chunk_audio)
+            .filter(|(_, &audio)| audio > 0.0)
+            .map(|(time, audio)| time / audio)
+            .collect();
+        MetricsReport {
+            audio,
+            processing,
+            real_time_factor: if audio > 0.0 { processing / audio } else { 0.0 },
+            chunk_processing: Percentiles::of(&self.chunk_times),
+            chunk_real_time_factor: Percentiles::of(&chunk_rtf),
+            first_partial_latency: Percentiles::of(&self.first_partial),
+            final_result_latency: Percentiles::of(&self.final_result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNK: Duration = Duration::from_millis(20);
+
+    /// A paced replay on a fake clock: each chunk is pushed once recorded, and takes
+    /// `processing` to recognize
+    fn replay(chunks: u32, processing: Duration, mut each: impl FnMut(&mut Metrics, Instant, u32)) -> Metrics {
+        let origin = Instant::now();
+        let mut metrics = Metrics::default();
+        for chunk in 1..=chunks {
+            let pushed = origin + CHUNK * chunk;
+            let started = metrics.start_chunk(pushed, CHUNK);
+            each(&mut metrics, pushed + processing, chunk);
+            metrics.end_chunk(started, pushed + processing, CHUNK);
+        }
+        metrics
+    }
+
+    #[test]
+    fn audio_time_starts_with_the_first_chunk() {
+        // a word ending with the third chunk, finalized as soon as it was processed
+        let metrics = replay(5, Duration::from_millis(5), |metrics, now, chunk| {
+            if chunk == 3 {
+                metrics.final_result(now, (CHUNK * 3).as_secs_f32());
+            }
+        });
+        let latency = metrics.report().final_result_latency;
+        assert_eq!(latency.count, 1);
+        assert!((latency.max - 0.005).abs() < 1e-4, "{:?}", latency);
+    }
+
+    #[test]
+    fn first_partial_latency() {
+        // a word starting at 10 ms gets its partial in the second chunk
+        let metrics = replay(3, Duration::from_millis(2), |metrics, now, chunk| {
+            if chunk == 2 {
+                metrics.first_partial(now, 0.010);
+            }
+        });
+        let latency = metrics.report().first_partial_latency;
+        assert!((latency.p50 - 0.032).abs() < 1e-4, "{:?}", latency);
+    }
+
+    #[test]
+    fn real_time_factor() {
+        let report = replay(10, Duration::from_millis(5), |_, _, _| {}).report();
+        assert!((report.audio - 0.2).abs() < 1e-6);
+        assert!((report.processing - 0.05).abs() < 1e-6);
+        assert!((report.real_time_factor - 0.25).abs() < 1e-4);
+        assert_eq!(report.chunk_processing.count, 10);
+        assert!((report.chunk_real_time_factor.max - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn percentiles_by_nearest_rank() {
+        let values: Vec<f32> = (1..=100).map(|value| value as f32).collect();
+        let percentiles = Percentiles::of(&values);
+        assert_eq!((percentiles.p50, percentiles.p90, percentiles.p99, percentiles.max), (50.0, 90.0, 99.0, 100.0));
+        assert_eq!(Percentiles::of(&[]), Percentiles::default());
+    }
+}
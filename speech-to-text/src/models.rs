@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use vosk::Model;
+
+use crate::{load_model, Error, SpeechToText};
+
+struct Entry {
+    path: PathBuf,
+    /// Loaded on first use
+    model: Option<Arc<Model>>,
+}
+
+/// Vosk models keyed by language ("en", "pl", ...), loaded on first use and cached.
+///
+/// Models are handed out as `Arc<Model>`: recognizers keep theirs alive, so `unload` only
+/// drops the registry's copy and the memory is freed once the last recognizer using it is
+/// gone. The registry can be shared between threads.
+#[derive(Default)]
+pub struct ModelRegistry {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ModelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make the model in `path` available as `language`, replacing an earlier one.
+    /// The directory layout is checked now, the model is loaded by the first `model` call.
+    pub fn register(&self, language: impl Into<String>, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        Self::validate(path)?;
+        let entry = Entry { path: path.to_path_buf(), model: None };
+        self.lock().insert(language.into(), entry);
+        Ok(())
+    }
+
+    /// Check that `path` looks like a Vosk model: an acoustic model, its feature config and
+    /// a decoding graph, in the `am/ conf/ graph/` layout or all in one directory
+    pub fn validate(path: &Path) -> Result<(), Error> {
+        if !path.is_dir() {
+            return Err(Error::ModelNotFound(format!("{}: no such directory", path.display())));
+        }
+        let exists = |candidates: &[&str]| candidates.iter().any(|file| path.join(file).is_file());
+        let mut missing = Vec::new();
+        if !exists(&["am/final.mdl", "final.mdl"]) {
+            missing.push("am/final.mdl");
+        }
+        if !exists(&["conf/mfcc.conf", "mfcc.conf"]) {
+            missing.push("conf/mfcc.conf");
+        }
+        // a static graph or the lookahead pair
+        let lookahead = exists(&["graph/HCLr.fst"]) && exists(&["graph/Gr.fst"]);
+        if !exists(&["graph/HCLG.fst", "HCLG.fst"]) && !lookahead {
+            missing.push("graph/HCLG.fst or graph/HCLr.fst + graph/Gr.fst");
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(Error::ModelNotFound(format!("{}: not a Vosk model, missing {}", path.display(), missing.join(", "))))
+    }
+
+    /// Model for `language`, loading it if this is the first request
+    pub fn model(&self, language: &str) -> Result<Arc<Model>, Error> {
+        // loading holds the lock, so two threads asking at once load the model once
+        let mut entries = self.lock();
+        let entry = entries
+            .get_mut(language)
+            .ok_or_else(|| Error::ModelNotFound(format!("no model registered for language {}", language)))?;
+        if let Some(model) = &entry.model {
+            return Ok(Arc::clone(model));
+        }
+        let model = load_model(&entry.path.to_string_lossy())?;
+        Ok(Arc::clone(entry.model.insert(Arc::new(model))))
+    }
+
+    /// Recognizer for `language` on the cached model
+    pub fn recognizer(&self, language: &str, sample_rate: f32) -> Result<SpeechToText, Error> {
+        SpeechToText::from_model(self.model(language)?, sample_rate)
+    }
+
+    /// Drop the cached model of `language`, it is loaded again when next asked for.
+    /// Recognizers still using it are not affected.
+    pub fn unload(&self, language: &str) {
+        if let Some(entry) = self.lock().get_mut(language) {
+            entry.model = None;
+        }
+    }
+
+    /// Registered languages, sorted
+    pub fn languages(&self) -> Vec<String> {
+        let mut languages: Vec<String> = self.lock().keys().cloned().collect();
+        languages.sort();
+        languages
+    }
+
+    pub fn is_loaded(&self, language: &str) -> bool {
+        self.lock().get(language).is_some_and(|entry| entry.model.is_some())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        // a panic while loading leaves the map itself intact
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Directory in the temporary directory with empty files, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str, files: &[&str]) -> Self {
+            let path = std::env::temp_dir().join(format!("speech-to-text-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            for file in files {
+                let file = path.join(file);
+                fs::create_dir_all(file.parent().unwrap()).unwrap();
+                fs::write(file, b"").unwrap();
+            }
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn reason(result: Result<(), Error>) -> String {
+        match result {
+            Err(Error::ModelNotFound(reason)) => reason,
+            other => panic!("expected ModelNotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn model_layouts() {
+        let layouts: &[(&str, &[&str])] = &[
+            ("split", &["am/final.mdl", "conf/mfcc.conf", "graph/HCLG.fst"]),
+            ("flat", &["final.mdl", "mfcc.conf", "HCLG.fst"]),
+            ("lookahead", &["am/final.mdl", "conf/mfcc.conf", "graph/HCLr.fst", "graph/Gr.fst"]),
+        ];
+        for (name, files) in layouts {
+            let dir = TempDir::new(name, files);
+            assert!(ModelRegistry::validate(&dir.0).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn incomplete_models_are_rejected() {
+        // created and removed again right away
+        let missing = TempDir::new("missing", &[]).0.clone();
+        assert!(reason(ModelRegistry::validate(&missing)).ends_with("no such directory"));
+
+        let dir = TempDir::new("no-am", &["conf/mfcc.conf", "graph/HCLG.fst"]);
+        assert!(reason(ModelRegistry::validate(&dir.0)).ends_with("missing am/final.mdl"));
+
+        let dir = TempDir::new("no-conf", &["am/final.mdl", "graph/HCLr.fst"]);
+        let reason = reason(ModelRegistry::validate(&dir.0));
+        assert!(reason.ends_with("missing conf/mfcc.conf, graph/HCLG.fst or graph/HCLr.fst + graph/Gr.fst"), "{}", reason);
+
+        // a file where the directory should be
+        let dir = TempDir::new("file", &["model"]);
+        assert!(ModelRegistry::validate(&dir.0.join("model")).is_err());
+    }
+
+    #[test]
+    fn registry_keeps_one_model_per_language() {
+        let first = TempDir::new("first", &["final.mdl", "mfcc.conf", "HCLG.fst"]);
+        let second = TempDir::new("second", &["final.mdl", "mfcc.conf", "HCLG.fst"]);
+        let broken = TempDir::new("broken", &["final.mdl"]);
+
+        let registry = ModelRegistry::new();
+        registry.register("pl", &first.0).unwrap();
+        registry.register("en", &first.0).unwrap();
+        // registering a language again replaces its model
+        registry.register("en", &second.0).unwrap();
+        assert_eq!(registry.languages(), ["en", "pl"]);
+        assert_eq!(registry.lock()["en"].path, second.0);
+
+        // a rejected model leaves the earlier one in place
+        assert!(matches!(registry.register("en", &broken.0), Err(Error::ModelNotFound(_))));
+        assert_eq!(registry.lock()["en"].path, second.0);
+        assert_eq!(registry.languages(), ["en", "pl"]);
+
+        // nothing is loaded before it is asked for
+        assert!(!registry.is_loaded("en"));
+        registry.unload("en");
+        registry.unload("de");
+        assert!(matches!(registry.model("de"), Err(Error::ModelNotFound(reason)) if reason.ends_with("language de")));
+    }
+}
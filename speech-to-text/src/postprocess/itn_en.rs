@@ -0,0 +1,269 @@
+//! English numbers, dates, times and amounts: "twenty one" -> "21", "march third twenty twenty four" ->
+//! "March 3, 2024", "three thirty p m" -> "3:30 pm", "five dollars" -> "$5"
+
+const UNITS: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: [&str; 8] = ["twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+const ORDINAL_UNITS: [&str; 20] = [
+    "zeroth", "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth",
+    "eleventh", "twelfth", "thirteenth", "fourteenth", "fifteenth", "sixteenth", "seventeenth", "eighteenth",
+    "nineteenth",
+];
+const ORDINAL_TENS: [&str; 8] = [
+    "twentieth", "thirtieth", "fortieth", "fiftieth", "sixtieth", "seventieth", "eightieth", "ninetieth",
+];
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+/// Months that are words of their own too: "you may two times", "march five miles"
+const AMBIGUOUS_MONTHS: [&str; 2] = ["march", "may"];
+
+/// Rewrite the longest number, date, time or amount at the start of `tokens`
+pub(super) fn rewrite(tokens: &[&str]) -> Option<(usize, String)> {
+    date(tokens).or_else(|| time(tokens)).or_else(|| money(tokens)).or_else(|| {
+        let (value, consumed) = cardinal(tokens)?;
+        // single digits read better as words: "one of them", "two people"
+        if consumed == 1 && value < 10 {
+            return None;
+        }
+        Some((consumed, value.to_string()))
+    })
+}
+
+fn unit(token: &str) -> Option<u64> {
+    UNITS.iter().position(|&u| u == token).map(|v| v as u64)
+}
+
+fn tens(token: &str) -> Option<u64> {
+    TENS.iter().position(|&t| t == token).map(|v| (v as u64 + 2) * 10)
+}
+
+fn scale(token: &str) -> Option<u64> {
+    match token {
+        "thousand" => Some(1_000),
+        "million" => Some(1_000_000),
+        "billion" => Some(1_000_000_000),
+        _ => None,
+    }
+}
+
+/// Longest cardinal number at the start, with the number of tokens it takes
+fn cardinal(tokens: &[&str]) -> Option<(u64, usize)> {
+    let mut total = 0;
+    // value below the last scale word, 0..999
+    let mut group = 0;
+    let mut last_scale = u64::MAX;
+    let mut last_tens = false;
+    // tokens making up the number so far
+    let mut end = 0;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let token = tokens[i];
+        if token == "and" && end == i && end > 0 {
+            // "one hundred and five", but not a trailing "and"
+            if tokens.get(i + 1).is_some_and(|t| unit(t).is_some() || tens(t).is_some()) {
+                i += 1;
+                continue;
+            }
+            break;
+        }
+
+        if let Some(v) = unit(token) {
+            if v == 0 {
+                // zero only stands on its own
+                if end > 0 {
+                    break;
+                }
+                return Some((0, 1));
+            }
+            if !(group % 100 == 0 || (last_tens && v < 10)) {
+                break;
+            }
+            group += v;
+            last_tens = false;
+        } else if let Some(v) = tens(token) {
+            if group % 100 != 0 {
+                break;
+            }
+            group += v;
+            last_tens = true;
+        } else if token == "hundred" {
+            // "nineteen hundred" only before any thousands
+            let max = if last_scale == u64::MAX { 100 } else { 10 };
+            if !(1..max).contains(&group) {
+                break;
+            }
+            group *= 100;
+            last_tens = false;
+        } else if let Some(v) = scale(token) {
+            if group == 0 || v >= last_scale {
+                break;
+            }
+            total += group * v;
+            group = 0;
+            last_scale = v;
+            last_tens = false;
+        } else {
+            break;
+        }
+        i += 1;
+        end = i;
+    }
+
+    (end > 0).then_some((total + group, end))
+}
+
+/// Ordinal 1..99 at the start: "third", "twenty first", "thirtieth"
+fn ordinal(tokens: &[&str]) -> Option<(u64, usize)> {
+    let first = *tokens.first()?;
+    if let Some(v) = ORDINAL_UNITS.iter().position(|&o| o == first) {
+        return Some((v as u64, 1));
+    }
+    if let Some(v) = ORDINAL_TENS.iter().position(|&o| o == first) {
+        return Some(((v as u64 + 2) * 10, 1));
+    }
+    let t = tens(first)?;
+    let second = *tokens.get(1)?;
+    let u = ORDINAL_UNITS.iter().position(|&o| o == second)?;
+    (1..10).contains(&u).then_some((t + u as u64, 2))
+}
+
+/// Day of month, ordinal or cardinal
+fn day(tokens: &[&str]) -> Option<(u64, usize)> {
+    let (value, consumed) = ordinal(tokens).or_else(|| cardinal(tokens))?;
+    (1..=31).contains(&value).then_some((value, consumed))
+}
+
+/// Year as "nineteen eighty four", "twenty twenty four", "nineteen oh five" or "two thousand ten"
+fn year(tokens: &[&str]) -> Option<(u64, usize)> {
+    if let Some((value, consumed)) = cardinal(tokens) {
+        if (1000..3000).contains(&value) && tokens[..consumed].contains(&"thousand") {
+            return Some((value, consumed));
+        }
+    }
+
+    let (century, first_len) = two_digits(tokens)?;
+    if !(10..30).contains(&century) {
+        return None;
+    }
+    let rest = &tokens[first_len..];
+    match rest.first() {
+        Some(&"hundred") => Some((century * 100, first_len + 1)),
+        Some(&"oh") => {
+            let u = unit(rest.get(1)?).filter(|&u| (1..10).contains(&u))?;
+            Some((century * 100 + u, first_len + 2))
+        }
+        _ => {
+            let (v, len) = two_digits(rest)?;
+            (v >= 10).then_some((century * 100 + v, first_len + len))
+        }
+    }
+}
+
+/// Number 0..99 written with at most two words
+fn two_digits(tokens: &[&str]) -> Option<(u64, usize)> {
+    let first = *tokens.first()?;
+    if let Some(v) = unit(first) {
+        return Some((v, 1));
+    }
+    let t = tens(first)?;
+    match tokens.get(1).and_then(|&u| unit(u)) {
+        Some(u) if (1..10).contains(&u) => Some((t + u, 2)),
+        _ => Some((t, 1)),
+    }
+}
+
+fn month(token: &str) -> Option<&'static str> {
+    const NAMES: [&str; 12] = [
+        "January", "February", "March", "April", "May", "June",
+        "July", "August", "September", "October", "November", "December",
+    ];
+    MONTHS.iter().position(|&m| m == token).map(|i| NAMES[i])
+}
+
+/// "march third [twenty twenty four]", "june five", "may nineteen eighty four" or "the third of march
+/// [twenty twenty four]"
+fn date(tokens: &[&str]) -> Option<(usize, String)> {
+    let first = *tokens.first()?;
+    let (name, day, mut consumed) = if let Some(name) = month(first) {
+        let rest = &tokens[1..];
+        let ordinal_day = ordinal(rest).filter(|(d, _)| (1..=31).contains(d));
+        let day = ordinal_day.or_else(|| day(rest));
+        let year_follows = day.is_some_and(|(_, len)| year(&rest[len..]).is_some());
+        if !year_follows {
+            if let Some((year, len)) = year(rest) {
+                return Some((1 + len, format!("{} {}", name, year)));
+            }
+        }
+        // a cardinal alone only makes a day after a month that is nothing else
+        let unambiguous = year_follows || ordinal_day.is_some() || !AMBIGUOUS_MONTHS.contains(&first);
+        let (day, len) = day.filter(|_| unambiguous)?;
+        (name, day, 1 + len)
+    } else {
+        let skip = usize::from(first == "the");
+        let (day, len) = ordinal(&tokens[skip..]).filter(|(d, _)| (1..=31).contains(d))?;
+        let of = skip + len;
+        if tokens.get(of) != Some(&"of") {
+            return None;
+        }
+        (month(tokens.get(of + 1)?)?, day, of + 2)
+    };
+
+    let mut text = format!("{} {}", name, day);
+    if let Some((year, len)) = year(&tokens[consumed..]) {
+        text.push_str(&format!(", {}", year));
+        consumed += len;
+    }
+    Some((consumed, text))
+}
+
+/// "three thirty p m" -> "3:30 pm", "nine oh five am" -> "9:05 am", "ten o'clock" -> "10:00".
+/// Without "o'clock" or "am"/"pm" numbers are left alone.
+fn time(tokens: &[&str]) -> Option<(usize, String)> {
+    let hour = unit(tokens.first()?).filter(|h| (1..=12).contains(h))?;
+    if tokens.get(1) == Some(&"o'clock") {
+        return Some((2, format!("{}:00", hour)));
+    }
+    let minutes = match &tokens[1..] {
+        ["oh", m, ..] => unit(m).filter(|m| (1..10).contains(m)).map(|m| (m, 2)),
+        rest => two_digits(rest).filter(|(m, _)| (10..60).contains(m)),
+    };
+    let at = 1 + minutes.map_or(0, |(_, len)| len);
+    let (suffix, len) = match &tokens[at..] {
+        ["am", ..] => ("am", 1),
+        ["pm", ..] => ("pm", 1),
+        ["a", "m", ..] => ("am", 2),
+        ["p", "m", ..] => ("pm", 2),
+        _ => return None,
+    };
+    let text = match minutes {
+        Some((minutes, _)) => format!("{}:{:02} {}", hour, minutes, suffix),
+        None => format!("{} {}", hour, suffix),
+    };
+    Some((at + len, text))
+}
+
+/// "five dollars" -> "$5", "twenty dollars and fifty cents" -> "$20.50", "ten euros" -> "€10"
+fn money(tokens: &[&str]) -> Option<(usize, String)> {
+    let (amount, len) = cardinal(tokens)?;
+    let symbol = match *tokens.get(len)? {
+        "dollar" | "dollars" => "$",
+        "euro" | "euros" => "€",
+        _ => return None,
+    };
+    let mut consumed = len + 1;
+    let mut text = format!("{}{}", symbol, amount);
+    if let ["and", rest @ ..] = &tokens[consumed..] {
+        if let Some((cents, cents_len)) = cardinal(rest).filter(|(c, _)| (1..100).contains(c)) {
+            if matches!(rest.get(cents_len), Some(&"cent" | &"cents")) {
+                text.push_str(&format!(".{:02}", cents));
+                consumed += 1 + cents_len + 1;
+            }
+        }
+    }
+    Some((consumed, text))
+}
@@ -0,0 +1,229 @@
+//! Polish numbers, dates, times and amounts: "dwadzieścia jeden" -> "21",
+//! "trzeciego marca dwa tysiące dwudziestego czwartego roku" -> "3 marca 2024 r.",
+//! "o godzinie piętnastej trzydzieści" -> "o godzinie 15:30", "pięć złotych" -> "5 zł"
+
+const UNITS: [&[&str]; 20] = [
+    &["zero"], &["jeden", "jedna", "jedno"], &["dwa", "dwie"], &["trzy"], &["cztery"], &["pięć"],
+    &["sześć"], &["siedem"], &["osiem"], &["dziewięć"], &["dziesięć"], &["jedenaście"], &["dwanaście"],
+    &["trzynaście"], &["czternaście"], &["piętnaście"], &["szesnaście"], &["siedemnaście"],
+    &["osiemnaście"], &["dziewiętnaście"],
+];
+const TENS: [&str; 8] = [
+    "dwadzieścia", "trzydzieści", "czterdzieści", "pięćdziesiąt", "sześćdziesiąt", "siedemdziesiąt",
+    "osiemdziesiąt", "dziewięćdziesiąt",
+];
+const HUNDREDS: [&str; 9] = [
+    "sto", "dwieście", "trzysta", "czterysta", "pięćset", "sześćset", "siedemset", "osiemset", "dziewięćset",
+];
+/// Genitive ordinals, the case used for days and years in dates
+const ORDINAL_UNITS: [&str; 20] = [
+    "zerowego", "pierwszego", "drugiego", "trzeciego", "czwartego", "piątego", "szóstego", "siódmego",
+    "ósmego", "dziewiątego", "dziesiątego", "jedenastego", "dwunastego", "trzynastego", "czternastego",
+    "piętnastego", "szesnastego", "siedemnastego", "osiemnastego", "dziewiętnastego",
+];
+const ORDINAL_TENS: [&str; 8] = [
+    "dwudziestego", "trzydziestego", "czterdziestego", "pięćdziesiątego", "sześćdziesiątego",
+    "siedemdziesiątego", "osiemdziesiątego", "dziewięćdziesiątego",
+];
+/// Months in the genitive, as they follow the day: "trzeciego marca"
+const MONTHS: [&str; 12] = [
+    "stycznia", "lutego", "marca", "kwietnia", "maja", "czerwca",
+    "lipca", "sierpnia", "września", "października", "listopada", "grudnia",
+];
+/// Feminine ordinals naming the hour, nominative and locative: "godzina piąta", "o godzinie piątej"
+const HOURS: [[&str; 2]; 20] = [
+    ["pierwsza", "pierwszej"], ["druga", "drugiej"], ["trzecia", "trzeciej"], ["czwarta", "czwartej"],
+    ["piąta", "piątej"], ["szósta", "szóstej"], ["siódma", "siódmej"], ["ósma", "ósmej"],
+    ["dziewiąta", "dziewiątej"], ["dziesiąta", "dziesiątej"], ["jedenasta", "jedenastej"],
+    ["dwunasta", "dwunastej"], ["trzynasta", "trzynastej"], ["czternasta", "czternastej"],
+    ["piętnasta", "piętnastej"], ["szesnasta", "szesnastej"], ["siedemnasta", "siedemnastej"],
+    ["osiemnasta", "osiemnastej"], ["dziewiętnasta", "dziewiętnastej"], ["dwudziesta", "dwudziestej"],
+];
+
+/// Rewrite the longest number, date, time or amount at the start of `tokens`
+pub(super) fn rewrite(tokens: &[&str]) -> Option<(usize, String)> {
+    date(tokens).or_else(|| year_with_suffix(tokens)).or_else(|| time(tokens)).or_else(|| money(tokens)).or_else(|| {
+        let (value, consumed) = cardinal(tokens)?;
+        // single digits read better as words: "jeden z nich", "dwa razy"
+        if consumed == 1 && value < 10 {
+            return None;
+        }
+        Some((consumed, value.to_string()))
+    })
+}
+
+fn unit(token: &str) -> Option<u64> {
+    UNITS.iter().position(|forms| forms.contains(&token)).map(|v| v as u64)
+}
+
+fn tens(token: &str) -> Option<u64> {
+    TENS.iter().position(|&t| t == token).map(|v| (v as u64 + 2) * 10)
+}
+
+fn hundreds(token: &str) -> Option<u64> {
+    HUNDREDS.iter().position(|&h| h == token).map(|v| (v as u64 + 1) * 100)
+}
+
+/// Scale word with whether it is the singular form, which may stand without a count
+fn scale(token: &str) -> Option<(u64, bool)> {
+    match token {
+        "tysiąc" => Some((1_000, true)),
+        "tysiące" | "tysięcy" => Some((1_000, false)),
+        "milion" => Some((1_000_000, true)),
+        "miliony" | "milionów" => Some((1_000_000, false)),
+        "miliard" => Some((1_000_000_000, true)),
+        "miliardy" | "miliardów" => Some((1_000_000_000, false)),
+        _ => None,
+    }
+}
+
+/// Longest cardinal number at the start, with the number of tokens it takes
+fn cardinal(tokens: &[&str]) -> Option<(u64, usize)> {
+    let mut total = 0;
+    // value below the last scale word, 0..999
+    let mut group = 0;
+    let mut last_scale = u64::MAX;
+    // hundreds (2), tens (1) and units (0) follow each other in this order within a group
+    let mut rank = 3;
+    let mut end = 0;
+
+    for &token in tokens {
+        if let Some(v) = unit(token) {
+            if v == 0 {
+                // zero only stands on its own
+                if end > 0 {
+                    break;
+                }
+                return Some((0, 1));
+            }
+            // "dwadzieścia jeden" but not "dwadzieścia jedenaście"
+            if rank == 0 || (rank == 1 && v >= 10) {
+                break;
+            }
+            group += v;
+            rank = 0;
+        } else if let Some(v) = tens(token) {
+            if rank <= 1 {
+                break;
+            }
+            group += v;
+            rank = 1;
+        } else if let Some(v) = hundreds(token) {
+            if rank <= 2 {
+                break;
+            }
+            group += v;
+            rank = 2;
+        } else if let Some((v, singular)) = scale(token) {
+            if v >= last_scale || (group == 0 && !singular) {
+                break;
+            }
+            total += group.max(1) * v;
+            group = 0;
+            last_scale = v;
+            rank = 3;
+        } else {
+            break;
+        }
+        end += 1;
+    }
+
+    (end > 0).then_some((total + group, end))
+}
+
+/// Ordinal 1..99 at the start: "trzeciego", "dwudziestego czwartego"
+fn ordinal(tokens: &[&str]) -> Option<(u64, usize)> {
+    let first = *tokens.first()?;
+    if let Some(v) = ORDINAL_UNITS.iter().position(|&o| o == first) {
+        return Some((v as u64, 1));
+    }
+    let t = ORDINAL_TENS.iter().position(|&o| o == first).map(|v| (v as u64 + 2) * 10)?;
+    match tokens.get(1).and_then(|&u| ORDINAL_UNITS.iter().position(|&o| o == u)) {
+        Some(u) if (1..10).contains(&u) => Some((t + u as u64, 2)),
+        _ => Some((t, 1)),
+    }
+}
+
+/// Year in the genitive: only the last part is an ordinal, "tysiąc dziewięćset
+/// osiemdziesiątego czwartego", "dwa tysiące dwudziestego". A round 2000 is "dwutysięcznego".
+fn year(tokens: &[&str]) -> Option<(u64, usize)> {
+    if tokens.first() == Some(&"dwutysięcznego") {
+        return Some((2000, 1));
+    }
+    let (prefix, prefix_len) = cardinal(tokens)?;
+    if prefix < 1000 || prefix % 100 != 0 {
+        return None;
+    }
+    let (last, len) = ordinal(&tokens[prefix_len..])?;
+    Some((prefix + last, prefix_len + len))
+}
+
+/// Year followed by "roku": "dwa tysiące dwudziestego roku" -> "2020 r."
+fn year_with_suffix(tokens: &[&str]) -> Option<(usize, String)> {
+    let (year, len) = year(tokens)?;
+    (tokens.get(len) == Some(&"roku")).then(|| (len + 1, format!("{} r.", year)))
+}
+
+/// "trzeciego marca [dwa tysiące dwudziestego czwartego] [roku]"
+fn date(tokens: &[&str]) -> Option<(usize, String)> {
+    let (day, len) = ordinal(tokens).filter(|(d, _)| (1..=31).contains(d))?;
+    let month = MONTHS.iter().position(|&m| Some(&m) == tokens.get(len))?;
+    let mut consumed = len + 1;
+
+    let mut text = format!("{} {}", day, MONTHS[month]);
+    if let Some((year, len)) = year(&tokens[consumed..]) {
+        text.push_str(&format!(" {}", year));
+        consumed += len;
+        if tokens.get(consumed) == Some(&"roku") {
+            text.push_str(" r.");
+            consumed += 1;
+        }
+    }
+    Some((consumed, text))
+}
+
+/// Hour 1..24 at the start: "piąta", "dwudziestej pierwszej"
+fn hour(tokens: &[&str]) -> Option<(u64, usize)> {
+    let ordinal = |token: &&str| HOURS.iter().position(|forms| forms.contains(token)).map(|h| h as u64 + 1);
+    let first = ordinal(tokens.first()?)?;
+    match tokens.get(1).and_then(ordinal) {
+        Some(u) if first == 20 && u <= 4 => Some((first + u, 2)),
+        _ => Some((first, 1)),
+    }
+}
+
+/// "godzina piąta" -> "godzina 5:00", "o godzinie piętnastej zero pięć" -> "o godzinie 15:05".
+/// The hour needs "godzina" before it, "piąta" alone is just "fifth".
+fn time(tokens: &[&str]) -> Option<(usize, String)> {
+    let word = *tokens.first().filter(|&&t| t == "godzina" || t == "godzinie")?;
+    let (hour, len) = hour(&tokens[1..])?;
+    let at = 1 + len;
+    let minutes = match &tokens[at..] {
+        ["zero", m, ..] => unit(m).filter(|m| (1..10).contains(m)).map(|m| (m, 2)),
+        rest => cardinal(rest).filter(|(m, _)| (1..60).contains(m)),
+    };
+    let (minutes, len) = minutes.unwrap_or((0, 0));
+    Some((at + len, format!("{} {}:{:02}", word, hour, minutes)))
+}
+
+/// "pięć złotych" -> "5 zł", "dwa złote pięćdziesiąt groszy" -> "2,50 zł", "sto euro" -> "100 €"
+fn money(tokens: &[&str]) -> Option<(usize, String)> {
+    let (amount, len) = cardinal(tokens)?;
+    match *tokens.get(len)? {
+        "złoty" | "złote" | "złotych" => {
+            let mut consumed = len + 1;
+            let mut text = amount.to_string();
+            let skip = usize::from(tokens.get(consumed) == Some(&"i"));
+            let rest = &tokens[consumed + skip..];
+            if let Some((grosze, grosze_len)) = cardinal(rest).filter(|(g, _)| (1..100).contains(g)) {
+                if matches!(rest.get(grosze_len), Some(&"grosz" | &"grosze" | &"groszy")) {
+                    text.push_str(&format!(",{:02}", grosze));
+                    consumed += skip + grosze_len + 1;
+                }
+            }
+            Some((consumed, format!("{} zł", text)))
+        }
+        "euro" => Some((len + 1, format!("{} €", amount))),
+        _ => None,
+    }
+}
@@ -0,0 +1,359 @@
+use std::time::Duration;
+
+use crate::{Utterance, Word};
+
+mod itn_en;
+mod itn_pl;
+
+/// One step of the post-processing applied to final results.
+///
+/// Stages work on the word list so timings survive; words merged by a stage (e.g. "twenty
+/// one" into "21") span from the start of the first to the end of the last one.
+pub trait PostProcessor: Send {
+    fn process(&self, words: Vec<Word>) -> Vec<Word>;
+}
+
+/// Rule sets available for language specific stages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Polish,
+}
+
+/// Chain of post-processing stages run on each final result
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn PostProcessor>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number normalization, pause based punctuation and sentence capitalization
+    pub fn for_language(language: Language) -> Self {
+        Self::new()
+            .with(InverseTextNormalization::new(language))
+            .with(PausePunctuation::default())
+            .with(Capitalization)
+    }
+
+    /// Append a stage, stages run in the order they were added
+    pub fn with(mut self, stage: impl PostProcessor + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Rewrite `text` and `words` of the utterance. The n-best `alternatives` stay as recognized.
+    /// An utterance without words gets only its `text` rewritten.
+    pub fn apply(&self, utterance: &mut Utterance) {
+        let timed = !utterance.words.is_empty();
+        let mut words = if !timed {
+            // no timings available, the pause based stages see no pauses
+            utterance
+                .text
+                .split_whitespace()
+                .map(|word| Word { word: word.to_string(), start: 0.0, end: 0.0, conf: 1.0 })
+                .collect()
+        } else {
+            std::mem::take(&mut utterance.words)
+        };
+        for stage in &self.stages {
+            words = stage.process(words);
+        }
+        utterance.text = words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" ");
+        if timed {
+            // made up timings are not passed off as recognized ones
+            utterance.words = words;
+        }
+    }
+}
+
+/// Capitalize the first word of every sentence
+pub struct Capitalization;
+
+impl PostProcessor for Capitalization {
+    fn process(&self, mut words: Vec<Word>) -> Vec<Word> {
+        let mut sentence_start = true;
+        for word in &mut words {
+            if sentence_start {
+                let mut chars = word.word.chars();
+                if let Some(first) = chars.next() {
+                    word.word = first.to_uppercase().chain(chars).collect();
+                }
+            }
+            sentence_start = word.word.ends_with(['.', '?', '!']);
+        }
+        words
+    }
+}
+
+/// Punctuation from the pauses between words: a comma after a short pause, a full stop after
+/// a long one and at the end of the utterance
+#[derive(Debug, Clone)]
+pub struct PausePunctuation {
+    pub comma_pause: Duration,
+    pub sentence_pause: Duration,
+}
+
+impl Default for PausePunctuation {
+    fn default() -> Self {
+        Self { comma_pause: Duration::from_millis(300), sentence_pause: Duration::from_millis(800) }
+    }
+}
+
+impl PostProcessor for PausePunctuation {
+    fn process(&self, mut words: Vec<Word>) -> Vec<Word> {
+        let gaps: Vec<f32> = words.windows(2).map(|pair| pair[1].start - pair[0].end).collect();
+        for (word, gap) in words.iter_mut().zip(gaps) {
+            if word.word.ends_with(|c: char| c.is_ascii_punctuation()) {
+                continue;
+            }
+            if gap >= self.sentence_pause.as_secs_f32() {
+                word.word.push('.');
+            } else if gap >= self.comma_pause.as_secs_f32() {
+                word.word.push(',');
+            }
+        }
+        if let Some(last) = words.last_mut() {
+            if !last.word.ends_with(['.', '?', '!']) {
+                last.word.push('.');
+            }
+        }
+        words
+    }
+}
+
+/// Inverse text normalization: numbers, dates, times and amounts spoken as words are written with digits
+#[derive(Debug, Clone)]
+pub struct InverseTextNormalization {
+    language: Language,
+}
+
+impl InverseTextNormalization {
+    pub fn new(language: Language) -> Self {
+        Self { language }
+    }
+}
+
+impl PostProcessor for InverseTextNormalization {
+    fn process(&self, words: Vec<Word>) -> Vec<Word> {
+        let rewrite = match self.language {
+            Language::English => itn_en::rewrite,
+            Language::Polish => itn_pl::rewrite,
+        };
+
+        let lowercase: Vec<String> = words.iter().map(|w| w.word.to_lowercase()).collect();
+        let tokens: Vec<&str> = lowercase.iter().map(String::as_str).collect();
+        let mut out = Vec::with_capacity(words.len());
+        let mut i = 0;
+        while i < words.len() {
+            match rewrite(&tokens[i..]) {
+                Some((consumed, text)) if consumed > 0 => {
+                    out.push(merge(&words[i..i + consumed], text));
+                    i += consumed;
+                }
+                _ => {
+                    out.push(words[i].clone());
+                    i += 1;
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Replace several words by one spanning all of them
+fn merge(words: &[Word], text: String) -> Word {
+    Word {
+        word: text,
+        start: words[0].start,
+        end: words[words.len() - 1].end,
+        conf: words.iter().map(|w| w.conf).fold(f32::INFINITY, f32::min),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str, start: f32, end: f32) -> Word {
+        Word { word: word.to_string(), start, end, conf: 1.0 }
+    }
+
+    fn normalize(language: Language, text: &str) -> String {
+        let words = text.split_whitespace().map(|w| word(w, 0.0, 0.0)).collect();
+        let words = InverseTextNormalization::new(language).process(words);
+        words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" ")
+    }
+
+    fn check(language: Language, cases: &[(&str, &str)]) {
+        for (spoken, written) in cases {
+            assert_eq!(normalize(language, spoken), *written, "{:?}", spoken);
+        }
+    }
+
+    #[test]
+    fn english_numbers() {
+        check(Language::English, &[
+            ("one of them", "one of them"),
+            ("zero", "zero"),
+            ("ten people", "10 people"),
+            ("twenty one", "21"),
+            ("one hundred and five", "105"),
+            ("nineteen hundred", "1900"),
+            ("two thousand three hundred forty five", "2345"),
+            ("three million two thousand", "3002000"),
+            ("rock and roll", "rock and roll"),
+            ("five and", "five and"),
+        ]);
+    }
+
+    #[test]
+    fn english_ordinals_name_days() {
+        check(Language::English, &[
+            ("the third time", "the third time"),
+            ("the third of may", "May 3"),
+            ("the twenty first of june nineteen ninety", "June 21, 1990"),
+            ("march third", "March 3"),
+            ("march twenty first twenty twenty four", "March 21, 2024"),
+            ("the fortieth of may", "the fortieth of may"),
+        ]);
+    }
+
+    #[test]
+    fn english_dates() {
+        check(Language::English, &[
+            ("june five", "June 5"),
+            ("june five two thousand ten", "June 5, 2010"),
+            ("may nineteen eighty four", "May 1984"),
+            ("march five twenty twenty four", "March 5, 2024"),
+            ("october nineteen oh five", "October 1905"),
+            ("january thirty two", "january 32"),
+        ]);
+    }
+
+    #[test]
+    fn english_months_that_are_words() {
+        check(Language::English, &[
+            ("you may two times", "you may two times"),
+            ("march five miles", "march five miles"),
+            ("we may twenty people", "we may 20 people"),
+            ("may i", "may i"),
+        ]);
+    }
+
+    #[test]
+    fn english_times() {
+        check(Language::English, &[
+            ("three thirty p m", "3:30 pm"),
+            ("nine oh five am", "9:05 am"),
+            ("seven a m", "7 am"),
+            ("ten o'clock", "10:00"),
+            ("three thirty", "three 30"),
+            ("thirteen pm", "13 pm"),
+        ]);
+    }
+
+    #[test]
+    fn english_amounts() {
+        check(Language::English, &[
+            ("five dollars", "$5"),
+            ("one dollar", "$1"),
+            ("twenty dollars and fifty cents", "$20.50"),
+            ("two dollars and five cents", "$2.05"),
+            ("ten euros", "€10"),
+            ("five dollars and more", "$5 and more"),
+        ]);
+    }
+
+    #[test]
+    fn polish_numbers() {
+        check(Language::Polish, &[
+            ("jeden z nich", "jeden z nich"),
+            ("dwanaście osób", "12 osób"),
+            ("dwadzieścia jeden", "21"),
+            ("sto pięć", "105"),
+            ("dwieście czterdzieści trzy", "243"),
+            ("tysiąc dwieście", "1200"),
+            ("dwa tysiące dwadzieścia", "2020"),
+            ("pięć milionów", "5000000"),
+            ("dwadzieścia jedenaście", "20 11"),
+        ]);
+    }
+
+    #[test]
+    fn polish_ordinals_name_days_and_years() {
+        check(Language::Polish, &[
+            ("trzeciego razu", "trzeciego razu"),
+            ("trzeciego marca", "3 marca"),
+            ("dwudziestego pierwszego czerwca", "21 czerwca"),
+            ("trzeciego marca dwa tysiące dwudziestego czwartego roku", "3 marca 2024 r."),
+            ("pierwszego maja tysiąc dziewięćset osiemdziesiątego czwartego", "1 maja 1984"),
+            ("dwutysięcznego roku", "2000 r."),
+            ("trzydziestego drugiego marca", "trzydziestego 2 marca"),
+        ]);
+    }
+
+    #[test]
+    fn polish_times() {
+        check(Language::Polish, &[
+            ("godzina piąta", "godzina 5:00"),
+            ("o godzinie piętnastej trzydzieści", "o godzinie 15:30"),
+            ("o godzinie ósmej zero pięć", "o godzinie 8:05"),
+            ("godzina dwudziesta pierwsza czterdzieści pięć", "godzina 21:45"),
+            ("piąta rano", "piąta rano"),
+            ("godzina jazdy", "godzina jazdy"),
+        ]);
+    }
+
+    #[test]
+    fn polish_amounts() {
+        check(Language::Polish, &[
+            ("pięć złotych", "5 zł"),
+            ("jeden złoty", "1 zł"),
+            ("dwa złote pięćdziesiąt groszy", "2,50 zł"),
+            ("dwa złote i pięć groszy", "2,05 zł"),
+            ("sto euro", "100 €"),
+            ("dwa złote i", "2 zł i"),
+        ]);
+    }
+
+    #[test]
+    fn merged_words_span_their_timings() {
+        let words = vec![word("about", 0.0, 0.3), word("twenty", 0.4, 0.6), word("one", 0.6, 0.8), word("days", 0.9, 1.2)];
+        let words = InverseTextNormalization::new(Language::English).process(words);
+        let texts: Vec<_> = words.iter().map(|w| w.word.as_str()).collect();
+        assert_eq!(texts, ["about", "21", "days"]);
+        assert_eq!((words[1].start, words[1].end), (0.4, 0.8));
+    }
+
+    #[test]
+    fn pipeline_rewrites_text_and_words() {
+        let pipeline = Pipeline::for_language(Language::English);
+        let mut utterance = Utterance {
+            text: "about twenty one days".to_string(),
+            words: vec![word("about", 0.0, 0.3), word("twenty", 0.4, 0.6), word("one", 0.6, 0.8), word("days", 0.9, 1.2)],
+            ..Default::default()
+        };
+        pipeline.apply(&mut utterance);
+        assert_eq!(utterance.text, "About 21 days.");
+        let texts: Vec<_> = utterance.words.iter().map(|w| w.word.as_str()).collect();
+        assert_eq!(texts, ["About", "21", "days."]);
+    }
+
+    #[test]
+    fn utterance_without_words_keeps_none() {
+        let pipeline = Pipeline::for_language(Language::English);
+        let mut utterance = Utterance { text: "twenty one people came".to_string(), ..Default::default() };
+        pipeline.apply(&mut utterance);
+        assert_eq!(utterance.text, "21 people came.");
+        assert!(utterance.words.is_empty());
+
+        let mut utterance = Utterance::default();
+        pipeline.apply(&mut utterance);
+        assert_eq!(utterance.text, "");
+        assert!(utterance.words.is_empty());
+    }
+}
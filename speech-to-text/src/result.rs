@@ -0,0 +1,206 @@
+use vosk::CompleteResult;
+
+use crate::XVector;
+
+/// Recognized word with its position on the audio timeline (seconds)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub word: String,
+    pub start: f32,
+    pub end: f32,
+    pub conf: f32,
+}
+
+/// One hypothesis of the n-best list
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Alternative {
+    pub text: String,
+    /// Vosk's score of the whole hypothesis, only comparable within one utterance.
+    /// Without n-best enabled this is the mean word confidence.
+    pub confidence: f32,
+    pub words: Vec<Word>,
+}
+
+/// Final result of a single utterance
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Utterance {
+    /// Text of the best alternative
+    pub text: String,
+    /// Words of the best alternative
+    pub words: Vec<Word>,
+    /// All hypotheses, best first. A single entry unless `set_max_alternatives` asked for more.
+    pub alternatives: Vec<Alternative>,
+    /// Speaker embedding, present when a speaker model is loaded and n-best is off
+    pub xvector: Option<XVector>,
+    /// Speaker label from `SpeechToText::set_diarization`, 0 is the first speaker heard
+    pub speaker: Option<usize>,
+}
+
+impl Utterance {
+    /// Convert a vosk result, shifting word times by `offset` seconds.
+    /// Returns `None` for the empty results vosk emits on silence.
+    pub(crate) fn from_vosk(result: CompleteResult, offset: f32) -> Option<Self> {
+        let mut xvector = None;
+        let mut alternatives: Vec<Alternative> = match result {
+            CompleteResult::Single(result) => {
+                xvector = result.speaker_info.map(|info| XVector { vector: info.vector, frames: info.frames });
+                let words: Vec<Word> = result
+                    .result
+                    .iter()
+                    .map(|w| Word { word: w.word.to_string(), start: w.start + offset, end: w.end + offset, conf: w.conf })
+                    .collect();
+                let confidence = if words.is_empty() {
+                    0.0
+                } else {
+                    words.iter().map(|w| w.conf).sum::<f32>() / words.len() as f32
+                };
+                vec![Alternative { text: result.text.to_string(), confidence, words }]
+            }
+            CompleteResult::Multiple(result) => result
+                .alternatives
+                .iter()
+                .map(|alternative| Alternative {
+                    text: alternative.text.to_string(),
+                    confidence: alternative.confidence,
+                    // n-best words carry no confidence of their own
+                    words: alternative
+                        .result
+                        .iter()
+                        .map(|w| Word { word: w.word.to_string(), start: w.start + offset, end: w.end + offset, conf: alternative.confidence })
+                        .collect(),
+                })
+                .collect(),
+        };
+        alternatives.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+        let best = alternatives.first()?;
+        if best.text.is_empty() {
+            return None;
+        }
+        Some(Utterance { text: best.text.clone(), words: best.words.clone(), alternatives, xvector, speaker: None })
+    }
+
+    /// Start of the first word
+    pub fn start(&self) -> Option<f32> {
+        self.words.first().map(|w| w.start)
+    }
+
+    /// End of the last word
+    pub fn end(&self) -> Option<f32> {
+        self.words.last().map(|w| w.end)
+    }
+
+    /// Speaker for display, "Speaker 1" for label 0
+    pub fn speaker_label(&self) -> Option<String> {
+        self.speaker.map(|speaker| format!("Speaker {}", speaker + 1))
+    }
+}
+
+/// All final results of a file or stream
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Transcript {
+    pub utterances: Vec<Utterance>,
+    /// Length of the transcribed audio in seconds
+    pub duration: f32,
+}
+
+impl Transcript {
+    /// Text of all utterances, one per line
+    pub fn text(&self) -> String {
+        self.utterances.iter().map(|u| u.text.as_str()).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Move every word `seconds` later (earlier if negative), e.g. from the timeline of an
+    /// excerpt to that of the whole recording
+    pub fn shift(&mut self, seconds: f32) {
+        let utterances = self.utterances.iter_mut();
+        let words = utterances.flat_map(|u| u.words.iter_mut().chain(u.alternatives.iter_mut().flat_map(|a| &mut a.words)));
+        for word in words {
+            word.start += seconds;
+            word.end += seconds;
+        }
+    }
+}
+
+/// Everything `SpeechToText` reports while audio is pushed through it
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpeechEvent {
+    /// Voice activity detector found the beginning of speech, `at` seconds into the stream
+    SpeechStarted { at: f32 },
+    /// Voice activity detector closed the utterance, `at` seconds into the stream
+    SpeechEnded { at: f32 },
+    /// Hypothesis of the utterance in progress, changes as more audio arrives
+    Partial(String),
+    /// Finalized utterance
+    Result(Utterance),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a result the way vosk prints it
+    fn utterance(json: &str, offset: f32) -> Option<Utterance> {
+        Utterance::from_vosk(serde_json::from_str(json).unwrap(), offset)
+    }
+
+    fn times(words: &[Word]) -> Vec<(&str, f32, f32)> {
+        words.iter().map(|w| (w.word.as_str(), w.start, w.end)).collect()
+    }
+
+    #[test]
+    fn single_result() {
+        let json = r#"{
+            "result": [
+                {"conf": 1.0, "start": 0.5, "end": 0.75, "word": "hello"},
+                {"conf": 0.5, "start": 0.75, "end": 1.25, "word": "world"}
+            ],
+            "spk": [0.5, -0.25],
+            "spk_frames": 40,
+            "text": "hello world"
+        }"#;
+        let utterance = utterance(json, 10.0).unwrap();
+        assert_eq!(utterance.text, "hello world");
+        // word times moved by the offset
+        assert_eq!(times(&utterance.words), [("hello", 10.5, 10.75), ("world", 10.75, 11.25)]);
+        assert_eq!(utterance.words[1].conf, 0.5);
+        // the only alternative is scored by the mean word confidence
+        assert_eq!(utterance.alternatives.len(), 1);
+        assert_eq!(utterance.alternatives[0].confidence, 0.75);
+        assert_eq!(utterance.alternatives[0].words, utterance.words);
+        assert_eq!(utterance.xvector, Some(XVector { vector: vec![0.5, -0.25], frames: 40 }));
+        assert_eq!(utterance.speaker, None);
+        assert_eq!((utterance.start(), utterance.end()), (Some(10.5), Some(11.25)));
+    }
+
+    #[test]
+    fn alternatives_best_first() {
+        let json = r#"{
+            "alternatives": [
+                {"confidence": 180.5, "result": [{"start": 0.0, "end": 0.5, "word": "wreck"}, {"start": 0.5, "end": 1.0, "word": "a"}], "text": "wreck a"},
+                {"confidence": 220.25, "result": [{"start": 0.0, "end": 1.0, "word": "recognize"}], "text": "recognize"},
+                {"confidence": 95.0, "result": [{"start": 0.0, "end": 1.0, "word": "record"}], "text": "record"}
+            ]
+        }"#;
+        let utterance = utterance(json, 2.0).unwrap();
+        let ranked: Vec<_> = utterance.alternatives.iter().map(|a| (a.text.as_str(), a.confidence)).collect();
+        assert_eq!(ranked, [("recognize", 220.25), ("wreck a", 180.5), ("record", 95.0)]);
+        // the best one is the utterance, every word scored like its alternative
+        assert_eq!(utterance.text, "recognize");
+        assert_eq!(times(&utterance.words), [("recognize", 2.0, 3.0)]);
+        assert_eq!(utterance.words[0].conf, 220.25);
+        assert_eq!(times(&utterance.alternatives[1].words), [("wreck", 2.0, 2.5), ("a", 2.5, 3.0)]);
+        assert_eq!(utterance.xvector, None);
+    }
+
+    #[test]
+    fn empty_results_are_none() {
+        assert_eq!(utterance(r#"{"text": ""}"#, 0.0), None);
+        assert_eq!(utterance(r#"{"alternatives": []}"#, 0.0), None);
+        assert_eq!(utterance(r#"{"alternatives": [{"confidence": 0.0, "text": ""}]}"#, 0.0), None);
+
+        // words without text do not make an utterance either
+        let json = r#"{"result": [{"conf": 1.0, "start": 0.0, "end": 0.5, "word": "uh"}], "text": ""}"#;
+        assert_eq!(utterance(json, 0.0), None);
+    }
+}
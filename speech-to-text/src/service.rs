@@ -0,0 +1,280 @@
+use std::io::{self, Cursor, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::{event_json, transcript_json, AudioFile, Error, Model, SpeechToText, VadConfig};
+
+/// Settings of `TranscriptionService`
+#[derive(Debug, Clone)]
+pub struct ServiceConfig {
+    /// Uploads and streams handled at the same time, further ones are answered with 503
+    pub max_sessions: usize,
+    /// Largest accepted upload in bytes
+    pub max_upload: usize,
+    /// Sample rate of streamed audio when the client passes no `sample_rate`
+    pub default_sample_rate: u32,
+    /// Voice activity detection for streams, see `SpeechToText::set_vad`
+    pub vad: Option<VadConfig>,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self { max_sessions: 4, max_upload: 200 * 1024 * 1024, default_sample_rate: 16000, vad: None }
+    }
+}
+
+/// Local transcription daemon: the model is loaded once and every session gets its own
+/// recognizer on it.
+///
+/// - `GET /health` answers `{"status": "ok", "sessions": <active>}`
+/// - `POST /transcribe` takes a WAV or FLAC file as the body and answers with the transcript
+///   as JSON (see `transcript_json`)
+/// - `GET /stream?sample_rate=16000` upgrades to a WebSocket. Binary messages carry 16-bit
+///   little endian mono samples; every event comes back as a JSON text message (see
+///   `event_json`). The text message `{"eof": true}` finalizes the last utterance, after
+///   which the server closes the connection.
+///
+/// Binding to `127.0.0.1:0` picks a free port, `local_addr` tells which.
+pub struct TranscriptionService {
+    server: Server,
+    new_session: NewSession,
+    config: ServiceConfig,
+    sessions: Arc<AtomicUsize>,
+}
+
+/// Makes the recognizer of a session for a sample rate, on the shared model unless
+/// testing
+pub(crate) type NewSession = Arc<dyn Fn(f32) -> Result<SpeechToText, Error> + Send + Sync>;
+
+impl TranscriptionService {
+    pub fn bind(addr: impl ToSocketAddrs, model: Arc<Model>, config: ServiceConfig) -> Result<Self, Error> {
+        Self::bind_with(addr, Arc::new(move |sample_rate| SpeechToText::from_model(Arc::clone(&model), sample_rate)), config)
+    }
+
+    pub(crate) fn bind_with(addr: impl ToSocketAddrs, new_session: NewSession, config: ServiceConfig) -> Result<Self, Error> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        Ok(Self { server, new_session, config, sessions: Arc::new(AtomicUsize::new(0)) })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Serve requests until `stop` is called, each session on its own thread
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            let path = request.url().split('?').next().unwrap_or_default().to_string();
+            let stream = match (request.method(), path.as_str()) {
+                (Method::Get, "/health") => {
+                    let sessions = self.sessions.load(Ordering::Relaxed);
+                    let _ = request.respond(json_response(200, &json!({ "status": "ok", "sessions": sessions })));
+                    continue;
+                }
+                (Method::Post, "/transcribe") => false,
+                (Method::Get, "/stream") => true,
+                _ => {
+                    let _ = request.respond(error_response(404, "Not found"));
+                    continue;
+                }
+            };
+
+            let Some(session) = Session::open(&self.sessions, self.config.max_sessions) else {
+                let _ = request.respond(error_response(503, "Too many sessions"));
+                continue;
+            };
+            let new_session = Arc::clone(&self.new_session);
+            let config = self.config.clone();
+            thread::spawn(move || {
+                let _session = session;
+                if stream {
+                    handle_stream(request, &new_session, &config);
+                } else {
+                    handle_upload(request, &new_session, &config);
+                }
+            });
+        }
+    }
+
+    /// Make `run` return. Sessions in progress are finished.
+    pub fn stop(&self) {
+        self.server.unblock();
+    }
+}
+
+/// Counts a session as active while alive
+struct Session(Arc<AtomicUsize>);
+
+impl Session {
+    fn open(sessions: &Arc<AtomicUsize>, max_sessions: usize) -> Option<Self> {
+        if sessions.fetch_add(1, Ordering::SeqCst) >= max_sessions {
+            sessions.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(Self(Arc::clone(sessions)))
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn handle_upload(mut request: Request, new_session: &NewSession, config: &ServiceConfig) {
+    if request.body_length().is_some_and(|len| len > config.max_upload) {
+        let _ = request.respond(error_response(413, "Upload too large"));
+        return;
+    }
+    let mut body = Vec::new();
+    if let Err(e) = request.as_reader().take(config.max_upload as u64 + 1).read_to_end(&mut body) {
+        let _ = request.respond(error_response(400, &e.to_string()));
+        return;
+    }
+    if body.len() > config.max_upload {
+        let _ = request.respond(error_response(413, "Upload too large"));
+        return;
+    }
+
+    let response = transcribe_upload(body, new_session).unwrap_or_else(|e| {
+        let status = match e {
+            Error::InvalidAudio(_) => 400,
+            _ => 500,
+        };
+        error_response(status, &e.to_string())
+    });
+    let _ = request.respond(response);
+}
+
+fn transcribe_upload(body: Vec<u8>, new_session: &NewSession) -> Result<Response<Cursor<Vec<u8>>>, Error> {
+    let mut file = AudioFile::from_reader(Cursor::new(body))?;
+    let mut stt = new_session(file.sample_rate() as f32)?;
+    let transcript = stt.transcribe(&mut file, |_| {})?;
+    Ok(json_response(200, &transcript_json(&transcript)))
+}
+
+fn handle_stream(request: Request, new_session: &NewSession, config: &ServiceConfig) {
+    let header_value =
+        |field: &'static str| request.headers().iter().find(|h| h.field.equiv(field)).map(|h| h.value.to_string());
+    let upgrade = header_value("Upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    let version = header_value("Sec-WebSocket-Version").is_some_and(|value| value.trim() == "13");
+    let (Some(key), true, true) = (header_value("Sec-WebSocket-Key"), upgrade, version) else {
+        let _ = request.respond(error_response(400, "Expected a WebSocket upgrade"));
+        return;
+    };
+    let sample_rate = match query_param(request.url(), "sample_rate").map(str::parse::<u32>) {
+        None => config.default_sample_rate,
+        Some(Ok(rate)) if rate > 0 => rate,
+        Some(_) => {
+            let _ = request.respond(error_response(400, "Invalid sample_rate"));
+            return;
+        }
+    };
+    // set up before upgrading, so failures are still plain HTTP errors
+    let mut stt = match new_session(sample_rate as f32) {
+        Ok(stt) => stt,
+        Err(e) => {
+            let _ = request.respond(error_response(500, &e.to_string()));
+            return;
+        }
+    };
+    stt.set_vad(config.vad.clone());
+
+    let accept = derive_accept_key(key.as_bytes());
+    let mut response = Response::empty(101);
+    if let Some(accept) = header("Sec-WebSocket-Accept", &accept) {
+        response.add_header(accept);
+    }
+    let stream = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    // a sample can be split between two messages
+    let mut odd_byte = None;
+    let mut samples = Vec::new();
+    loop {
+        let events = match socket.read() {
+            Ok(Message::Binary(data)) => {
+                samples.clear();
+                let mut bytes = odd_byte.take().into_iter().chain(data.iter().copied());
+                while let Some(low) = bytes.next() {
+                    match bytes.next() {
+                        Some(high) => samples.push(i16::from_le_bytes([low, high])),
+                        None => odd_byte = Some(low),
+                    }
+                }
+                stt.push_audio_mono(&samples)
+            }
+            Ok(Message::Text(text)) => {
+                let eof = serde_json::from_str::<Value>(text.as_str()).is_ok_and(|v| v.get("eof").is_some());
+                if !eof {
+                    let _ = socket.send(error_message("Expected binary audio or {\"eof\": true}"));
+                    continue;
+                }
+                match stt.finish() {
+                    Ok(events) => {
+                        for event in &events {
+                            let _ = socket.send(Message::text(event_json(event).to_string()));
+                        }
+                    }
+                    Err(e) => {
+                        let _ = socket.send(error_message(&e.to_string()));
+                    }
+                }
+                let _ = socket.close(None);
+                // wait for the client to confirm the close
+                while socket.read().is_ok() {}
+                return;
+            }
+            Ok(Message::Close(_)) | Err(_) => return,
+            // ping and pong are answered by tungstenite
+            Ok(_) => continue,
+        };
+
+        match events {
+            Ok(events) => {
+                for event in &events {
+                    if socket.send(Message::text(event_json(event).to_string())).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                let _ = socket.send(error_message(&e.to_string()));
+                let _ = socket.close(None);
+                return;
+            }
+        }
+    }
+}
+
+fn query_param<'a>(url: &'a str, name: &str) -> Option<&'a str> {
+    let (_, query) = url.split_once('?')?;
+    query.split('&').filter_map(|pair| pair.split_once('=')).find(|(key, _)| *key == name).map(|(_, value)| value)
+}
+
+fn header(field: &str, value: &str) -> Option<Header> {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).ok()
+}
+
+fn json_response(status: u16, value: &Value) -> Response<Cursor<Vec<u8>>> {
+    let mut response = Response::from_string(value.to_string()).with_status_code(status);
+    if let Some(content_type) = header("Content-Type", "application/json") {
+        response.add_header(content_type);
+    }
+    response
+}
+
+fn error_response(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    json_response(status, &json!({ "error": message }))
+}
+
+fn error_message(message: &str) -> Message {
+    Message::text(json!({ "type": "error", "message": message }).to_string())
+}
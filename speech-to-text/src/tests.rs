@@ -0,0 +1,261 @@
+//! `SpeechToText` stream handling against a scripted recognizer
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use vosk::{DecodingState, SpeakerModel};
+
+use crate::backend::Backend;
+use crate::{Error, SpeechEvent, SpeechToText, Utterance, VadConfig, Word};
+
+const SAMPLE_RATE: f32 = 16000.0;
+
+/// What the mock recognizer was asked to do
+#[derive(Default)]
+struct MockState {
+    samples: usize,
+    /// A word per loud chunk, like a recognizer that understands anything
+    words: Vec<String>,
+    resets: usize,
+    final_results: usize,
+    /// Fail every call once this many samples have been accepted
+    fail_after: Option<usize>,
+}
+
+struct MockBackend(Arc<Mutex<MockState>>);
+
+impl Backend for MockBackend {
+    fn accept_waveform(&mut self, samples: &[i16]) -> Result<DecodingState, Error> {
+        let mut state = self.0.lock().unwrap();
+        if state.fail_after.is_some_and(|limit| state.samples >= limit) {
+            return Err(Error::Backend("mock recognizer failed".to_string()));
+        }
+        state.samples += samples.len();
+        if samples.iter().any(|s| s.unsigned_abs() > 1000) {
+            let word = format!("word{}", state.words.len() + 1);
+            state.words.push(word);
+        }
+        Ok(DecodingState::Running)
+    }
+
+    fn partial_result(&mut self) -> (String, Option<f32>) {
+        let state = self.0.lock().unwrap();
+        (state.words.join(" "), (!state.words.is_empty()).then_some(0.0))
+    }
+
+    fn result(&mut self, _offset: f32) -> Option<Utterance> {
+        None
+    }
+
+    fn final_result(&mut self, offset: f32) -> Option<Utterance> {
+        let mut state = self.0.lock().unwrap();
+        state.final_results += 1;
+        if state.words.is_empty() {
+            return None;
+        }
+        let words: Vec<Word> = std::mem::take(&mut state.words)
+            .into_iter()
+            .enumerate()
+            .map(|(i, word)| Word { word, start: offset + i as f32 * 0.1, end: offset + i as f32 * 0.1 + 0.1, conf: 1.0 })
+            .collect();
+        let text = words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" ");
+        Some(Utterance { text, words, ..Default::default() })
+    }
+
+    fn reset(&mut self) {
+        let mut state = self.0.lock().unwrap();
+        state.words.clear();
+        state.resets += 1;
+    }
+
+    fn set_max_alternatives(&mut self, _max_alternatives: u16) {}
+
+    fn set_speaker_model(&mut self, _speaker_model: &SpeakerModel) {}
+}
+
+fn mock_stt() -> (SpeechToText, Arc<Mutex<MockState>>) {
+    let state = Arc::new(Mutex::new(MockState::default()));
+    let stt = SpeechToText::with_backend(None, Box::new(MockBackend(Arc::clone(&state))), SAMPLE_RATE, None);
+    (stt, state)
+}
+
+/// 200 Hz tone, comfortably above the VAD's energy threshold
+fn speech(duration: Duration) -> Vec<i16> {
+    let len = (duration.as_secs_f32() * SAMPLE_RATE) as usize;
+    (0..len).map(|i| ((i as f32 * 200.0 / SAMPLE_RATE * std::f32::consts::TAU).sin() * 8000.0) as i16).collect()
+}
+
+fn silence(duration: Duration) -> Vec<i16> {
+    vec![0; (duration.as_secs_f32() * SAMPLE_RATE) as usize]
+}
+
+fn texts(events: &[SpeechEvent]) -> Vec<&str> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            SpeechEvent::Result(utterance) => Some(utterance.text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ended(events: &[SpeechEvent]) -> bool {
+    events.iter().any(|event| matches!(event, SpeechEvent::SpeechEnded { .. }))
+}
+
+#[test]
+fn partial_opens_an_utterance() {
+    let (mut stt, _) = mock_stt();
+    assert!(!stt.is_in_utterance());
+
+    stt.push_audio_mono(&silence(Duration::from_millis(100))).unwrap();
+    assert!(!stt.is_in_utterance());
+
+    let events = stt.push_audio_mono(&speech(Duration::from_millis(100))).unwrap();
+    assert_eq!(events, vec![SpeechEvent::Partial("word1".to_string())]);
+    assert!(stt.is_in_utterance());
+    assert_eq!(stt.partial_text(), "word1");
+    assert_eq!(stt.decoding_state(), DecodingState::Running);
+}
+
+#[test]
+fn force_finalize_reports_the_utterance() {
+    let (mut stt, state) = mock_stt();
+    stt.push_audio_mono(&speech(Duration::from_millis(100))).unwrap();
+    stt.push_audio_mono(&speech(Duration::from_millis(100))).unwrap();
+
+    let events = stt.force_finalize().unwrap();
+    assert_eq!(texts(&events), ["word1 word2"]);
+    assert!(!stt.is_in_utterance());
+    assert_eq!(stt.partial_text(), "");
+    assert_eq!(stt.decoding_state(), DecodingState::Finalized);
+
+    // the stream carries on
+    stt.push_audio_mono(&speech(Duration::from_millis(100))).unwrap();
+    assert!(stt.is_in_utterance());
+    assert_eq!(texts(&stt.force_finalize().unwrap()), ["word1"]);
+    assert_eq!(state.lock().unwrap().final_results, 2);
+}
+
+#[test]
+fn reset_drops_the_utterance() {
+    let (mut stt, state) = mock_stt();
+    stt.push_audio_mono(&speech(Duration::from_millis(100))).unwrap();
+    assert!(stt.is_in_utterance());
+
+    stt.reset();
+    assert!(!stt.is_in_utterance());
+    assert_eq!(stt.partial_text(), "");
+    assert_eq!(state.lock().unwrap().resets, 1);
+    assert!(texts(&stt.force_finalize().unwrap()).is_empty());
+}
+
+#[test]
+fn reset_keeps_the_timeline() {
+    let (mut stt, _) = mock_stt();
+    stt.set_vad(Some(VadConfig::default()));
+    stt.push_audio_mono(&speech(Duration::from_millis(505))).unwrap();
+    stt.reset();
+    stt.push_audio_mono(&silence(Duration::from_millis(500))).unwrap();
+
+    let events = stt.push_audio_mono(&speech(Duration::from_millis(100))).unwrap();
+    let Some(SpeechEvent::SpeechStarted { at }) = events.first() else { panic!("expected speech, got {:?}", events) };
+    // the 5 ms dropped with the reset still count
+    assert!((at - 1.005).abs() < 0.001, "speech started at {}", at);
+}
+
+#[test]
+fn vad_speech_opens_an_utterance() {
+    let (mut stt, _) = mock_stt();
+    stt.set_vad(Some(VadConfig::default()));
+
+    let events = stt.push_audio_mono(&speech(Duration::from_millis(300))).unwrap();
+    assert!(matches!(events.first(), Some(SpeechEvent::SpeechStarted { .. })));
+    assert!(stt.is_in_utterance());
+
+    let events = stt.force_finalize().unwrap();
+    assert_eq!(texts(&events).len(), 1);
+    assert!(ended(&events));
+    assert!(!stt.is_in_utterance());
+}
+
+#[test]
+fn reset_closes_the_vad_utterance_silently() {
+    let (mut stt, _) = mock_stt();
+    stt.set_vad(Some(VadConfig::default()));
+    stt.push_audio_mono(&speech(Duration::from_millis(305))).unwrap();
+    assert!(stt.is_in_utterance());
+
+    stt.reset();
+    assert!(!stt.is_in_utterance());
+    // the VAD starts over in silence, nothing is left to end
+    let events = stt.push_audio_mono(&silence(Duration::from_secs(1))).unwrap();
+    assert!(events.is_empty());
+    assert!(stt.force_finalize().unwrap().is_empty());
+}
+
+#[test]
+fn configure_vad_shortens_the_pause_mid_utterance() {
+    let (mut stt, _) = mock_stt();
+    stt.set_vad(Some(VadConfig::default()));
+    stt.push_audio_mono(&speech(Duration::from_millis(300))).unwrap();
+
+    // 300 ms of silence is within the default 600 ms pause
+    let events = stt.push_audio_mono(&silence(Duration::from_millis(300))).unwrap();
+    assert!(!ended(&events));
+    assert!(stt.is_in_utterance());
+
+    stt.configure_vad(|config| config.max_pause = Duration::from_millis(200));
+    assert_eq!(stt.vad_config().map(|config| config.max_pause), Some(Duration::from_millis(200)));
+    // already past the new limit, the next frame ends the utterance
+    let events = stt.push_audio_mono(&silence(Duration::from_millis(10))).unwrap();
+    assert!(ended(&events));
+    assert_eq!(texts(&events).len(), 1);
+    assert!(!stt.is_in_utterance());
+}
+
+#[test]
+fn configure_vad_keeps_the_utterance_across_frame_changes() {
+    let (mut stt, _) = mock_stt();
+    stt.set_vad(Some(VadConfig::default()));
+    stt.push_audio_mono(&speech(Duration::from_millis(300))).unwrap();
+
+    stt.push_audio_mono(&silence(Duration::from_millis(300))).unwrap();
+
+    // the 300 ms of pause so far still count as 300 ms with longer frames
+    stt.configure_vad(|config| config.frame = Duration::from_millis(20));
+    assert!(stt.is_in_utterance());
+    let events = stt.push_audio_mono(&silence(Duration::from_millis(100))).unwrap();
+    assert!(!ended(&events));
+    let events = stt.push_audio_mono(&silence(Duration::from_millis(300))).unwrap();
+    assert!(ended(&events));
+}
+
+#[test]
+fn configure_vad_raises_the_silence_threshold() {
+    let (mut stt, _) = mock_stt();
+    stt.configure_vad(|config| config.energy_threshold_db = 0.0);
+    assert!(stt.vad_config().is_some());
+
+    // a tone at -12 dBFS is silence now
+    let events = stt.push_audio_mono(&speech(Duration::from_millis(500))).unwrap();
+    assert!(events.is_empty());
+    assert!(!stt.is_in_utterance());
+
+    stt.configure_vad(|config| config.energy_threshold_db = -45.0);
+    let events = stt.push_audio_mono(&speech(Duration::from_millis(100))).unwrap();
+    assert!(matches!(events.first(), Some(SpeechEvent::SpeechStarted { .. })));
+}
+
+#[test]
+fn silence_is_not_fed_with_vad() {
+    let (mut stt, state) = mock_stt();
+    stt.set_vad(Some(VadConfig::default()));
+    stt.push_audio_mono(&silence(Duration::from_secs(2))).unwrap();
+    assert_eq!(state.lock().unwrap().samples, 0);
+    assert!(!stt.is_in_utterance());
+}
+
+#[cfg(feature = "service")]
+mod service;
+mod worker;
@@ -0,0 +1,191 @@
+//! `TranscriptionService` over loopback, sessions on the scripted recognizer
+
+use std::io::{Cursor, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tungstenite::{Message, WebSocket};
+
+use super::{mock_stt, speech, SAMPLE_RATE};
+use crate::service::{NewSession, TranscriptionService};
+use crate::ServiceConfig;
+
+/// A service on a free port, stopped when dropped
+struct Running {
+    service: Arc<TranscriptionService>,
+    thread: Option<JoinHandle<()>>,
+    addr: SocketAddr,
+}
+
+impl Running {
+    fn start(config: ServiceConfig) -> Self {
+        let new_session: NewSession = Arc::new(|_sample_rate| Ok(mock_stt().0));
+        let service = Arc::new(TranscriptionService::bind_with("127.0.0.1:0", new_session, config).unwrap());
+        let addr = service.local_addr().unwrap();
+        let thread = thread::spawn({
+            let service = Arc::clone(&service);
+            move || service.run()
+        });
+        Self { service, thread: Some(thread), addr }
+    }
+
+    /// Send a raw HTTP request, answer with the status and the JSON body
+    fn request(&self, head: &str, body: &[u8]) -> (u16, Value) {
+        let mut stream = TcpStream::connect(self.addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        write!(stream, "{}\r\nHost: {}\r\nConnection: close\r\n\r\n", head, self.addr).unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let response = String::from_utf8(response).unwrap();
+        let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap_or(Value::Null))
+    }
+
+    fn upload(&self, body: &[u8]) -> (u16, Value) {
+        self.request(&format!("POST /transcribe HTTP/1.1\r\nContent-Length: {}", body.len()), body)
+    }
+
+    fn connect(&self) -> WebSocket<TcpStream> {
+        let stream = TcpStream::connect(self.addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let url = format!("ws://{}/stream?sample_rate={}", self.addr, SAMPLE_RATE);
+        tungstenite::client(url, stream).unwrap().0
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.service.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn wav(samples: &[i16]) -> Vec<u8> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE as u32,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+    for &sample in samples {
+        writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+    bytes.into_inner()
+}
+
+fn samples_message(samples: &[i16]) -> Message {
+    Message::binary(samples.iter().flat_map(|sample| sample.to_le_bytes()).collect::<Vec<u8>>())
+}
+
+#[test]
+fn health() {
+    let service = Running::start(ServiceConfig::default());
+    assert_eq!(service.request("GET /health HTTP/1.1", b""), (200, json!({ "status": "ok", "sessions": 0 })));
+}
+
+#[test]
+fn unknown_paths_are_not_found() {
+    let service = Running::start(ServiceConfig::default());
+    assert_eq!(service.request("GET /nothing HTTP/1.1", b"").0, 404);
+    assert_eq!(service.request("GET /transcribe HTTP/1.1", b"").0, 404);
+}
+
+#[test]
+fn upload_is_transcribed() {
+    let service = Running::start(ServiceConfig::default());
+    let (status, transcript) = service.upload(&wav(&speech(Duration::from_millis(500))));
+    assert_eq!(status, 200, "{}", transcript);
+    assert!(transcript["text"].as_str().unwrap().starts_with("word1"), "{}", transcript);
+}
+
+#[test]
+fn upload_of_bad_audio_is_a_bad_request() {
+    let service = Running::start(ServiceConfig::default());
+    let (status, body) = service.upload(b"this is not a sound file");
+    assert_eq!(status, 400);
+    assert!(body["error"].is_string());
+    assert_eq!(service.upload(b"RI").0, 400);
+}
+
+#[test]
+fn upload_too_large() {
+    let service = Running::start(ServiceConfig { max_upload: 64, ..Default::default() });
+    assert_eq!(service.upload(&[0; 65]).0, 413);
+    // a small one still gets through to the decoder
+    assert_eq!(service.upload(&[0; 64]).0, 400);
+}
+
+#[test]
+fn sessions_over_the_limit_are_refused() {
+    let service = Running::start(ServiceConfig { max_sessions: 1, ..Default::default() });
+    let mut socket = service.connect();
+    assert_eq!(service.request("GET /health HTTP/1.1", b"").1["sessions"], 1);
+    assert_eq!(service.upload(&wav(&speech(Duration::from_millis(100)))).0, 503);
+    socket.close(None).unwrap();
+    while socket.read().is_ok() {}
+}
+
+#[test]
+fn stream_sends_events_until_eof() {
+    let service = Running::start(ServiceConfig::default());
+    let mut socket = service.connect();
+    let samples = speech(Duration::from_millis(300));
+    // an odd split puts half a sample in each message
+    let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+    let (first, second) = bytes.split_at(bytes.len() / 2 + 1);
+    socket.send(Message::binary(first.to_vec())).unwrap();
+    socket.send(Message::binary(second.to_vec())).unwrap();
+    socket.send(samples_message(&speech(Duration::from_millis(100)))).unwrap();
+    socket.send(Message::text(r#"{"eof": true}"#)).unwrap();
+
+    let mut events = Vec::new();
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => events.push(serde_json::from_str::<Value>(text.as_str()).unwrap()),
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(e) => panic!("{}", e),
+        }
+    }
+    let result = events.iter().find(|event| event["type"] == "result").expect("no result");
+    assert!(result["text"].as_str().unwrap().starts_with("word1"), "{:?}", events);
+    assert!(events.iter().all(|event| event["type"] != "error"), "{:?}", events);
+}
+
+#[test]
+fn stream_answers_text_other_than_eof_with_an_error() {
+    let service = Running::start(ServiceConfig::default());
+    let mut socket = service.connect();
+    socket.send(Message::text("hello")).unwrap();
+    let Message::Text(text) = socket.read().unwrap() else { panic!("expected an error message") };
+    assert_eq!(serde_json::from_str::<Value>(text.as_str()).unwrap()["type"], "error");
+    socket.close(None).unwrap();
+    while socket.read().is_ok() {}
+}
+
+#[test]
+fn stream_needs_a_websocket_handshake() {
+    let service = Running::start(ServiceConfig::default());
+    let key = "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==";
+    let upgrade = "Connection: Upgrade\r\nUpgrade: websocket";
+    for head in [
+        format!("GET /stream HTTP/1.1\r\n{}\r\nSec-WebSocket-Version: 13", upgrade),
+        format!("GET /stream HTTP/1.1\r\n{}\r\nSec-WebSocket-Version: 13", key),
+        format!("GET /stream HTTP/1.1\r\n{}\r\nConnection: Upgrade\r\nUpgrade: h2c\r\nSec-WebSocket-Version: 13", key),
+        format!("GET /stream HTTP/1.1\r\n{}\r\n{}", key, upgrade),
+        format!("GET /stream HTTP/1.1\r\n{}\r\n{}\r\nSec-WebSocket-Version: 8", key, upgrade),
+        format!("GET /stream?sample_rate=0 HTTP/1.1\r\n{}\r\n{}\r\nSec-WebSocket-Version: 13", key, upgrade),
+    ] {
+        assert_eq!(service.request(&head, b"").0, 400, "{}", head);
+    }
+}
@@ -0,0 +1,70 @@
+//! `SpeechWorker` queue and thread handling on the scripted recognizer
+
+use std::time::Duration;
+
+use super::{mock_stt, speech, texts, SAMPLE_RATE};
+use crate::{Error, SpeechWorker};
+
+#[test]
+fn a_full_queue_drops_and_counts() {
+    let (stt, _) = mock_stt();
+    let (mut sender, worker) = SpeechWorker::spawn(stt, 1000);
+
+    // one push can not queue more than the ring holds, the rest is dropped
+    let audio = speech(Duration::from_millis(500));
+    let queued = sender.push(&audio);
+    assert_eq!(queued, 1000);
+    let stats = sender.stats();
+    assert_eq!(stats.pushed, 1000);
+    assert_eq!(stats.dropped, audio.len() as u64 - 1000);
+    assert_eq!(stats.overruns, 1);
+    assert_eq!(stats.max_queued, 1000);
+    assert_eq!(worker.stats(), stats);
+
+    // what was queued is still recognized
+    let (_, events) = worker.join().unwrap();
+    assert_eq!(texts(&events).len(), 1);
+    let stats = sender.stats();
+    assert_eq!((stats.pushed, stats.processed, stats.overruns), (1000, 1000, 1));
+}
+
+#[test]
+fn join_drains_the_queue() {
+    let (stt, state) = mock_stt();
+    let (mut sender, worker) = SpeechWorker::spawn(stt, 1600);
+
+    let audio = speech(Duration::from_secs(1));
+    for chunk in audio.chunks(800) {
+        assert_eq!(sender.push_blocking(chunk), chunk.len());
+    }
+    let (mut stt, events) = worker.join().unwrap();
+
+    // everything queued reached the recognizer, the open utterance is finalized
+    let stats = sender.stats();
+    assert_eq!(stats.pushed, audio.len() as u64);
+    assert_eq!(stats.processed, audio.len() as u64);
+    assert_eq!((stats.dropped, stats.overruns), (0, 0));
+    assert!(stats.max_queued <= 1600);
+    assert_eq!(state.lock().unwrap().samples, SAMPLE_RATE as usize);
+    assert_eq!(texts(&events).len(), 1);
+    assert!(texts(&events)[0].starts_with("word1"));
+    assert!(!stt.is_in_utterance());
+    assert!(stt.finish().unwrap().is_empty());
+}
+
+#[test]
+fn recognizer_errors_stop_the_worker() {
+    let (stt, state) = mock_stt();
+    state.lock().unwrap().fail_after = Some(3200);
+    let (mut sender, worker) = SpeechWorker::spawn(stt, 1600);
+
+    // the thread goes away, so the producer gives up instead of waiting forever
+    let audio = speech(Duration::from_secs(1));
+    assert!(sender.push_blocking(&audio) < audio.len());
+
+    // the utterance recognized so far is still delivered, then the channel closes
+    let events: Vec<_> = worker.events().iter().collect();
+    assert_eq!(texts(&events).len(), 1);
+    assert!(matches!(worker.join(), Err(Error::Backend(message)) if message == "mock recognizer failed"));
+    assert!(sender.stats().processed >= 3200);
+}
@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+/// Tuning of the energy / zero-crossing voice activity detector
+#[derive(Debug, Clone, PartialEq)]
+pub struct VadConfig {
+    /// Length of a single analysis frame
+    pub frame: Duration,
+    /// Frames quieter than this (dBFS) are silence
+    pub energy_threshold_db: f32,
+    /// Frames crossing zero more often than this (crossings per sample) are treated as
+    /// noise, broadband hiss sits around 0.5 while voiced speech stays well below
+    pub max_zero_crossing_rate: f32,
+    /// How long speech has to last before the utterance is considered started
+    pub min_speech: Duration,
+    /// Pause after which the utterance is ended and the recognizer forced to finalize
+    pub max_pause: Duration,
+    /// Utterances longer than this are cut and finalized
+    pub max_utterance: Duration,
+    /// Audio kept from before the speech start, so the first phoneme is not clipped
+    pub pre_roll: Duration,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            frame: Duration::from_millis(10),
+            energy_threshold_db: -45.0,
+            max_zero_crossing_rate: 0.35,
+            min_speech: Duration::from_millis(60),
+            max_pause: Duration::from_millis(600),
+            max_utterance: Duration::from_secs(15),
+            pre_roll: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Change of the detector state reported for a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VadTransition {
+    SpeechStarted,
+    /// Pause was longer than `max_pause`
+    SpeechEnded,
+    /// Utterance reached `max_utterance` while still speaking
+    UtteranceTooLong,
+}
+
+/// Frame based energy / zero-crossing voice activity detector
+pub struct Vad {
+    config: VadConfig,
+    frame_len: usize,
+    min_speech_frames: usize,
+    max_pause_frames: usize,
+    max_utterance_frames: usize,
+    pre_roll_frames: usize,
+    in_speech: bool,
+    speech_run: usize,
+    silence_run: usize,
+    utterance_frames: usize,
+}
+
+impl Vad {
+    pub fn new(config: VadConfig, sample_rate: f32) -> Self {
+        let frame_len = ((config.frame.as_secs_f32() * sample_rate) as usize).max(1);
+        let frames = |d: Duration| (d.as_secs_f32() / config.frame.as_secs_f32()).ceil() as usize;
+        Self {
+            frame_len,
+            min_speech_frames: frames(config.min_speech).max(1),
+            max_pause_frames: frames(config.max_pause).max(1),
+            max_utterance_frames: frames(config.max_utterance).max(1),
+            pre_roll_frames: frames(config.pre_roll),
+            config,
+            in_speech: false,
+            speech_run: 0,
+            silence_run: 0,
+            utterance_frames: 0,
+        }
+    }
+
+    pub fn config(&self) -> &VadConfig {
+        &self.config
+    }
+
+    /// Switch to `config` without losing track of the utterance in progress. Shorter limits
+    /// take effect with the next frame, e.g. a `max_pause` already exceeded ends the utterance.
+    pub fn set_config(&mut self, config: VadConfig, sample_rate: f32) {
+        let old_frame_len = self.frame_len;
+        let (in_speech, speech_run, silence_run, utterance_frames) =
+            (self.in_speech, self.speech_run, self.silence_run, self.utterance_frames);
+        *self = Self::new(config, sample_rate);
+        // runs are counted in frames, which may have changed length
+        let rescale = |frames: usize| frames * old_frame_len / self.frame_len;
+        self.in_speech = in_speech;
+        self.speech_run = rescale(speech_run);
+        self.silence_run = rescale(silence_run);
+        self.utterance_frames = rescale(utterance_frames);
+    }
+
+    /// Number of samples `process_frame` expects
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    /// Samples to keep while in silence: the pre-roll plus the frames needed to confirm speech
+    pub fn pre_roll_len(&self) -> usize {
+        (self.pre_roll_frames + self.min_speech_frames) * self.frame_len
+    }
+
+    /// Samples since the start of the current utterance
+    pub fn utterance_len(&self) -> usize {
+        self.utterance_frames * self.frame_len
+    }
+
+    /// True between `SpeechStarted` and `SpeechEnded`
+    pub fn in_speech(&self) -> bool {
+        self.in_speech
+    }
+
+    /// Forget the current utterance and go back to silence
+    pub fn reset(&mut self) {
+        self.in_speech = false;
+        self.speech_run = 0;
+        self.silence_run = 0;
+        self.utterance_frames = 0;
+    }
+
+    /// Classify one frame and advance the state machine
+    pub fn process_frame(&mut self, frame: &[i16]) -> Option<VadTransition> {
+        let voiced = self.is_speech(frame);
+
+        if !self.in_speech {
+            self.speech_run = if voiced { self.speech_run + 1 } else { 0 };
+            if self.speech_run >= self.min_speech_frames {
+                self.in_speech = true;
+                self.silence_run = 0;
+                self.utterance_frames = self.speech_run;
+                return Some(VadTransition::SpeechStarted);
+            }
+            return None;
+        }
+
+        self.utterance_frames += 1;
+        self.silence_run = if voiced { 0 } else { self.silence_run + 1 };
+
+        if self.silence_run >= self.max_pause_frames {
+            self.reset();
+            return Some(VadTransition::SpeechEnded);
+        }
+        if self.utterance_frames >= self.max_utterance_frames {
+            self.reset();
+            return Some(VadTransition::UtteranceTooLong);
+        }
+        None
+    }
+
+    fn is_speech(&self, frame: &[i16]) -> bool {
+        if frame.is_empty() {
+            return false;
+        }
+        energy_db(frame) >= self.config.energy_threshold_db
+            && zero_crossing_rate(frame) <= self.config.max_zero_crossing_rate
+    }
+}
+
+/// RMS level of the frame in dBFS
+pub fn energy_db(frame: &[i16]) -> f32 {
+    let sum: f64 = frame.iter().map(|&s| (s as f64).powi(2)).sum();
+    let rms = (sum / frame.len() as f64).sqrt() / i16::MAX as f64;
+    20.0 * rms.max(1e-10).log10() as f32
+}
+
+/// Fraction of neighbouring sample pairs that change sign
+pub fn zero_crossing_rate(frame: &[i16]) -> f32 {
+    if frame.len() < 2 {
+        return 0.0;
+    }
+    let crossings = frame
+        .windows(2)
+        .filter(|w| (w[0] >= 0) != (w[1] >= 0))
+        .count();
+    crossings as f32 / (frame.len() - 1) as f32
+}
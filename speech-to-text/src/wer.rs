@@ -0,0 +1,227 @@
+/// One step of the alignment between reference and hypothesis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlignOp {
+    Match(String),
+    Substitution { reference: String, hypothesis: String },
+    /// Token in the hypothesis that is not in the reference
+    Insertion(String),
+    /// Reference token missing from the hypothesis
+    Deletion(String),
+}
+
+/// Error counts of one comparison, can be summed up over a test set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ErrorCounts {
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// Number of tokens in the reference
+    pub reference_len: usize,
+}
+
+impl ErrorCounts {
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.insertions + self.deletions
+    }
+
+    /// Errors per reference token, can exceed 1.0 with many insertions
+    pub fn rate(&self) -> f64 {
+        if self.reference_len == 0 {
+            return if self.errors() == 0 { 0.0 } else { 1.0 };
+        }
+        self.errors() as f64 / self.reference_len as f64
+    }
+}
+
+impl std::ops::AddAssign for ErrorCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.substitutions += other.substitutions;
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+        self.reference_len += other.reference_len;
+    }
+}
+
+/// Result of aligning a hypothesis against its reference
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Alignment {
+    pub ops: Vec<AlignOp>,
+    pub counts: ErrorCounts,
+}
+
+impl Alignment {
+    /// Reference, hypothesis and error markers (S, I, D) as three column aligned lines
+    pub fn pretty(&self) -> [String; 3] {
+        let mut lines = [String::new(), String::new(), String::new()];
+        for op in &self.ops {
+            let (reference, hypothesis, marker) = match op {
+                AlignOp::Match(token) => (token.as_str(), token.as_str(), ""),
+                AlignOp::Substitution { reference, hypothesis } => (reference.as_str(), hypothesis.as_str(), "S"),
+                AlignOp::Insertion(token) => ("***", token.as_str(), "I"),
+                AlignOp::Deletion(token) => (token.as_str(), "***", "D"),
+            };
+            let width = reference.chars().count().max(hypothesis.chars().count());
+            for (line, text) in lines.iter_mut().zip([reference, hypothesis, marker]) {
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(&format!("{:<width$}", text, width = width));
+            }
+        }
+        lines.map(|line| line.trim_end().to_string())
+    }
+}
+
+/// Lowercase and strip punctuation so only the words are compared
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_alphanumeric() || c.is_whitespace() || c == '\'' { c } else { ' ' })
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Word level alignment, the basis of the word error rate
+pub fn align_words(reference: &str, hypothesis: &str) -> Alignment {
+    let reference = normalize(reference);
+    let hypothesis = normalize(hypothesis);
+    let reference: Vec<&str> = reference.split_whitespace().collect();
+    let hypothesis: Vec<&str> = hypothesis.split_whitespace().collect();
+    align(&reference, &hypothesis)
+}
+
+/// Character level alignment, the basis of the character error rate. Spaces are not counted.
+pub fn align_chars(reference: &str, hypothesis: &str) -> Alignment {
+    let chars = |text: &str| -> Vec<String> {
+        normalize(text).chars().filter(|c| !c.is_whitespace()).map(String::from).collect()
+    };
+    let reference = chars(reference);
+    let hypothesis = chars(hypothesis);
+    let reference: Vec<&str> = reference.iter().map(String::as_str).collect();
+    let hypothesis: Vec<&str> = hypothesis.iter().map(String::as_str).collect();
+    align(&reference, &hypothesis)
+}
+
+/// Levenshtein alignment with backtrace, preferring matches and substitutions on ties
+fn align(reference: &[&str], hypothesis: &[&str]) -> Alignment {
+    let (n, m) = (reference.len(), hypothesis.len());
+    // cost[i][j]: edit distance between reference[..i] and hypothesis[..j]
+    let mut cost = vec![vec![0usize; m + 1]; n + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in cost[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let diagonal = cost[i - 1][j - 1] + usize::from(reference[i - 1] != hypothesis[j - 1]);
+            cost[i][j] = diagonal.min(cost[i - 1][j] + 1).min(cost[i][j - 1] + 1);
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n.max(m));
+    let mut counts = ErrorCounts { reference_len: n, ..Default::default() };
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            let same = reference[i - 1] == hypothesis[j - 1];
+            if cost[i][j] == cost[i - 1][j - 1] + usize::from(!same) {
+                if same {
+                    ops.push(AlignOp::Match(reference[i - 1].to_string()));
+                } else {
+                    counts.substitutions += 1;
+                    ops.push(AlignOp::Substitution {
+                        reference: reference[i - 1].to_string(),
+                        hypothesis: hypothesis[j - 1].to_string(),
+                    });
+                }
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+        if i > 0 && cost[i][j] == cost[i - 1][j] + 1 {
+            counts.deletions += 1;
+            ops.push(AlignOp::Deletion(reference[i - 1].to_string()));
+            i -= 1;
+        } else {
+            counts.insertions += 1;
+            ops.push(AlignOp::Insertion(hypothesis[j - 1].to_string()));
+            j -= 1;
+        }
+    }
+    ops.reverse();
+    Alignment { ops, counts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(token: &str) -> String {
+        token.to_string()
+    }
+
+    #[test]
+    fn substitution() {
+        let alignment = align_words("the cat sat", "the hat sat");
+        assert_eq!(alignment.ops, [
+            AlignOp::Match(word("the")),
+            AlignOp::Substitution { reference: word("cat"), hypothesis: word("hat") },
+            AlignOp::Match(word("sat")),
+        ]);
+        assert_eq!(alignment.counts, ErrorCounts { substitutions: 1, insertions: 0, deletions: 0, reference_len: 3 });
+        assert!((alignment.counts.rate() - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn insertion() {
+        let alignment = align_words("the cat sat", "the black cat sat");
+        assert_eq!(alignment.ops[1], AlignOp::Insertion(word("black")));
+        assert_eq!(alignment.counts, ErrorCounts { substitutions: 0, insertions: 1, deletions: 0, reference_len: 3 });
+        assert_eq!(alignment.pretty(), ["the ***   cat sat", "the black cat sat", "    I"]);
+    }
+
+    #[test]
+    fn deletion() {
+        let alignment = align_words("The cat, sat.", "the sat");
+        assert_eq!(alignment.ops, [AlignOp::Match(word("the")), AlignOp::Deletion(word("cat")), AlignOp::Match(word("sat"))]);
+        assert_eq!(alignment.counts, ErrorCounts { substitutions: 0, insertions: 0, deletions: 1, reference_len: 3 });
+    }
+
+    #[test]
+    fn empty_reference() {
+        let alignment = align_words("", "hello there");
+        assert_eq!(alignment.counts, ErrorCounts { substitutions: 0, insertions: 2, deletions: 0, reference_len: 0 });
+        assert_eq!(alignment.counts.rate(), 1.0);
+        assert_eq!(align_words("", "").counts.rate(), 0.0);
+    }
+
+    #[test]
+    fn empty_hypothesis() {
+        let alignment = align_words("hello there", "");
+        assert_eq!(alignment.ops, [AlignOp::Deletion(word("hello")), AlignOp::Deletion(word("there"))]);
+        assert_eq!(alignment.counts.rate(), 1.0);
+    }
+
+    #[test]
+    fn characters_are_compared_by_char_not_byte() {
+        // "ł" and "l" differ in one character, though "ł" takes two bytes; "d" matches
+        let alignment = align_chars("Łódź", "lodz");
+        assert_eq!(alignment.counts, ErrorCounts { substitutions: 3, insertions: 0, deletions: 0, reference_len: 4 });
+        let alignment = align_chars("zażółć gęślą", "zazółć gęśla");
+        assert_eq!(alignment.counts, ErrorCounts { substitutions: 2, insertions: 0, deletions: 0, reference_len: 11 });
+        assert_eq!(alignment.ops[2], AlignOp::Substitution { reference: word("ż"), hypothesis: word("z") });
+    }
+
+    #[test]
+    fn counts_add_up() {
+        let mut total = align_words("a b c", "a x c").counts;
+        total += align_words("d e", "d e f").counts;
+        assert_eq!(total, ErrorCounts { substitutions: 1, insertions: 1, deletions: 0, reference_len: 5 });
+        assert!((total.rate() - 0.4).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,226 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::{Error, SpeechEvent, SpeechToText};
+
+/// How long the worker sleeps when there is no audio queued
+const IDLE_WAIT: Duration = Duration::from_millis(5);
+/// Largest block handed to the recognizer in one call
+const MAX_CHUNK: usize = 4800;
+
+#[derive(Default)]
+struct Counters {
+    pushed: AtomicU64,
+    dropped: AtomicU64,
+    overruns: AtomicU64,
+    processed: AtomicU64,
+    max_queued: AtomicUsize,
+}
+
+/// Snapshot of the queue statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WorkerStats {
+    /// Samples accepted into the queue
+    pub pushed: u64,
+    /// Samples thrown away because the queue was full
+    pub dropped: u64,
+    /// Pushes that did not fit into the queue completely
+    pub overruns: u64,
+    /// Samples already passed to the recognizer
+    pub processed: u64,
+    /// Highest number of samples waiting in the queue so far
+    pub max_queued: usize,
+}
+
+impl Counters {
+    fn snapshot(&self) -> WorkerStats {
+        WorkerStats {
+            pushed: self.pushed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            processed: self.processed.load(Ordering::Relaxed),
+            max_queued: self.max_queued.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Producing half of the worker queue, meant to live in the audio callback.
+/// It never blocks, locks or allocates in `push`.
+pub struct AudioSender {
+    producer: Producer<i16>,
+    counters: Arc<Counters>,
+}
+
+impl AudioSender {
+    /// Queue mono samples. Whatever does not fit is dropped and counted as an overrun.
+    /// Returns the number of samples queued.
+    pub fn push(&mut self, samples: &[i16]) -> usize {
+        let written = self.write(samples);
+        if written < samples.len() {
+            self.counters.dropped.fetch_add((samples.len() - written) as u64, Ordering::Relaxed);
+            self.counters.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        written
+    }
+
+    /// Queue all mono samples, waiting for the worker whenever the queue is full.
+    /// For producers that can be slowed down, like file readers.
+    /// Returns less than `samples.len()` only if the worker has stopped.
+    pub fn push_blocking(&mut self, samples: &[i16]) -> usize {
+        let mut queued = 0;
+        while queued < samples.len() {
+            let written = self.write(&samples[queued..]);
+            queued += written;
+            if written == 0 {
+                if self.producer.is_abandoned() {
+                    break;
+                }
+                thread::sleep(IDLE_WAIT);
+            }
+        }
+        queued
+    }
+
+    pub fn stats(&self) -> WorkerStats {
+        self.counters.snapshot()
+    }
+
+    fn write(&mut self, samples: &[i16]) -> usize {
+        let n = samples.len().min(self.producer.slots());
+        if n == 0 {
+            return 0;
+        }
+        let Ok(chunk) = self.producer.write_chunk_uninit(n) else {
+            return 0;
+        };
+        let written = chunk.fill_from_iter(samples.iter().copied());
+        let queued = self.producer.buffer().capacity() - self.producer.slots();
+        self.counters.pushed.fetch_add(written as u64, Ordering::Relaxed);
+        self.counters.max_queued.fetch_max(queued, Ordering::Relaxed);
+        written
+    }
+}
+
+/// `SpeechToText` running on its own thread.
+///
+/// Audio comes in through the `AudioSender` returned by `spawn`, results go out through `events()`.
+pub struct SpeechWorker {
+    events: Receiver<SpeechEvent>,
+    counters: Arc<Counters>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Result<SpeechToText, Error>>>,
+}
+
+impl SpeechWorker {
+    /// Move the recognizer to a worker thread. `capacity` is the queue length in samples.
+    pub fn spawn(stt: SpeechToText, capacity: usize) -> (AudioSender, SpeechWorker) {
+        let (producer, consumer) = RingBuffer::new(capacity);
+        let (sender, events) = mpsc::channel();
+        let counters = Arc::new(Counters::default());
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let counters = counters.clone();
+            let stop = stop.clone();
+            thread::spawn(move || run(stt, consumer, sender, &counters, &stop))
+        };
+
+        let audio_sender = AudioSender { producer, counters: counters.clone() };
+        let worker = SpeechWorker { events, counters, stop, handle: Some(handle) };
+        (audio_sender, worker)
+    }
+
+    /// Recognition results, in the order they were produced
+    pub fn events(&self) -> &Receiver<SpeechEvent> {
+        &self.events
+    }
+
+    pub fn stats(&self) -> WorkerStats {
+        self.counters.snapshot()
+    }
+
+    /// Process whatever is still queued, finalize the last utterance and stop the thread.
+    /// Returns the recognizer together with the events nobody has received yet.
+    /// If the recognizer failed, the thread has already finalized the utterance it was in;
+    /// its events stay on `events()` and the error is returned here.
+    pub fn join(mut self) -> Result<(SpeechToText, Vec<SpeechEvent>), Error> {
+        let stt = match self.stop_thread() {
+            Some(result) => result?,
+            // only `join` and `drop` stop the thread, and both consume the worker
+            None => return Err(Error::Backend("worker thread already stopped".to_string())),
+        };
+        let events = self.events.try_iter().collect();
+        Ok((stt, events))
+    }
+
+    fn stop_thread(&mut self) -> Option<Result<SpeechToText, Error>> {
+        self.stop.store(true, Ordering::Relaxed);
+        let handle = self.handle.take()?;
+        Some(handle.join().unwrap_or_else(|_| Err(Error::Backend("worker thread panicked".to_string()))))
+    }
+}
+
+impl Drop for SpeechWorker {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            let _ = self.stop_thread();
+        }
+    }
+}
+
+fn run(
+    mut stt: SpeechToText,
+    mut consumer: Consumer<i16>,
+    sender: Sender<SpeechEvent>,
+    counters: &Counters,
+    stop: &AtomicBool,
+) -> Result<SpeechToText, Error> {
+    let mut block = Vec::with_capacity(MAX_CHUNK);
+    loop {
+        let available = consumer.slots().min(MAX_CHUNK);
+        if available == 0 {
+            if stop.load(Ordering::Relaxed) || consumer.is_abandoned() {
+                break;
+            }
+            thread::sleep(IDLE_WAIT);
+            continue;
+        }
+
+        let Ok(chunk) = consumer.read_chunk(available) else {
+            continue;
+        };
+        let (first, second) = chunk.as_slices();
+        block.clear();
+        block.extend_from_slice(first);
+        block.extend_from_slice(second);
+        chunk.commit_all();
+
+        let events = match stt.push_audio_mono(&block) {
+            Ok(events) => events,
+            Err(error) => {
+                // still deliver the utterance in progress, then report why recognition stopped
+                if let Ok(events) = stt.finish() {
+                    for event in events {
+                        let _ = sender.send(event);
+                    }
+                }
+                return Err(error);
+            }
+        };
+        counters.processed.fetch_add(block.len() as u64, Ordering::Relaxed);
+        for event in events {
+            // nobody listening is not a reason to stop recognizing
+            let _ = sender.send(event);
+        }
+    }
+
+    for event in stt.finish()? {
+        let _ = sender.send(event);
+    }
+    Ok(stt)
+}
@@ -1,166 +1,207 @@
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use error_iter::ErrorIter as _;
 use log::error;
 use pixels::{Pixels, SurfaceTexture};
+use raw_video::{
+    Action, Clock, Converter, Options, Presenter, Seek, SystemClock, TimedFrame, Transport, VideoFormat, VideoReader,
+};
+#[cfg(feature = "audio")]
+use raw_video::{AudioPlayer, AudioTrack};
 use winit::{
     application::ApplicationHandler,
     error::EventLoopError,
-    event::WindowEvent,
+    event::{ElementState, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowAttributes},
 };
 
-// Video configuration constants
-const VIDEO_WIDTH: u32 = 1920;
-const VIDEO_HEIGHT: u32 = 1080;
-const FPS: u32 = 25;
-const FRAME_DURATION: Duration = Duration::from_millis(1000 / FPS as u64);
-
-// YUV420p frame size calculations
-const Y_SIZE: usize = (VIDEO_WIDTH * VIDEO_HEIGHT) as usize;
-const U_SIZE: usize = ((VIDEO_WIDTH / 2) * (VIDEO_HEIGHT / 2)) as usize;
-const V_SIZE: usize = ((VIDEO_WIDTH / 2) * (VIDEO_HEIGHT / 2)) as usize;
-const FRAME_SIZE: usize = Y_SIZE + U_SIZE + V_SIZE;
-
-// Video frame data
-struct VideoFrame {
-    rgba_data: Vec<u8>, // RGBA data for pixels (4 bytes per pixel)
-    frame_number: usize,
-}
-
-// Convert YUV420p to RGBA for pixels format
-fn yuv420p_to_rgba(y_data: &[u8], u_data: &[u8], v_data: &[u8]) -> Vec<u8> {
-    let mut rgba = Vec::with_capacity((VIDEO_WIDTH * VIDEO_HEIGHT * 4) as usize);
-
-    for y in 0..VIDEO_HEIGHT as usize {
-        for x in 0..VIDEO_WIDTH as usize {
-            let y_idx = y * VIDEO_WIDTH as usize + x;
-            let u_idx = (y / 2) * (VIDEO_WIDTH as usize / 2) + (x / 2);
-            let v_idx = (y / 2) * (VIDEO_WIDTH as usize / 2) + (x / 2);
-
-            let y_val = y_data[y_idx] as i32;
-            let u_val = u_data[u_idx] as i32 - 128;
-            let v_val = v_data[v_idx] as i32 - 128;
-
-            // YUV to RGB conversion (BT.601 standard)
-            let r = (y_val as f32 + 1.402 * v_val as f32).clamp(0.0, 255.0) as u8;
-            let g = (y_val as f32 - 0.344 * u_val as f32 - 0.714 * v_val as f32).clamp(0.0, 255.0) as u8;
-            let b = (y_val as f32 + 1.772 * u_val as f32).clamp(0.0, 255.0) as u8;
-
-            // Pixels expects RGBA format (4 bytes per pixel)
-            rgba.push(r);
-            rgba.push(g);
-            rgba.push(b);
-            rgba.push(255); // Alpha channel
-        }
-    }
-
-    rgba
-}
+// Video frame data, RGBA for pixels (4 bytes per pixel)
+type VideoFrame = TimedFrame<Vec<u8>>;
 
 // Function to run in separate thread for reading video frames
 fn video_reader_thread(
     frame_sender: crossbeam_channel::Sender<VideoFrame>,
+    seek_receiver: crossbeam_channel::Receiver<Seek>,
     stop_signal: Arc<Mutex<bool>>,
+    mut reader: VideoReader,
+    clock: Arc<dyn Clock>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::{Read, Seek, SeekFrom};
-
-    let mut file = File::open("../out.yuv")?;
-    let mut frame_buffer = vec![0u8; FRAME_SIZE];
-    let mut frame_number = 0;
-
-    println!("Starting video playback: {}x{} @ {}fps", VIDEO_WIDTH, VIDEO_HEIGHT, FPS);
-
-    loop {
+    let format = reader.format();
+    // conversion is split into bands of rows, one per core
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let converter = Converter::new(&format, &reader.color()).with_threads(threads);
+    let frame_duration = format.frame_rate.frame_duration();
+    let mut frame_buffer = vec![0u8; format.frame_size()];
+    // Added to the timestamps when looping so the clock never has to jump back; a
+    // seek starts the timeline over
+    let mut loop_offset = Duration::ZERO;
+    // Frames read after a seek carry its epoch
+    let mut epoch = 0;
+    let mut started = false;
+    let mut skipped = 0;
+
+    'frames: loop {
         // Check if we should stop
         if *stop_signal.lock().unwrap() {
             break;
         }
 
-        let begin = Instant::now();
+        // Go where the last key pressed asks for
+        if let Some(seek) = seek_receiver.try_iter().last() {
+            let frame_number = reader.seek(seek.frame)?;
+            let timestamp = format.frame_rate.timestamp(frame_number);
+            println!("Seek to frame {} at {:.3}s", frame_number, timestamp.as_secs_f64());
+            clock.seek(timestamp);
+            loop_offset = Duration::ZERO;
+            epoch = seek.epoch;
+        }
 
         // Read one frame
-        let bytes_read = file.read(&mut frame_buffer)?;
-        if bytes_read != FRAME_SIZE {
+        let frame_number = reader.position();
+        if !reader.read_frame(&mut frame_buffer)? {
             println!("End of video file reached after {} frames, looping back to start", frame_number);
-            // Seek back to the beginning of the file
-            file.seek(SeekFrom::Start(0))?;
-            frame_number = 0;
+            // Seek back to the first frame
+            loop_offset += format.frame_rate.timestamp(frame_number);
+            reader.rewind()?;
             continue;
         }
 
-        // Extract Y, U, V planes
-        let y_data = &frame_buffer[0..Y_SIZE];
-        let u_data = &frame_buffer[Y_SIZE..Y_SIZE + U_SIZE];
-        let v_data = &frame_buffer[Y_SIZE + U_SIZE..];
+        let timestamp = loop_offset + format.frame_rate.timestamp(frame_number);
 
-        // Convert to RGBA
-        let rgba_data = yuv420p_to_rgba(y_data, u_data, v_data);
-
-        // Create video frame
-        let frame = VideoFrame {
-            rgba_data,
-            frame_number,
-        };
+        // Too late to be shown at all, don't spend a conversion on it
+        if started && timestamp + frame_duration < clock.position() {
+            skipped += 1;
+            continue;
+        }
 
-        // Send frame to main thread (non-blocking)
-        // if frame_sender.try_send(frame).is_err() {
-        //     // Main thread is not keeping up, skip this frame
-        //     println!("Warning: Frame {} dropped - main thread not keeping up", frame_number);
-        // }
-        frame_sender.send(frame).unwrap();
+        // Convert to RGB
+        let mut rgba_data = vec![0u8; format.width * format.height * 4];
+        converter.to_rgba(&frame_buffer, &mut rgba_data);
 
-        frame_number += 1;
+        // Create video frame
+        let mut frame = Some(VideoFrame {
+            index: frame_number,
+            timestamp,
+            epoch,
+            frame: rgba_data,
+        });
+
+        // Send frame to main thread, the channel paces reading to the display
+        while let Some(pending) = frame.take() {
+            match frame_sender.send_timeout(pending, Duration::from_millis(20)) {
+                Ok(()) => {}
+                Err(crossbeam_channel::SendTimeoutError::Timeout(pending)) => {
+                    if *stop_signal.lock().unwrap() {
+                        return Ok(());
+                    }
+                    // A seek makes the frame stale, it won't be shown
+                    if !seek_receiver.is_empty() {
+                        continue 'frames;
+                    }
+                    frame = Some(pending);
+                }
+                Err(crossbeam_channel::SendTimeoutError::Disconnected(_)) => return Ok(()),
+            }
+        }
 
-        let end = Instant::now();
-        let diff = end - begin;
-        if diff < FRAME_DURATION {
-            // Sleep for frame duration
-            thread::sleep(FRAME_DURATION - diff);
+        // The clock starts once there is something to show
+        if !started {
+            clock.set_paused(false);
+            started = true;
         }
     }
 
-    println!("Video reader thread finished");
+    println!("Video reader thread finished, {} late frames skipped", skipped);
     Ok(())
 }
 
 pub struct App {
+    format: VideoFormat,
     window: Option<Arc<Window>>,
     pixels: Option<Pixels>,
     frame_receiver: Option<crossbeam_channel::Receiver<VideoFrame>>,
-    current_frame: Option<VideoFrame>,
+    presenter: Presenter<Vec<u8>>,
+    clock: Arc<dyn Clock>,
+    transport: Transport,
+    seek_sender: crossbeam_channel::Sender<Seek>,
+    // Keeps the audio stream playing
+    #[cfg(feature = "audio")]
+    audio: Option<AudioPlayer>,
     stop_signal: Arc<Mutex<bool>>,
     video_thread_handle: Option<thread::JoinHandle<()>>,
 }
 
 fn main() -> Result<(), EventLoopError> {
+    let args: Vec<String> = std::env::args().collect();
+    let options = Options::parse(args[1..].iter().cloned(), &args[0], "../out.yuv").unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    });
+    // a .y4m header overrides the geometry given on the command line
+    let reader = VideoReader::open(&options).unwrap_or_else(|e| {
+        eprintln!("Cannot open {}: {}", options.path.display(), e);
+        std::process::exit(1);
+    });
+    let format = reader.format();
+    match reader.y4m_header() {
+        Some(header) => println!("Starting video playback: {} from {}", header, options.path.display()),
+        None => println!("Starting video playback: {} from {}", format, options.path.display()),
+    }
+    if !format.pixel_format.is_rgb() {
+        println!("Colors: {}", reader.color());
+    }
+
+    // With an audio track its device is the clock the frames are presented against
+    #[cfg(feature = "audio")]
+    let audio = start_audio(&options);
+    #[cfg(feature = "audio")]
+    let clock: Arc<dyn Clock> = match &audio {
+        Some(player) => player.clock(),
+        None => Arc::new(SystemClock::new()),
+    };
+    #[cfg(not(feature = "audio"))]
+    let clock: Arc<dyn Clock> = {
+        if options.audio.is_some() {
+            eprintln!("Built without audio, playing video only; build with --features audio to play it");
+        }
+        Arc::new(SystemClock::new())
+    };
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Wait);
 
     // Channel for sending video frames from reader thread to main thread
     let (frame_sender, frame_receiver) = crossbeam_channel::bounded(10); // Buffer 10 frames
+    // Channel for the seeks asked for from the keyboard
+    let (seek_sender, seek_receiver) = crossbeam_channel::unbounded();
 
     // Stop signal for the video reader thread
     let stop_signal = Arc::new(Mutex::new(false));
     let stop_signal_clone = stop_signal.clone();
+    let clock_clone = clock.clone();
 
     // Start video reader thread
     let video_thread = thread::spawn(move || {
-        if let Err(e) = video_reader_thread(frame_sender, stop_signal_clone) {
+        if let Err(e) = video_reader_thread(frame_sender, seek_receiver, stop_signal_clone, reader, clock_clone) {
             eprintln!("Error in video reader thread: {}", e);
         }
     });
 
     let mut app = App {
+        format,
         window: None,
         pixels: None,
         frame_receiver: Some(frame_receiver),
-        current_frame: None,
+        presenter: Presenter::new(),
+        transport: Transport::new(clock.clone(), format.frame_rate, options.seek_step),
+        seek_sender,
+        clock,
+        #[cfg(feature = "audio")]
+        audio,
         stop_signal,
         video_thread_handle: Some(video_thread),
     };
@@ -173,7 +214,7 @@ impl ApplicationHandler for App {
         let window = event_loop
             .create_window(
                 WindowAttributes::default()
-                    .with_inner_size(winit::dpi::LogicalSize::new(VIDEO_WIDTH, VIDEO_HEIGHT))
+                    .with_inner_size(winit::dpi::LogicalSize::new(self.format.width as u32, self.format.height as u32))
             )
             .unwrap();
         let window = Arc::new(window);
@@ -182,7 +223,7 @@ impl ApplicationHandler for App {
         self.pixels = {
             let (window_width, window_height) = window.inner_size().into();
             let surface_texture = SurfaceTexture::new(window_width, window_height, &window);
-            match Pixels::new(VIDEO_WIDTH, VIDEO_HEIGHT, surface_texture) {
+            match Pixels::new(self.format.width as u32, self.format.height as u32, surface_texture) {
                 Ok(pixels) => {
                     window.request_redraw();
                     Some(pixels)
@@ -217,18 +258,25 @@ impl ApplicationHandler for App {
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
-                // Try to receive a new frame
-                if let Some(receiver) = &self.frame_receiver {
-                    self.current_frame = Some(receiver.recv().unwrap());
+                #[cfg(feature = "audio")]
+                if let Some(error) = self.audio.as_ref().and_then(AudioPlayer::take_error) {
+                    eprintln!("Audio error: {}", error);
                 }
 
+                // Take the newest frame that is due, keep showing the current one until then
+                let position = self.clock.position();
+                let changed = match &self.frame_receiver {
+                    Some(receiver) => self.presenter.update(position, || receiver.try_recv().ok()),
+                    None => false,
+                };
+
                 if let Some(pixels) = &mut self.pixels {
                     let frame = pixels.frame_mut();
 
-                    // Check if we have a frame to display
-                    if let Some(video_frame) = &self.current_frame {
+                    // Check if we have a new frame to display
+                    if let (true, Some(video_frame)) = (changed, self.presenter.current()) {
                         // Copy video frame data to pixels buffer
-                        let frame_data = &video_frame.rgba_data;
+                        let frame_data = &video_frame.frame;
                         let frame_len = frame_data.len();
                         let buffer_len = frame.len();
 
@@ -237,8 +285,13 @@ impl ApplicationHandler for App {
                         frame[..copy_len].copy_from_slice(&frame_data[..copy_len]);
 
                         // Display frame info
-                        println!("Displaying frame {} ({:?})", video_frame.frame_number, std::time::Instant::now());
-                    } else {
+                        println!(
+                            "Displaying frame {} at {:.3}s ({} dropped)",
+                            video_frame.index,
+                            position.as_secs_f64(),
+                            self.presenter.dropped()
+                        );
+                    } else if self.presenter.current().is_none() {
                         // No frame available, show black screen
                         frame.fill(0);
                     }
@@ -276,6 +329,14 @@ impl ApplicationHandler for App {
                     }
 
                     event_loop.exit();
+                } else if let (PhysicalKey::Code(code), ElementState::Pressed) = (event.physical_key, event.state) {
+                    if let Some(action) = transport_action(code, self.clock.is_paused()) {
+                        match self.transport.apply(action, &mut self.presenter) {
+                            // the reader thread seeks and reports
+                            Some(seek) => self.seek_sender.send(seek).unwrap(),
+                            None => println!("{}", self.transport),
+                        }
+                    }
                 }
             }
             _ => {}
@@ -289,9 +350,50 @@ impl ApplicationHandler for App {
     }
 }
 
+// Keyboard transport; the arrow keys step a frame while paused and seek while playing
+fn transport_action(key: KeyCode, paused: bool) -> Option<Action> {
+    Some(match key {
+        KeyCode::Space => Action::TogglePause,
+        KeyCode::ArrowLeft if paused => Action::Step(-1),
+        KeyCode::ArrowRight if paused => Action::Step(1),
+        KeyCode::ArrowLeft | KeyCode::ArrowDown => Action::Seek(-1),
+        KeyCode::ArrowRight | KeyCode::ArrowUp => Action::Seek(1),
+        KeyCode::Comma => Action::Step(-1),
+        KeyCode::Period => Action::Step(1),
+        KeyCode::Home => Action::First,
+        KeyCode::End => Action::Last,
+        KeyCode::BracketLeft => Action::Slower,
+        KeyCode::BracketRight => Action::Faster,
+        KeyCode::Backspace => Action::NormalSpeed,
+        _ => return None,
+    })
+}
+
 fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
     error!("{method_name}() failed: {err}");
     for source in err.sources().skip(1) {
         error!("  Caused by: {source}");
     }
-}
\ No newline at end of file
+}
+
+// Open and start playing the --audio track, if any
+#[cfg(feature = "audio")]
+fn start_audio(options: &Options) -> Option<AudioPlayer> {
+    options.audio.as_ref().and_then(|path| {
+        let track = AudioTrack::open(path, options.audio_rate, options.audio_channels).unwrap_or_else(|e| {
+            eprintln!("Cannot open {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        println!("Audio: {:.1}s from {}", track.duration().as_secs_f64(), path.display());
+        match AudioPlayer::start(track) {
+            Ok(player) => {
+                println!("Audio: {}", player.description());
+                Some(player)
+            }
+            Err(e) => {
+                eprintln!("Cannot play audio, playing video only: {}", e);
+                None
+            }
+        }
+    })
+}
@@ -2,158 +2,201 @@ use std::num::NonZeroU32;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
-use winit::event::{Event, KeyEvent, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
+use raw_video::{Action, Clock, Converter, Options, Presenter, Seek, SystemClock, TimedFrame, Transport, VideoReader};
+#[cfg(feature = "audio")]
+use raw_video::{AudioPlayer, AudioTrack};
+use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopProxy};
 use winit::keyboard::{Key, NamedKey};
 
 #[path = "utils/winit_app.rs"]
 mod winit_app;
 
-// Video configuration constants
-const VIDEO_WIDTH: usize = 1920;
-const VIDEO_HEIGHT: usize = 1080;
-const FPS: u32 = 25;
-const FRAME_DURATION: Duration = Duration::from_millis(1000 / FPS as u64);
-
-// YUV420p frame size calculations
-const Y_SIZE: usize = VIDEO_WIDTH * VIDEO_HEIGHT;
-const U_SIZE: usize = (VIDEO_WIDTH / 2) * (VIDEO_HEIGHT / 2);
-const V_SIZE: usize = (VIDEO_WIDTH / 2) * (VIDEO_HEIGHT / 2);
-const FRAME_SIZE: usize = Y_SIZE + U_SIZE + V_SIZE;
-
-// RGBA buffer for display
+// 0x00RRGGBB buffer for display
 type RgbaBuffer = Vec<u32>;
 
 // Video frame data
-struct VideoFrame {
-    rgba_data: RgbaBuffer,
-    frame_number: usize,
-}
-
-// Convert YUV420p to RGBA
-fn yuv420p_to_rgba(y_data: &[u8], u_data: &[u8], v_data: &[u8]) -> RgbaBuffer {
-    let mut rgba = Vec::with_capacity(VIDEO_WIDTH * VIDEO_HEIGHT);
-
-    for y in 0..VIDEO_HEIGHT {
-        for x in 0..VIDEO_WIDTH {
-            let y_idx = y * VIDEO_WIDTH + x;
-            let u_idx = (y / 2) * (VIDEO_WIDTH / 2) + (x / 2);
-            let v_idx = (y / 2) * (VIDEO_WIDTH / 2) + (x / 2);
-
-            let y_val = y_data[y_idx] as i32;
-            let u_val = u_data[u_idx] as i32 - 128;
-            let v_val = v_data[v_idx] as i32 - 128;
-
-            // YUV to RGB conversion (BT.601 standard)
-            let r = (y_val as f32 + 1.402 * v_val as f32).clamp(0.0, 255.0) as u8;
-            let g = (y_val as f32 - 0.344 * u_val as f32 - 0.714 * v_val as f32).clamp(0.0, 255.0) as u8;
-            let b = (y_val as f32 + 1.772 * u_val as f32).clamp(0.0, 255.0) as u8;
-
-            // Pack into RGBA format (softbuffer expects 0xRRGGBBAA)
-            let rgba_pixel = (r as u32) | ((g as u32) << 8) | ((b as u32) << 16) | 0xFF000000;
-            rgba.push(rgba_pixel);
-        }
-    }
-
-    rgba
-}
+type VideoFrame = TimedFrame<RgbaBuffer>;
 
 // Function to run in separate thread for reading video frames
 fn video_reader_thread(
     frame_sender: crossbeam::channel::Sender<VideoFrame>,
+    seek_receiver: crossbeam::channel::Receiver<Seek>,
+    // Wakes the event loop for each frame sent
+    wake: EventLoopProxy<()>,
     stop_signal: Arc<Mutex<bool>>,
+    mut reader: VideoReader,
+    clock: Arc<dyn Clock>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::Read;
-
-    let mut file = File::open("out.yuv")?;
-    let mut frame_buffer = vec![0u8; FRAME_SIZE];
-    let mut frame_number = 0;
-
-    println!("Starting video playback: {}x{} @ {}fps", VIDEO_WIDTH, VIDEO_HEIGHT, FPS);
-
-    loop {
+    let format = reader.format();
+    // conversion is split into bands of rows, one per core
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let converter = Converter::new(&format, &reader.color()).with_threads(threads);
+    let frame_duration = format.frame_rate.frame_duration();
+    let mut frame_buffer = vec![0u8; format.frame_size()];
+    // Frames read after a seek carry its epoch
+    let mut epoch = 0;
+    let mut at_end = false;
+    let mut started = false;
+    let mut skipped = 0;
+
+    'frames: loop {
         // Check if we should stop
         if *stop_signal.lock().unwrap() {
             break;
         }
 
+        // Go where the last key pressed asks for; at the end of the file only a seek
+        // gets playback going again
+        let seek = match at_end {
+            true => match seek_receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(seek) => Some(seek),
+                Err(crossbeam::channel::RecvTimeoutError::Timeout) => continue,
+                Err(crossbeam::channel::RecvTimeoutError::Disconnected) => break,
+            },
+            false => None,
+        };
+        if let Some(seek) = seek.into_iter().chain(seek_receiver.try_iter()).last() {
+            let frame_number = reader.seek(seek.frame)?;
+            let timestamp = format.frame_rate.timestamp(frame_number);
+            println!("Seek to frame {} at {:.3}s", frame_number, timestamp.as_secs_f64());
+            clock.seek(timestamp);
+            epoch = seek.epoch;
+            at_end = false;
+        }
+
         // Read one frame
-        let bytes_read = file.read(&mut frame_buffer)?;
-        if bytes_read != FRAME_SIZE {
+        let frame_number = reader.position();
+        if !reader.read_frame(&mut frame_buffer)? {
             println!("End of video file reached after {} frames", frame_number);
-            break;
+            at_end = true;
+            continue;
         }
 
-        // Extract Y, U, V planes
-        let y_data = &frame_buffer[0..Y_SIZE];
-        let u_data = &frame_buffer[Y_SIZE..Y_SIZE + U_SIZE];
-        let v_data = &frame_buffer[Y_SIZE + U_SIZE..];
+        let timestamp = format.frame_rate.timestamp(frame_number);
 
-        // Convert to RGBA
-        let rgba_data = yuv420p_to_rgba(y_data, u_data, v_data);
+        // Too late to be shown at all, don't spend a conversion on it
+        if started && timestamp + frame_duration < clock.position() {
+            skipped += 1;
+            continue;
+        }
 
-        // Create video frame
-        let frame = VideoFrame {
-            rgba_data,
-            frame_number,
-        };
+        // Convert to RGB
+        let mut rgba_data = vec![0u32; format.width * format.height];
+        converter.to_xrgb(&frame_buffer, &mut rgba_data);
 
-        // Send frame to main thread (non-blocking)
-        if frame_sender.try_send(frame).is_err() {
-            // Main thread is not keeping up, skip this frame
-            println!("Warning: Frame {} dropped - main thread not keeping up", frame_number);
+        // Create video frame
+        let mut frame = Some(VideoFrame {
+            index: frame_number,
+            timestamp,
+            epoch,
+            frame: rgba_data,
+        });
+
+        // Send frame to main thread, the channel paces reading to the display
+        while let Some(pending) = frame.take() {
+            match frame_sender.send_timeout(pending, Duration::from_millis(20)) {
+                Ok(()) => {
+                    let _ = wake.send_event(());
+                }
+                Err(crossbeam::channel::SendTimeoutError::Timeout(pending)) => {
+                    if *stop_signal.lock().unwrap() {
+                        return Ok(());
+                    }
+                    // A seek makes the frame stale, it won't be shown
+                    if !seek_receiver.is_empty() {
+                        continue 'frames;
+                    }
+                    frame = Some(pending);
+                }
+                Err(crossbeam::channel::SendTimeoutError::Disconnected(_)) => return Ok(()),
+            }
         }
 
-        frame_number += 1;
-
-        // Sleep for frame duration
-        thread::sleep(FRAME_DURATION/2);
+        // The clock starts once there is something to show
+        if !started {
+            clock.set_paused(false);
+            started = true;
+        }
     }
 
-    println!("Video reader thread finished");
+    println!("Video reader thread finished, {} late frames skipped", skipped);
     Ok(())
 }
 
 #[cfg(not(target_os = "android"))]
 fn main() {
-    entry(EventLoop::new().unwrap())
+    let args: Vec<String> = std::env::args().collect();
+    let options = Options::parse(args[1..].iter().cloned(), &args[0], "out.yuv").unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    });
+    entry(EventLoop::new().unwrap(), options)
 }
 
-pub(crate) fn entry(event_loop: EventLoop<()>) {
+pub(crate) fn entry(event_loop: EventLoop<()>, options: Options) {
+    // a .y4m header overrides the geometry given on the command line
+    let reader = VideoReader::open(&options).unwrap_or_else(|e| {
+        eprintln!("Cannot open {}: {}", options.path.display(), e);
+        std::process::exit(1);
+    });
+    let format = reader.format();
+    match reader.y4m_header() {
+        Some(header) => println!("Starting video playback: {} from {}", header, options.path.display()),
+        None => println!("Starting video playback: {} from {}", format, options.path.display()),
+    }
+    if !format.pixel_format.is_rgb() {
+        println!("Colors: {}", reader.color());
+    }
+
+    // With an audio track its device is the clock the frames are presented against
+    #[cfg(feature = "audio")]
+    let audio = start_audio(&options);
+    #[cfg(feature = "audio")]
+    let clock: Arc<dyn Clock> = match &audio {
+        Some(player) => player.clock(),
+        None => Arc::new(SystemClock::new()),
+    };
+    #[cfg(not(feature = "audio"))]
+    let clock: Arc<dyn Clock> = {
+        if options.audio.is_some() {
+            eprintln!("Built without audio, playing video only; build with --features audio to play it");
+        }
+        Arc::new(SystemClock::new())
+    };
+
     let context = softbuffer::Context::new(event_loop.owned_display_handle()).unwrap();
 
     // Channel for sending video frames from reader thread to main thread
     let (frame_sender, frame_receiver) = crossbeam::channel::bounded(10); // Buffer 10 frames
+    // Channel for the seeks asked for from the keyboard
+    let (seek_sender, seek_receiver) = crossbeam::channel::unbounded();
+    let wake = event_loop.create_proxy();
 
     // Stop signal for the video reader thread
     let stop_signal = Arc::new(Mutex::new(false));
     let stop_signal_clone = stop_signal.clone();
+    let clock_clone = clock.clone();
 
     // Start video reader thread
     let video_thread = thread::spawn(move || {
-        if let Err(e) = video_reader_thread(frame_sender, stop_signal) {
+        if let Err(e) = video_reader_thread(frame_sender, seek_receiver, wake, stop_signal, reader, clock_clone) {
             eprintln!("Error in video reader thread: {}", e);
         }
     });
 
-    // Current frame data
-    let current_frame = Arc::new(Mutex::new(None::<VideoFrame>));
+    // Picks the frame due on the clock, the current one is repeated until the next is due
+    let mut presenter = Presenter::<RgbaBuffer>::new();
+    let mut transport = Transport::new(clock.clone(), format.frame_rate, options.seek_step);
 
     // Create a separate thread handle for cleanup
     let video_thread_handle = Arc::new(Mutex::new(Some(video_thread)));
 
-    // Timer for requesting redraws at video frame rate
-    let last_redraw = Arc::new(Mutex::new(Instant::now()));
-    let redraw_interval = Duration::from_millis(1000 / FPS as u64);
-
     let app = winit_app::WinitAppBuilder::with_init(
-        |elwt| winit_app::make_window(elwt, |w| w.with_inner_size(winit::dpi::LogicalSize::new(VIDEO_WIDTH as u32, VIDEO_HEIGHT as u32))),
+        move |elwt| winit_app::make_window(elwt, |w| w.with_inner_size(winit::dpi::LogicalSize::new(format.width as u32, format.height as u32))),
         move |_elwt, window| softbuffer::Surface::new(&context, window.clone()).unwrap(),
     )
     .with_event_handler(move |window, surface, event, elwt| {
-        elwt.set_control_flow(ControlFlow::Wait);
-
         match event {
             Event::WindowEvent {
                 window_id,
@@ -179,11 +222,6 @@ pub(crate) fn entry(event_loop: EventLoop<()>) {
                     return;
                 };
 
-                // Try to receive a new frame
-                if let Ok(frame) = frame_receiver.try_recv() {
-                    *current_frame.lock().unwrap() = Some(frame);
-                }
-
                 let size = window.inner_size();
                 if let (Some(width), Some(height)) =
                     (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
@@ -191,18 +229,18 @@ pub(crate) fn entry(event_loop: EventLoop<()>) {
                     let mut buffer = surface.buffer_mut().unwrap();
 
                     // Check if we have a frame to display
-                    if let Some(frame) = current_frame.lock().unwrap().as_ref() {
+                    if let Some(frame) = presenter.current() {
                         // Display video frame
-                        let frame_width = VIDEO_WIDTH.min(width.get() as usize);
-                        let frame_height = VIDEO_HEIGHT.min(height.get() as usize);
+                        let frame_width = format.width.min(width.get() as usize);
+                        let frame_height = format.height.min(height.get() as usize);
 
                         // Clear buffer first
                         buffer.fill(0);
 
                         // Copy frame data to buffer more efficiently
-                        let frame_data = &frame.rgba_data;
+                        let frame_data = &frame.frame;
                         for y in 0..frame_height {
-                            let frame_row_start = y * VIDEO_WIDTH;
+                            let frame_row_start = y * format.width;
                             let buffer_row_start = y * width.get() as usize;
 
                             for x in 0..frame_width {
@@ -216,7 +254,12 @@ pub(crate) fn entry(event_loop: EventLoop<()>) {
                         }
 
                         // Display frame info
-                        println!("Displaying frame {} ({:?})", frame.frame_number, std::time::Instant::now());
+                        println!(
+                            "Displaying frame {} at {:.3}s ({} dropped)",
+                            frame.index,
+                            clock.position().as_secs_f64(),
+                            presenter.dropped()
+                        );
                     } else {
                         // No frame available, show black screen
                         buffer.fill(0);
@@ -250,14 +293,46 @@ pub(crate) fn entry(event_loop: EventLoop<()>) {
 
                 elwt.exit();
             }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                logical_key,
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    },
+                window_id,
+            } if window_id == window.id() => {
+                if let Some(action) = transport_action(&logical_key, clock.is_paused()) {
+                    match transport.apply(action, &mut presenter) {
+                        // the reader thread seeks and reports
+                        Some(seek) => seek_sender.send(seek).unwrap(),
+                        None => println!("{}", transport),
+                    }
+                }
+            }
             Event::AboutToWait => {
-                // Check if it's time to request a redraw
-                let now = Instant::now();
-                if let Ok(mut last) = last_redraw.lock() {
-                    if now.duration_since(*last) >= redraw_interval {
-                        *last = now;
-                        window.request_redraw();
+                #[cfg(feature = "audio")]
+                if let Some(error) = audio.as_ref().and_then(AudioPlayer::take_error) {
+                    eprintln!("Audio error: {}", error);
+                }
+
+                // Redraw when a new frame is due
+                let position = clock.position();
+                if presenter.update(position, || frame_receiver.try_recv().ok()) {
+                    window.request_redraw();
+                }
+
+                // Wake up when the next frame is due; the reader thread wakes us when one arrives
+                match presenter.next_due() {
+                    Some(due) if !clock.is_paused() => {
+                        let wait = due.saturating_sub(position).div_f64(transport.speed());
+                        elwt.set_control_flow(ControlFlow::WaitUntil(Instant::now() + wait));
                     }
+                    _ => elwt.set_control_flow(ControlFlow::Wait),
                 }
             }
             _ => {}
@@ -265,4 +340,48 @@ pub(crate) fn entry(event_loop: EventLoop<()>) {
     });
 
     winit_app::run_app(event_loop, app);
-}
\ No newline at end of file
+}
+
+// Keyboard transport; the arrow keys step a frame while paused and seek while playing
+fn transport_action(key: &Key, paused: bool) -> Option<Action> {
+    Some(match key {
+        Key::Named(NamedKey::Space) => Action::TogglePause,
+        Key::Named(NamedKey::ArrowLeft) if paused => Action::Step(-1),
+        Key::Named(NamedKey::ArrowRight) if paused => Action::Step(1),
+        Key::Named(NamedKey::ArrowLeft | NamedKey::ArrowDown) => Action::Seek(-1),
+        Key::Named(NamedKey::ArrowRight | NamedKey::ArrowUp) => Action::Seek(1),
+        Key::Named(NamedKey::Home) => Action::First,
+        Key::Named(NamedKey::End) => Action::Last,
+        Key::Named(NamedKey::Backspace) => Action::NormalSpeed,
+        Key::Character(character) => match character.as_str() {
+            "," => Action::Step(-1),
+            "." => Action::Step(1),
+            "[" => Action::Slower,
+            "]" => Action::Faster,
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+// Open and start playing the --audio track, if any
+#[cfg(feature = "audio")]
+fn start_audio(options: &Options) -> Option<AudioPlayer> {
+    options.audio.as_ref().and_then(|path| {
+        let track = AudioTrack::open(path, options.audio_rate, options.audio_channels).unwrap_or_else(|e| {
+            eprintln!("Cannot open {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        println!("Audio: {:.1}s from {}", track.duration().as_secs_f64(), path.display());
+        match AudioPlayer::start(track) {
+            Ok(player) => {
+                println!("Audio: {}", player.description());
+                Some(player)
+            }
+            Err(e) => {
+                eprintln!("Cannot play audio, playing video only: {}", e);
+                None
+            }
+        }
+    })
+}